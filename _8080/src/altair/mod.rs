@@ -0,0 +1,197 @@
+//! The MITS Altair 8800, with an 88-2SIO serial board for its terminal and a front panel that
+//! is worked from the host.
+//!
+//! The console is the first port of the 88-2SIO, an MC6850 ACIA at ports 0x10 (control and
+//! status) and 0x11 (data), which is where Altair BASIC and most other software for the machine
+//! look for their terminal. The panel's sixteen address switches are read by the program as the
+//! sense switches: `IN 0FFH` returns the upper eight.
+//!
+//! The panel works as the real one does. While the machine is stopped, `examine` and `deposit`
+//! (and their `_next` forms) move the program counter and read and write memory through it, and
+//! `single_step` executes one instruction; `run` sets it going from the program counter and
+//! `stop` stops it. A running machine only advances when the host gives it time with
+//! `run_slice`, which executes instructions for a number of processor cycles. The serial board
+//! is clocked from the machine's clock by a scheduled event, which passes its interrupts to the
+//! processor; RUN or SINGLE STEP starts it, after which `run_for` and the other run loops keep
+//! the board going as well. Programs such as BASIC are loaded from their image files with
+//! `load_file`.
+
+use crate::prelude::*;
+use crate::{MemoryMap, PortBus, device::Mc6850, image::{Format, Loaded}};
+use crate::chip::access::Internal::ProgramCounter;
+use core::cell::{RefCell, RefMut};
+use crate::rc::Rc;
+extern crate std;
+use std::{io::{self, Read, Write}, path::Path};
+
+/// The processor clock in Hz.
+pub const CLOCK: usize = 2_000_000;
+/// The first port of the console ACIA.
+pub const CONSOLE: raw::u8 = 0x10;
+/// The port that reads the sense switches.
+pub const SENSE: raw::u8 = 0xFF;
+/// The processor cycles in each cycle of the ACIA's clock, for 9600 baud at its ÷16 setting.
+const SERIAL_CLOCK: usize = 13;
+/// How often the serial board is clocked: once a bit at 9600 baud.
+const SERIAL_PERIOD: u64 = SERIAL_CLOCK as u64 * 16;
+
+/// What the lights on the front panel show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lights {
+    /// The address lights, which show the program counter.
+    pub address: raw::u16,
+    /// The data lights, which show the byte at the program counter.
+    pub data: raw::u8,
+    /// INTE, lit while the processor accepts interrupts.
+    pub interrupts: bool,
+    /// WAIT, lit while the machine is stopped.
+    pub wait: bool,
+    /// HLTA, lit while the processor is halted.
+    pub halted: bool,
+}
+
+/// An Altair 8800, as a Harness.
+pub struct Altair<R: Read + 'static, W: Write + 'static> {
+    bus: PortBus<MemoryMap>,
+    console: Rc<RefCell<Mc6850<R, W>>>,
+    switches: raw::u16,
+    running: bool,
+    /// Whether the event that clocks the serial board has been scheduled.
+    clocked: bool,
+}
+
+impl<R: Read + 'static, W: Write + 'static> Altair<R, W> {
+    /// Creates a stopped machine with 64K of RAM, whose terminal reads from `input` and writes
+    /// to `output` at 9600 baud.
+    pub fn new(input: R, output: W) -> Self { Self::with_memory(MemoryMap::new().ram(..), input, output) }
+
+    /// Creates a stopped machine with the memory boards in `memory`, whose terminal reads from
+    /// `input` and writes to `output` at 9600 baud.
+    pub fn with_memory(memory: MemoryMap, input: R, output: W) -> Self {
+        let console = Rc::new(RefCell::new(Mc6850::new(input, output, SERIAL_CLOCK)));
+        Self { bus: PortBus::new(memory).attach(CONSOLE, 0xFE, console.clone()), console, switches: 0, running: false, clocked: false }
+    }
+
+    /// The console ACIA, for reaching the streams behind it.
+    pub fn console(&self) -> RefMut<'_, Mc6850<R, W>> { RefCell::borrow_mut(&self.console) }
+
+    /// The positions of the sixteen address switches, up for 1.
+    pub fn switches(&self) -> raw::u16 { self.switches }
+
+    /// Sets the address switches; the upper eight are the sense switches.
+    pub fn set_switches(&mut self, switches: raw::u16) { self.switches = switches; }
+
+    /// Whether the machine is running.
+    pub fn running(&self) -> bool { self.running }
+}
+
+impl<R: Read + 'static, W: Write + 'static> Harness for Altair<R, W> {
+    fn read(&self, from: u16) -> u8 { self.bus.read(from) }
+    fn write(&mut self, to: u16, value: u8) { self.bus.write(to, value) }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        match port {
+            SENSE => Wrapping((self.switches >> 8) as raw::u8),
+            _ => self.bus.input(port),
+        }
+    }
+    fn output(&mut self, port: raw::u8, value: u8) { self.bus.output(port, value) }
+    fn bus_cycle(&mut self, cycle: crate::BusCycle) -> raw::u8 { self.bus.bus_cycle(cycle) }
+    #[cfg(feature="open")]
+    fn did_execute(&mut self, client: &crate::chip::State, did: crate::chip::opcode::Op) -> Result<Option<crate::chip::opcode::Op>, String> {
+        self.bus.did_execute(client, did)
+    }
+}
+
+impl<R: Read + 'static, W: Write + 'static, C: BorrowMut<Altair<R, W>>> Machine<Altair<R, W>, C> {
+    /// Starts clocking the serial board from the machine's clock, unless it already is.
+    fn power(&mut self) {
+        if self.clocked { return; }
+        self.clocked = true;
+        self.clock_bus(SERIAL_PERIOD, |altair| &mut altair.bus);
+    }
+
+    /// Loads an image file in any of the formats `image::load` understands, leaving the
+    /// program counter at its entry point, ready to RUN.
+    pub fn load_file(&mut self, path: impl AsRef<Path>, format: Format, origin: raw::u16) -> io::Result<Loaded> {
+        let image = std::fs::read(path)?;
+        self.load(format, &image, origin, None).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// The byte at the program counter.
+    fn current(&self) -> raw::u8 { self.read(self.chip[ProgramCounter]).0 }
+
+    /// EXAMINE: while stopped, moves the program counter to `address`. Returns the byte there.
+    pub fn examine(&mut self, address: raw::u16) -> raw::u8 {
+        if !self.running { self.chip[ProgramCounter] = Wrapping(address); }
+        self.current()
+    }
+
+    /// EXAMINE NEXT: while stopped, moves the program counter on by one. Returns the byte there.
+    pub fn examine_next(&mut self) -> raw::u8 {
+        if !self.running { self.chip[ProgramCounter] += 1; }
+        self.current()
+    }
+
+    /// DEPOSIT: while stopped, writes a byte at the program counter.
+    pub fn deposit(&mut self, value: raw::u8) {
+        if !self.running {
+            let address = self.chip[ProgramCounter];
+            self.write(address, Wrapping(value));
+        }
+    }
+
+    /// DEPOSIT NEXT: while stopped, moves the program counter on by one and writes a byte there.
+    pub fn deposit_next(&mut self, value: raw::u8) {
+        if !self.running {
+            self.chip[ProgramCounter] += 1;
+            self.deposit(value);
+        }
+    }
+
+    /// SINGLE STEP: while stopped, executes one instruction. Returns the cycles it took, or 0
+    /// if the machine is running.
+    pub fn single_step(&mut self) -> usize {
+        if self.running { return 0; }
+        self.power();
+        self.run_for(1).cycles
+    }
+
+    /// RUN: sets the machine running from the program counter.
+    pub fn run(&mut self) {
+        self.power();
+        self.running = true;
+    }
+
+    /// STOP: stops the machine after the current instruction.
+    pub fn stop(&mut self) { self.running = false; }
+
+    /// RESET: clears the processor, which starts again from address 0, and resets the serial
+    /// board and the machine's clock. Registers other than the program counter aren't defined
+    /// after a reset on the 8080, and here they are cleared too.
+    pub fn reset(&mut self) {
+        self.chip = crate::chip::State::new();
+        self.bus.reset();
+        self.set_clock(0);
+    }
+
+    /// While the machine is running, executes instructions with `run_for` until at least
+    /// `cycles` processor cycles have passed. Returns the cycles that passed, which are 0 if it
+    /// is stopped.
+    pub fn run_slice(&mut self, cycles: usize) -> usize {
+        if self.running { self.run_for(cycles).cycles } else { 0 }
+    }
+
+    /// What the panel's lights show.
+    pub fn lights(&self) -> Lights {
+        Lights {
+            address: self.chip[ProgramCounter].0,
+            data: self.current(),
+            interrupts: self.chip.is_interrupt_ready(),
+            wait: !self.running,
+            halted: self.chip.is_stopped(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
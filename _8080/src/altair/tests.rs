@@ -0,0 +1,73 @@
+use super::*;
+use crate::{assembler::assemble, vec::Vec};
+extern crate std;
+
+type Panel = Machine<Altair<&'static [raw::u8], Vec<raw::u8>>, Altair<&'static [raw::u8], Vec<raw::u8>>>;
+
+#[test]
+fn panel() {
+    let mut machine: Panel = Machine::new(Altair::new(&[], Vec::new()));
+    assert_eq!(machine.examine(0), 0);
+    machine.deposit(0x3E);
+    for byte in [0x2A, 0x32, 0x00, 0x01, 0x76] { machine.deposit_next(byte); }
+    assert_eq!(machine.examine(0), 0x3E);
+    assert_eq!(machine.examine_next(), 0x2A);
+    machine.examine(0);
+    assert_eq!(machine.single_step(), 7);
+    assert_eq!(machine.lights(), Lights { address: 2, data: 0x32, interrupts: false, wait: true, halted: false });
+    assert_eq!(machine.run_slice(100), 0);
+
+    machine.run();
+    assert_eq!(machine.single_step(), 0);
+    assert!(machine.run_slice(100) >= 100);
+    assert_eq!(machine.examine(0x0100), 0);
+    machine.deposit(0);
+    assert_eq!(machine.read(Wrapping(0x0100)).0, 0x2A);
+    machine.stop();
+    let lights = machine.lights();
+    assert!(lights.wait && lights.halted);
+    assert_eq!(lights.address, 6);
+
+    machine.reset();
+    assert_eq!(machine.lights(), Lights { address: 0, data: 0x3E, interrupts: false, wait: true, halted: false });
+    assert_eq!(machine.clock(), 0);
+}
+
+#[test]
+fn terminal() {
+    let program = assemble("
+        MVI     A,3
+        OUT     10H
+        MVI     A,15H
+        OUT     10H
+        IN      0FFH
+        CALL    PUT
+LOOP:   IN      10H
+        RRC
+        JNC     LOOP
+        IN      11H
+        ANI     0DFH
+        CALL    PUT
+        JMP     LOOP
+PUT:    MOV     B,A
+WAIT:   IN      10H
+        ANI     2
+        JZ      WAIT
+        MOV     A,B
+        OUT     11H
+        RET
+    ").unwrap();
+    let mut machine: Panel = Machine::new(Altair::new(b"abc", Vec::new()));
+    let path = std::env::temp_dir().join("lemurs-altair-terminal.bin");
+    std::fs::write(&path, &program.image).unwrap();
+    let loaded = machine.load_file(&path, Format::Binary, 0).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.entry, Some(0));
+    machine.set_switches(0x2100);
+    machine.run();
+    machine.run_slice(1_000);
+    assert_eq!(machine.console().get_mut().1, b"");
+    machine.run_slice(20_000);
+    assert_eq!(machine.console().get_mut().1, b"!ABC");
+    assert_eq!(machine.switches(), 0x2100);
+}
@@ -0,0 +1,212 @@
+use crate::prelude::{*, vec::Vec, string::ToString};
+use super::ErrorKind;
+
+/// One lexical element of an operand field.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Token {
+    Number(i32),
+    Name(String),
+    Text(Vec<raw::u8>),
+    Here,
+    Punct(char),
+}
+
+pub(super) fn is_name_start(c: char) -> bool { c.is_ascii_alphabetic() || "_?@.".contains(c) }
+pub(super) fn is_name_part(c: char) -> bool { c.is_ascii_alphanumeric() || "_?@.$".contains(c) }
+
+/// Reads a numeric constant in Intel notation: a decimal number by default, or one with a radix
+/// suffix of `H` (hexadecimal), `B` (binary), `O` or `Q` (octal) or `D` (decimal).
+fn number(text: &str) -> Result<i32, ErrorKind> {
+    let upper = text.to_ascii_uppercase();
+    let (digits, radix) = match upper.as_bytes().last() {
+        Some(b'H') => (&upper[..upper.len() - 1], 16),
+        Some(b'B') => (&upper[..upper.len() - 1], 2),
+        Some(b'O' | b'Q') => (&upper[..upper.len() - 1], 8),
+        Some(b'D') => (&upper[..upper.len() - 1], 10),
+        _ => (&upper[..], 10),
+    };
+    i64::from_str_radix(digits, radix).ok()
+        .filter(|value| *value <= 0xFFFF)
+        .map(|value| value as i32)
+        .ok_or_else(|| ErrorKind::BadNumber(text.to_string()))
+}
+
+/// Splits an operand into tokens. Quoted text uses either `'` or `"`, with a doubled quote
+/// standing for the quote character itself.
+pub(super) fn tokenize(text: &str) -> Result<Vec<Token>, ErrorKind> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '\'' | '"' => {
+                let mut body = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => match chars.peek() {
+                            Some(&(_, again)) if again == c => { chars.next(); body.push(c as raw::u8); }
+                            _ => break,
+                        }
+                        Some((_, ch)) => body.push(ch as raw::u8),
+                        None => return Err(ErrorKind::Syntax(text.to_string())),
+                    }
+                }
+                tokens.push(Token::Text(body));
+            }
+            '$' if !chars.peek().is_some_and(|&(_, next)| is_name_part(next)) => tokens.push(Token::Here),
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some(&(at, next)) = chars.peek() {
+                    if !next.is_ascii_alphanumeric() { break; }
+                    end = at + next.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Number(number(&text[start..end])?));
+            }
+            c if is_name_start(c) || c == '$' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(at, next)) = chars.peek() {
+                    if !is_name_part(next) { break; }
+                    end = at + next.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Name(text[start..end].to_ascii_uppercase()));
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' => tokens.push(Token::Punct(c)),
+            _ => return Err(ErrorKind::Syntax(text.to_string())),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Supplies the values an expression can refer to.
+pub(super) trait Scope {
+    /// The address of the statement being assembled, written as `$`.
+    fn here(&self) -> raw::u16;
+    /// The value of a symbol; this can report a placeholder instead of failing while
+    /// forward references are still unknown.
+    fn value(&self, name: &str) -> Result<i32, ErrorKind>;
+}
+
+/// How deeply parentheses and prefix operators can nest in one expression.
+const DEPTH: usize = 64;
+
+/// Binary operators from lowest to highest precedence.
+const LEVELS: [&[&str]; 5] = [
+    &["OR", "XOR"],
+    &["AND"],
+    &["EQ", "NE", "LT", "LE", "GT", "GE"],
+    &["+", "-"],
+    &["*", "/", "MOD", "SHL", "SHR"],
+];
+
+struct Parser<'a, S: Scope + ?Sized> {
+    tokens: &'a [Token],
+    at: usize,
+    depth: usize,
+    scope: &'a S,
+}
+
+impl<S: Scope + ?Sized> Parser<'_, S> {
+    fn peek_operator(&self) -> Option<&str> {
+        match self.tokens.get(self.at)? {
+            Token::Name(name) => Some(name),
+            Token::Punct('+') => Some("+"),
+            Token::Punct('-') => Some("-"),
+            Token::Punct('*') => Some("*"),
+            Token::Punct('/') => Some("/"),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i32, ErrorKind> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        if level == 2 && matches!(self.tokens.get(self.at), Some(Token::Name(name)) if name == "NOT") {
+            self.at += 1;
+            return self.nested(|parser| parser.binary(level)).map(|value| !value & 0xFFFF);
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.peek_operator().filter(|op| LEVELS[level].contains(op)) {
+            let op = op.to_string();
+            self.at += 1;
+            let right = self.binary(level + 1)?;
+            let truth = |test: bool| if test { -1 } else { 0 };
+            let (l, r) = (left as raw::u16, right as raw::u16);
+            left = 0xFFFF & match op.as_str() {
+                "OR" => left | right,
+                "XOR" => left ^ right,
+                "AND" => left & right,
+                "EQ" => truth(l == r),
+                "NE" => truth(l != r),
+                "LT" => truth(l < r),
+                "LE" => truth(l <= r),
+                "GT" => truth(l > r),
+                "GE" => truth(l >= r),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" | "MOD" if r == 0 => return Err(ErrorKind::DivideByZero),
+                "/" => (l / r) as i32,
+                "MOD" => (l % r) as i32,
+                "SHL" => ((l as u32) << (r as u32).min(16)) as i32,
+                "SHR" => ((l as u32) >> (r as u32).min(16)) as i32,
+                _ => unreachable!(),
+            };
+        }
+        Ok(left)
+    }
+
+    /// Parses a nested part of the expression, failing once nesting goes past `DEPTH`.
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<i32, ErrorKind>) -> Result<i32, ErrorKind> {
+        if self.depth == DEPTH { return Err(ErrorKind::Nesting); }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn unary(&mut self) -> Result<i32, ErrorKind> {
+        let token = self.tokens.get(self.at).ok_or(ErrorKind::MissingOperand)?;
+        self.at += 1;
+        match token {
+            Token::Punct('-') => Ok(self.nested(Self::unary)?.wrapping_neg() & 0xFFFF),
+            Token::Punct('+') => self.nested(Self::unary),
+            Token::Name(name) if name == "HIGH" => Ok(self.nested(Self::unary)? >> 8 & 0xFF),
+            Token::Name(name) if name == "LOW" => Ok(self.nested(Self::unary)? & 0xFF),
+            Token::Punct('(') => {
+                let value = self.nested(|parser| parser.binary(0))?;
+                match self.tokens.get(self.at) {
+                    Some(Token::Punct(')')) => { self.at += 1; Ok(value) }
+                    _ => Err(ErrorKind::Syntax(")".to_string())),
+                }
+            }
+            Token::Number(value) => Ok(*value),
+            Token::Here => Ok(self.scope.here() as i32),
+            Token::Text(text) => match text[..] {
+                [c] => Ok(c as i32),
+                [hi, lo] => Ok(raw::u16::from_be_bytes([hi, lo]) as i32),
+                _ => Err(ErrorKind::BadOperand(String::from_utf8_lossy(text).into_owned())),
+            },
+            Token::Name(name) => self.scope.value(name),
+            Token::Punct(c) => Err(ErrorKind::Syntax(c.to_string())),
+        }
+    }
+}
+
+/// Evaluates an operand as an expression, giving a value from 0 to 0FFFFH. Arithmetic wraps at
+/// 16 bits, so a negative number stands for its two's complement; comparisons yield
+/// `0FFFFH` for true and `0` for false, as in Intel's assemblers.
+pub(super) fn evaluate<S: Scope + ?Sized>(text: &str, scope: &S) -> Result<i32, ErrorKind> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err(ErrorKind::MissingOperand);
+    }
+    let mut parser = Parser { tokens: &tokens, at: 0, depth: 0, scope };
+    let value = parser.binary(0)?;
+    match parser.tokens.get(parser.at) {
+        None => Ok(value),
+        Some(_) => Err(ErrorKind::Syntax(text.to_string())),
+    }
+}
@@ -0,0 +1,163 @@
+use crate::prelude::{*, string::ToString};
+use crate::chip::{access::{*, Double::*, Internal::*, Word::*}, opcode::{Op, Op::*, Test, Flag::*}};
+use super::{ErrorKind, expression::{evaluate, Scope}};
+
+fn register(text: &str) -> Result<Byte, ErrorKind> {
+    Ok(match text.trim().to_ascii_uppercase().as_str() {
+        "A" => Byte::Single(Register::A),
+        "B" => Byte::Single(Register::B),
+        "C" => Byte::Single(Register::C),
+        "D" => Byte::Single(Register::D),
+        "E" => Byte::Single(Register::E),
+        "H" => Byte::Single(Register::H),
+        "L" => Byte::Single(Register::L),
+        "M" => Byte::Indirect,
+        _ => return Err(ErrorKind::BadOperand(text.trim().to_string())),
+    })
+}
+
+fn pair(text: &str) -> Result<Double, ErrorKind> {
+    Ok(match text.trim().to_ascii_uppercase().as_str() {
+        "B" | "BC" => BC,
+        "D" | "DE" => DE,
+        "H" | "HL" => HL,
+        _ => return Err(ErrorKind::BadOperand(text.trim().to_string())),
+    })
+}
+
+fn wide(text: &str) -> Result<Internal, ErrorKind> {
+    match text.trim().to_ascii_uppercase().as_str() {
+        "SP" => Ok(StackPointer),
+        _ => pair(text).map(Wide),
+    }
+}
+
+fn stacked(text: &str) -> Result<Word, ErrorKind> {
+    match text.trim().to_ascii_uppercase().as_str() {
+        "PSW" => Ok(ProgramStatus),
+        _ => pair(text).map(|pair| OnBoard(Wide(pair))),
+    }
+}
+
+fn condition(code: &str) -> Option<Test> {
+    Some(match code {
+        "NZ" => Test::Not(Zero),
+        "Z" => Test::Is(Zero),
+        "NC" => Test::Not(Carry),
+        "C" => Test::Is(Carry),
+        "PO" => Test::Not(EvenParity),
+        "PE" => Test::Is(EvenParity),
+        "P" => Test::Not(Negative),
+        "M" => Test::Is(Negative),
+        _ => return None,
+    })
+}
+
+/// Evaluates an operand that must fit in a byte; values whose high byte is 0FFH, such as
+/// negative values down to -256, are accepted as their two's-complement form.
+pub(super) fn byte<S: Scope + ?Sized>(text: &str, scope: &S) -> Result<raw::u8, ErrorKind> {
+    let value = evaluate(text, scope)?;
+    match value {
+        0..=0xFF | 0xFF00..=0xFFFF => Ok(value as raw::u8),
+        _ => Err(ErrorKind::OutOfRange(value)),
+    }
+}
+
+/// Evaluates an operand as a 16-bit word.
+pub(super) fn word<S: Scope + ?Sized>(text: &str, scope: &S) -> Result<raw::u16, ErrorKind> {
+    evaluate(text, scope).map(|value| value as raw::u16)
+}
+
+/// Builds the operation named by an Intel 8080 mnemonic from its operand fields, or returns
+/// `None` if the mnemonic isn't an instruction.
+pub(super) fn build<S: Scope + ?Sized>(mnemonic: &str, operands: &[&str], scope: &S) -> Option<Result<Op, ErrorKind>> {
+    let arity = match mnemonic {
+        "MOV" | "MVI" | "LXI" => 2,
+        "NOP" | "HLT" | "RET" | "XCHG" | "XTHL" | "PCHL" | "SPHL" | "EI" | "DI" | "STC" | "CMC" |
+        "CMA" | "DAA" | "RLC" | "RRC" | "RAL" | "RAR" => 0,
+        code if code.len() > 1 && code.starts_with('R') && condition(&code[1..]).is_some() => 0,
+        _ => 1,
+    };
+    let arguments = || if operands.len() == arity { Ok(operands) } else { Err(ErrorKind::Operands(mnemonic.to_string())) };
+    let byte = |text: &str| byte(text, scope).map(Wrapping);
+    let word = |text: &str| word(text, scope).map(Wrapping);
+    let op = |build: &dyn Fn(&[&str]) -> Result<Op, ErrorKind>| Some(arguments().and_then(build));
+    match mnemonic {
+        "NOP" => op(&|_| Ok(NOP(4))),
+        "HLT" => op(&|_| Ok(Halt)),
+        "RET" => op(&|_| Ok(Return)),
+        "XCHG" => op(&|_| Ok(ExchangeDoubleWithHilo)),
+        "XTHL" => op(&|_| Ok(ExchangeTopWithHilo)),
+        "PCHL" => op(&|_| Ok(ProgramCounterFromHilo)),
+        "SPHL" => op(&|_| Ok(StackPointerFromHilo)),
+        "EI" => op(&|_| Ok(Interrupts(true))),
+        "DI" => op(&|_| Ok(Interrupts(false))),
+        "STC" => op(&|_| Ok(CarryFlag(true))),
+        "CMC" => op(&|_| Ok(CarryFlag(false))),
+        "CMA" => op(&|_| Ok(ComplementAccumulator)),
+        "DAA" => op(&|_| Ok(DecimalAddAdjust)),
+        "RLC" => op(&|_| Ok(RotateLeftCarrying)),
+        "RRC" => op(&|_| Ok(RotateRightCarrying)),
+        "RAL" => op(&|_| Ok(RotateAccumulatorLeft)),
+        "RAR" => op(&|_| Ok(RotateAccumulatorRight)),
+        "MOV" => op(&|args| match (register(args[0])?, register(args[1])?) {
+            (Byte::Indirect, Byte::Indirect) => Err(ErrorKind::BadOperand(args[1].trim().to_string())),
+            (to, from) => Ok(Move { to, from }),
+        }),
+        "MVI" => op(&|args| Ok(MoveData { to: register(args[0])?, value: byte(args[1])? })),
+        "LXI" => op(&|args| Ok(LoadExtendedWith { to: wide(args[0])?, value: word(args[1])? })),
+        "ADD" => op(&|args| Ok(Add { from: register(args[0])?, carry: false })),
+        "ADC" => op(&|args| Ok(Add { from: register(args[0])?, carry: true })),
+        "SUB" => op(&|args| Ok(Subtract { from: register(args[0])?, carry: false })),
+        "SBB" => op(&|args| Ok(Subtract { from: register(args[0])?, carry: true })),
+        "ANA" => op(&|args| Ok(And { from: register(args[0])? })),
+        "XRA" => op(&|args| Ok(ExclusiveOr { from: register(args[0])? })),
+        "ORA" => op(&|args| Ok(Or { from: register(args[0])? })),
+        "CMP" => op(&|args| Ok(Compare { from: register(args[0])? })),
+        "ADI" => op(&|args| Ok(AddTo { value: byte(args[0])?, carry: false })),
+        "ACI" => op(&|args| Ok(AddTo { value: byte(args[0])?, carry: true })),
+        "SUI" => op(&|args| Ok(SubtractBy { value: byte(args[0])?, carry: false })),
+        "SBI" => op(&|args| Ok(SubtractBy { value: byte(args[0])?, carry: true })),
+        "ANI" => op(&|args| Ok(AndWith { value: byte(args[0])? })),
+        "XRI" => op(&|args| Ok(ExclusiveOrWith { value: byte(args[0])? })),
+        "ORI" => op(&|args| Ok(OrWith { value: byte(args[0])? })),
+        "CPI" => op(&|args| Ok(CompareWith { value: byte(args[0])? })),
+        "INR" => op(&|args| Ok(IncrementByte { register: register(args[0])? })),
+        "DCR" => op(&|args| Ok(DecrementByte { register: register(args[0])? })),
+        "INX" => op(&|args| Ok(IncrementWord { register: wide(args[0])? })),
+        "DCX" => op(&|args| Ok(DecrementWord { register: wide(args[0])? })),
+        "DAD" => op(&|args| Ok(DoubleAdd { register: wide(args[0])? })),
+        "LDAX" => op(&|args| match pair(args[0])? {
+            HL => Err(ErrorKind::BadOperand(args[0].trim().to_string())),
+            register => Ok(LoadAccumulatorIndirect { register }),
+        }),
+        "STAX" => op(&|args| match pair(args[0])? {
+            HL => Err(ErrorKind::BadOperand(args[0].trim().to_string())),
+            register => Ok(StoreAccumulatorIndirect { register }),
+        }),
+        "PUSH" => op(&|args| Ok(Push(stacked(args[0])?))),
+        "POP" => op(&|args| Ok(Pop(stacked(args[0])?))),
+        "LDA" => op(&|args| Ok(LoadAccumulator { address: word(args[0])? })),
+        "STA" => op(&|args| Ok(StoreAccumulator { address: word(args[0])? })),
+        "LHLD" => op(&|args| Ok(LoadHilo { address: word(args[0])? })),
+        "SHLD" => op(&|args| Ok(StoreHilo { address: word(args[0])? })),
+        "JMP" => op(&|args| Ok(Jump { to: word(args[0])? })),
+        "CALL" => op(&|args| Ok(Call { sub: word(args[0])? })),
+        "IN" => op(&|args| Ok(In(byte(args[0])?.0))),
+        "OUT" => op(&|args| Ok(Out(byte(args[0])?.0))),
+        "RST" => op(&|args| match byte(args[0])?.0 {
+            vector @ 0..=7 => Ok(Reset { vector }),
+            vector => Err(ErrorKind::OutOfRange(vector as i32)),
+        }),
+        _ => {
+            let (kind, code) = mnemonic.split_at(1);
+            let test = condition(code)?;
+            match kind {
+                "J" => op(&|args| Ok(JumpIf(test, word(args[0])?))),
+                "C" => op(&|args| Ok(CallIf(test, word(args[0])?))),
+                "R" => op(&|_| Ok(ReturnIf(test))),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,623 @@
+//! A two-pass assembler for Intel 8080 source text.
+//!
+//! The assembler accepts the usual Intel syntax: one statement per line, made of an optional
+//! label (followed by a colon, or starting in the first column), an instruction or directive,
+//! its comma-separated operands and an optional comment introduced by `;`. Operands can be
+//! expressions over numbers (`10`, `0FFH`, `1010B`, `17Q`), quoted characters, symbols and
+//! the location counter `$`, combined with `+ - * /`, `MOD`, `SHL`, `SHR`, `NOT`, `AND`, `OR`,
+//! `XOR`, `HIGH`, `LOW` and the comparisons `EQ`, `NE`, `LT`, `LE`, `GT` and `GE`.
+//!
+//! Besides the 8080 instructions, it understands the `ORG`, `EQU`, `SET`, `DB`, `DW`, `DS` and
+//! `END` directives. Instructions are built as `Op` values and encoded with the same opcode
+//! tables the emulator uses to decode them.
+//!
+//! It also follows MAC and M80 in supporting macros (`MACRO` ... `ENDM`, with `LOCAL` names,
+//! `&` to join parameters to other text and `EXITM`), repeat blocks (`REPT` ... `ENDM`),
+//! conditional assembly (`IF`, `IFE`, `IFDEF` and `IFNDEF`, with `ELSE` and `ENDIF`) and
+//! `INCLUDE`, which reads files through `Assembler::includes`.
+
+use crate::prelude::{*, vec::Vec, collections::{BTreeMap, BTreeSet}, rc::Rc, string::ToString, fmt::{self, Display, Formatter}};
+use crate::chip::opcode::Op;
+use core::{cell::Cell, fmt::Write, iter};
+#[cfg(feature="std")]
+extern crate std;
+
+mod expression;
+mod instruction;
+mod source;
+
+use expression::{evaluate, tokenize, Scope, Token};
+use source::{argument, substitute, Macro, Reader};
+
+/// The reasons a statement can fail to assemble.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// The statement or operand couldn't be parsed.
+    Syntax(String),
+    /// A numeric constant had digits that don't belong to its radix, or didn't fit in 16 bits.
+    BadNumber(String),
+    /// An operand isn't valid for the instruction, such as `LDAX H` or an unknown register.
+    BadOperand(String),
+    /// The instruction or directive was given the wrong number of operands.
+    Operands(String),
+    /// An expression was expected but the operand was empty.
+    MissingOperand,
+    /// The operation field didn't name an instruction or directive.
+    UnknownOperation(String),
+    /// A symbol was used but never defined.
+    Undefined(String),
+    /// A label or `EQU` symbol was defined more than once.
+    Duplicate(String),
+    /// A directive that needs a name (such as `EQU`) had no label.
+    MissingName(String),
+    /// An operand that decides the layout of the program (as for `ORG` or `DS`) used a symbol
+    /// that wasn't defined yet.
+    Forward(String),
+    /// A value didn't fit in the field it was used for.
+    OutOfRange(i32),
+    /// An expression divided by zero.
+    DivideByZero,
+    /// A block directive (`MACRO`, `REPT` or `IF`) wasn't closed before its source ended.
+    Unterminated(String),
+    /// A directive that closes or continues a block (`ENDM`, `ELSE`, `ENDIF` or `EXITM`)
+    /// appeared outside of one.
+    Unmatched(String),
+    /// An `INCLUDE` named a file that couldn't be read.
+    Include(String),
+    /// Macro expansions or included files nested too deeply, as when one expands itself
+    /// without an `IF` to stop it, or an expression's parentheses or prefix operators did.
+    Nesting,
+    /// Code or data ran past the end of memory at 0FFFFH.
+    PastEnd,
+}
+
+/// An assembly failure, along with the (1-based) line of source it occurred on. Errors in an
+/// included file name that file; errors inside a macro expansion are reported at the line
+/// that invoked the macro.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub file: Option<String>,
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(text) => write!(f, "syntax error at `{text}`"),
+            Self::BadNumber(text) => write!(f, "invalid number `{text}`"),
+            Self::BadOperand(text) => write!(f, "invalid operand `{text}`"),
+            Self::Operands(name) => write!(f, "wrong number of operands for {name}"),
+            Self::MissingOperand => write!(f, "missing operand"),
+            Self::UnknownOperation(name) => write!(f, "unknown instruction or directive `{name}`"),
+            Self::Undefined(name) => write!(f, "undefined symbol `{name}`"),
+            Self::Duplicate(name) => write!(f, "symbol `{name}` defined more than once"),
+            Self::MissingName(name) => write!(f, "{name} needs a label"),
+            Self::Forward(text) => write!(f, "`{text}` must be defined before it is used here"),
+            Self::OutOfRange(value) => write!(f, "value {value} out of range"),
+            Self::DivideByZero => write!(f, "division by zero"),
+            Self::Unterminated(name) => write!(f, "{name} block is never closed"),
+            Self::Unmatched(name) => write!(f, "{name} without a matching block"),
+            Self::Include(name) => write!(f, "can't include `{name}`"),
+            Self::Nesting => write!(f, "macros, includes or expressions nested too deeply"),
+            Self::PastEnd => write!(f, "code or data runs past 0FFFFH"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{file}, line {}: {}", self.line, self.kind),
+            None => write!(f, "line {}: {}", self.line, self.kind),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The product of a successful assembly.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Assembly {
+    /// The lowest address that received code or data.
+    pub origin: raw::u16,
+    /// Every byte from `origin` through the highest address that received code or data; gaps
+    /// between `ORG` blocks and space reserved with `DS` inside the image are zero-filled.
+    pub image: Vec<raw::u8>,
+    /// Every label and `EQU`/`SET` symbol, with its final value.
+    pub symbols: BTreeMap<String, raw::u16>,
+    /// The instructions of the program in source order, with their addresses.
+    pub ops: Vec<(raw::u16, Op)>,
+    /// The start address given as the operand of `END`, if any.
+    pub entry: Option<raw::u16>,
+}
+
+/// How a symbol was defined; only `SET` symbols may be redefined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Definition {
+    Fixed,
+    Variable,
+}
+
+/// The fields of one source line.
+#[derive(Debug, Default, PartialEq)]
+struct Statement<'a> {
+    label: Option<&'a str>,
+    operation: Option<String>,
+    operands: Vec<&'a str>,
+}
+
+const DIRECTIVES: [&str; 24] = [
+    "ORG", "EQU", "SET", "DB", "DW", "DS", "END", "TITLE", "PAGE", "EJECT", "SPACE",
+    "MACRO", "ENDM", "EXITM", "LOCAL", "REPT", "INCLUDE", "IF", "IFE", "IFDEF", "IFNDEF", "ELSE", "ENDIF", "ENDC",
+];
+
+/// Directives whose label names the thing they define, even when the label is indented.
+const DEFINITIONS: [&str; 3] = ["EQU", "SET", "MACRO"];
+
+fn is_name(text: &str) -> bool {
+    matches!(tokenize(text).as_deref(), Ok([Token::Name(_)]))
+}
+
+/// Splits the operand field at commas that aren't inside quotes, parentheses or the angle
+/// brackets that group a macro argument.
+fn operands(text: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let (mut start, mut depth, mut quote) = (0, 0, None);
+    for (at, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(' | '<') => depth += 1,
+            (None, ')' | '>') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                fields.push(text[start..at].trim());
+                start = at + 1;
+            }
+            _ => (),
+        }
+    }
+    let last = text[start..].trim();
+    if !last.is_empty() || !fields.is_empty() {
+        fields.push(last);
+    }
+    fields
+}
+
+/// Removes a trailing `;` comment, ignoring semicolons inside quotes.
+fn uncomment(text: &str) -> &str {
+    let mut quote = None;
+    for (at, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ';') => return &text[..at],
+            _ => (),
+        }
+    }
+    text
+}
+
+fn leading_name(text: &str) -> (&str, &str) {
+    let end = text.find(|c: char| !(c.is_ascii_alphanumeric() || "_?@.$".contains(c))).unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Breaks a line into its fields. A name in the first column is a label unless it names an
+/// operation (so that unindented directives still work); elsewhere, labels need a colon.
+fn parse<'a>(line: &'a str, is_operation: &dyn Fn(&str) -> bool) -> Result<Statement<'a>, ErrorKind> {
+    if line.starts_with('*') {
+        return Ok(Statement::default());
+    }
+    let text = uncomment(line);
+    let mut statement = Statement::default();
+    let mut rest = text.trim_start();
+    let (name, after) = leading_name(rest);
+    if !name.is_empty() && is_name(name) {
+        if let Some(after) = after.strip_prefix(':') {
+            statement.label = Some(name);
+            rest = after.strip_prefix(':').unwrap_or(after).trim_start();
+        } else if rest.len() == text.len() && !is_operation(&name.to_ascii_uppercase())
+            || after.starts_with(char::is_whitespace)
+                && DEFINITIONS.contains(&leading_name(after.trim_start()).0.to_ascii_uppercase().as_str()) {
+            statement.label = Some(name);
+            rest = after.trim_start();
+        }
+    }
+    let (name, after) = leading_name(rest);
+    if name.is_empty() {
+        return if rest.trim().is_empty() { Ok(statement) } else { Err(ErrorKind::Syntax(rest.trim().to_string())) };
+    }
+    if !is_name(name) {
+        return Err(ErrorKind::Syntax(name.to_string()));
+    }
+    statement.operation = Some(name.to_ascii_uppercase());
+    statement.operands = operands(after);
+    Ok(statement)
+}
+
+struct NoSymbols;
+
+impl Scope for NoSymbols {
+    fn here(&self) -> raw::u16 { 0 }
+    fn value(&self, _name: &str) -> Result<i32, ErrorKind> { Ok(0) }
+}
+
+fn is_operation(name: &str) -> bool {
+    DIRECTIVES.contains(&name) || instruction::build(name, &[], &NoSymbols).is_some()
+}
+
+/// An open `IF` block.
+struct Condition {
+    /// Whether the statements of the current branch are assembled.
+    active: bool,
+    /// Whether a branch has been taken already (or can't be, inside a skipped block).
+    decided: bool,
+    /// Whether the block has reached its `ELSE`.
+    otherwise: bool,
+}
+
+/// The working state of one pass over the source.
+struct Pass<'s> {
+    assembler: &'s Assembler,
+    last: bool,
+    here: raw::u16,
+    symbols: &'s mut BTreeMap<String, (i32, Definition)>,
+    defined: BTreeSet<String>,
+    pending: usize,
+    unresolved: Cell<bool>,
+    segments: Vec<(raw::u16, Vec<raw::u8>)>,
+    ops: Vec<(raw::u16, Op)>,
+    entry: Option<raw::u16>,
+    ended: bool,
+    reader: Reader,
+    conditions: Vec<Condition>,
+    macros: BTreeMap<String, Macro>,
+    locals: usize,
+}
+
+impl Scope for Pass<'_> {
+    fn here(&self) -> raw::u16 { self.here }
+    fn value(&self, name: &str) -> Result<i32, ErrorKind> {
+        match self.symbols.get(name) {
+            Some(&(value, _)) => Ok(value),
+            None if !self.last => { self.unresolved.set(true); Ok(0) }
+            None => Err(ErrorKind::Undefined(name.to_string())),
+        }
+    }
+}
+
+impl Pass<'_> {
+    fn define(&mut self, name: &str, value: i32, definition: Definition) -> Result<(), ErrorKind> {
+        let name = name.to_ascii_uppercase();
+        match self.symbols.get(&name) {
+            Some((_, Definition::Fixed)) if definition == Definition::Fixed && self.defined.contains(&name) =>
+                return Err(ErrorKind::Duplicate(name)),
+            Some((_, Definition::Fixed)) if definition == Definition::Variable => return Err(ErrorKind::Duplicate(name)),
+            Some((_, Definition::Variable)) if definition == Definition::Fixed => return Err(ErrorKind::Duplicate(name)),
+            _ => (),
+        }
+        self.defined.insert(name.clone());
+        self.symbols.insert(name, (value, definition));
+        Ok(())
+    }
+
+    /// Evaluates an operand, and reports whether it depended on a symbol that isn't known yet.
+    fn resolve<T>(&self, read: impl FnOnce(&Self) -> Result<T, ErrorKind>) -> Result<(T, bool), ErrorKind> {
+        self.unresolved.set(false);
+        let value = read(self)?;
+        Ok((value, self.unresolved.replace(false)))
+    }
+
+    fn emit(&mut self, bytes: &[raw::u8]) -> Result<(), ErrorKind> {
+        if self.here as usize + bytes.len() > 0x10000 { return Err(ErrorKind::PastEnd); }
+        match self.segments.last_mut() {
+            Some((start, block)) if start.wrapping_add(block.len() as raw::u16) == self.here => block.extend_from_slice(bytes),
+            _ => self.segments.push((self.here, bytes.to_vec())),
+        }
+        self.here = self.here.wrapping_add(bytes.len() as raw::u16);
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: Statement) -> Result<(), ErrorKind> {
+        let Statement { label, operation, operands } = statement;
+        let Some(operation) = operation else {
+            if let Some(label) = label { self.define(label, self.here as i32, Definition::Fixed)?; }
+            return Ok(());
+        };
+        let arguments = |count: usize| if operands.len() == count { Ok(&operands[..]) } else { Err(ErrorKind::Operands(operation.clone())) };
+        match operation.as_str() {
+            "EQU" | "SET" => {
+                let name = label.ok_or_else(|| ErrorKind::MissingName(operation.clone()))?;
+                let (value, unresolved) = self.resolve(|pass| evaluate(arguments(1)?[0], pass))?;
+                if unresolved {
+                    self.pending += 1;
+                } else {
+                    let definition = if operation == "SET" { Definition::Variable } else { Definition::Fixed };
+                    self.define(name, value as raw::u16 as i32, definition)?;
+                }
+                return Ok(());
+            }
+            _ => if let Some(label) = label { self.define(label, self.here as i32, Definition::Fixed)?; }
+        }
+        match operation.as_str() {
+            "ORG" | "DS" => {
+                let text = arguments(1)?[0];
+                let (value, unresolved) = self.resolve(|pass| instruction::word(text, pass))?;
+                if unresolved { return Err(ErrorKind::Forward(text.to_string())); }
+                self.here = if operation == "ORG" { value } else { self.here.wrapping_add(value) };
+            }
+            "DB" => for text in operands.iter().copied() {
+                match tokenize(text)?.as_slice() {
+                    [Token::Text(bytes)] if bytes.len() != 1 => self.emit(bytes)?,
+                    _ => {
+                        let byte = instruction::byte(text, self)?;
+                        self.emit(&[byte])?;
+                    }
+                }
+            }
+            "DW" => for text in operands.iter().copied() {
+                let word = instruction::word(text, self)?;
+                self.emit(&word.to_le_bytes())?;
+            }
+            "END" => {
+                if let [text] = operands[..] {
+                    self.entry = Some(instruction::word(text, self)?);
+                }
+                self.ended = true;
+            }
+            "TITLE" | "PAGE" | "EJECT" | "SPACE" => (),
+            mnemonic => {
+                let op = instruction::build(mnemonic, &operands, self)
+                    .ok_or_else(|| ErrorKind::UnknownOperation(mnemonic.to_string()))??;
+                let bytes: [raw::u8; 4] = op.into();
+                if self.last { self.ops.push((self.here, op)); }
+                self.emit(&bytes[1..=bytes[0] as usize])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn active(&self) -> bool {
+        self.conditions.last().is_none_or(|condition| condition.active)
+    }
+
+    /// Decides whether an `IF`, `IFE`, `IFDEF` or `IFNDEF` block is assembled. `IF` takes any
+    /// nonzero value as true, as M80 does; the operand can't depend on symbols defined later.
+    fn condition(&self, operation: &str, operands: &[&str]) -> Result<bool, ErrorKind> {
+        let [text] = operands else { return Err(ErrorKind::Operands(operation.to_string())) };
+        match operation {
+            "IFDEF" | "IFNDEF" => {
+                if !is_name(text) { return Err(ErrorKind::BadOperand(text.to_string())); }
+                let name = text.to_ascii_uppercase();
+                let defined = self.defined.contains(&name) || self.assembler.predefined.contains_key(&name);
+                Ok(defined == (operation == "IFDEF"))
+            }
+            _ => {
+                let (value, unresolved) = self.resolve(|pass| evaluate(text, pass))?;
+                if unresolved { return Err(ErrorKind::Forward(text.to_string())); }
+                Ok((value as raw::u16 != 0) == (operation == "IF"))
+            }
+        }
+    }
+
+    /// Reads the body of a `MACRO` or `REPT` block, up to its matching `ENDM`.
+    fn body(&mut self, directive: &str) -> Result<Vec<String>, ErrorKind> {
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let line = self.reader.take().ok_or_else(|| ErrorKind::Unterminated(directive.to_string()))?;
+            match parse(&line, &is_operation).ok().and_then(|statement| statement.operation).as_deref() {
+                Some("MACRO" | "REPT") => depth += 1,
+                Some("ENDM") if depth == 0 => return Ok(body),
+                Some("ENDM") => depth -= 1,
+                _ => (),
+            }
+            body.push(line);
+        }
+    }
+
+    /// Produces the lines a macro invocation stands for. Missing arguments are empty, and each
+    /// name declared with `LOCAL` becomes a name unique to this expansion, such as `??0001`.
+    fn expand(&mut self, name: &str, arguments: &[&str]) -> Result<Vec<String>, ErrorKind> {
+        let Macro { parameters, body } = self.macros[name].clone();
+        if arguments.len() > parameters.len() {
+            return Err(ErrorKind::Operands(name.to_string()));
+        }
+        let mut bindings = parameters.into_iter()
+            .zip(arguments.iter().map(|text| argument(text).to_string()).chain(iter::repeat(String::new())))
+            .collect::<BTreeMap<_, _>>();
+        let mut lines = Vec::new();
+        for line in body {
+            let line = substitute(&line, &bindings);
+            match parse(&line, &is_operation) {
+                Ok(Statement { operation: Some(operation), operands, .. }) if operation == "LOCAL" => for local in operands {
+                    if !is_name(local) { return Err(ErrorKind::BadOperand(local.to_string())); }
+                    self.locals += 1;
+                    let mut unique = String::new();
+                    let _ = write!(unique, "??{:04}", self.locals);
+                    bindings.insert(local.to_ascii_uppercase(), unique);
+                }
+                _ => lines.push(line),
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Assembles one line of source, first handling the directives that decide which lines are
+    /// assembled: conditionals, macros, repeat blocks and included files.
+    fn line(&mut self, line: &str) -> Result<(), ErrorKind> {
+        let active = self.active();
+        let macros = &self.macros;
+        let statement = match parse(line, &|name| is_operation(name) || macros.contains_key(name)) {
+            Ok(statement) => statement,
+            Err(_) if !active => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        let Some(operation) = statement.operation.clone() else {
+            return if active { self.statement(statement) } else { Ok(()) };
+        };
+        let single = || match statement.operands[..] {
+            [text] => Ok(text),
+            _ => Err(ErrorKind::Operands(operation.clone())),
+        };
+        match operation.as_str() {
+            "IF" | "IFE" | "IFDEF" | "IFNDEF" => {
+                let taken = active && self.condition(&operation, &statement.operands)?;
+                self.conditions.push(Condition { active: taken, decided: taken || !active, otherwise: false });
+            }
+            "ELSE" => match self.conditions.last_mut() {
+                Some(condition) if !condition.otherwise => {
+                    condition.active = !condition.decided;
+                    condition.decided = true;
+                    condition.otherwise = true;
+                }
+                _ => return Err(ErrorKind::Unmatched(operation)),
+            }
+            "ENDIF" | "ENDC" => if self.conditions.pop().is_none() {
+                return Err(ErrorKind::Unmatched(operation));
+            }
+            _ if !active => (),
+            "MACRO" => {
+                let name = statement.label.ok_or_else(|| ErrorKind::MissingName(operation.clone()))?.to_ascii_uppercase();
+                if is_operation(&name) || self.macros.contains_key(&name) {
+                    return Err(ErrorKind::Duplicate(name));
+                }
+                let parameters = statement.operands.iter()
+                    .map(|text| if is_name(text) { Ok(text.to_ascii_uppercase()) } else { Err(ErrorKind::BadOperand(text.to_string())) })
+                    .collect::<Result<_, _>>()?;
+                let body = self.body(&operation)?;
+                self.macros.insert(name, Macro { parameters, body });
+            }
+            "REPT" => {
+                let text = single()?;
+                let (count, unresolved) = self.resolve(|pass| instruction::word(text, pass))?;
+                if unresolved { return Err(ErrorKind::Forward(text.to_string())); }
+                self.statement(Statement { label: statement.label, ..Statement::default() })?;
+                let body = self.body(&operation)?;
+                let lines = (0..count).flat_map(|_| body.iter().cloned()).collect();
+                self.reader.expand(lines, self.conditions.len())?;
+            }
+            "INCLUDE" => {
+                let name = single()?;
+                let name = name.strip_prefix(['\'', '"']).and_then(|name| name.strip_suffix(['\'', '"'])).unwrap_or(name);
+                let text = self.assembler.includes.as_ref()
+                    .and_then(|read| read(name))
+                    .ok_or_else(|| ErrorKind::Include(name.to_string()))?;
+                self.statement(Statement { label: statement.label, ..Statement::default() })?;
+                self.reader.include(name, &text, self.conditions.len())?;
+            }
+            "ENDM" | "LOCAL" => return Err(ErrorKind::Unmatched(operation)),
+            "EXITM" => self.reader.exit(&mut self.conditions)?,
+            name if self.macros.contains_key(name) => {
+                let lines = self.expand(name, &statement.operands)?;
+                self.statement(Statement { label: statement.label, ..Statement::default() })?;
+                self.reader.expand(lines, self.conditions.len())?;
+            }
+            _ => self.statement(statement)?,
+        }
+        Ok(())
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        let (file, line) = self.reader.location();
+        Error { file, line, kind }
+    }
+}
+
+/// An assembler, holding any symbols that should be defined before the source is read and the
+/// means of reading files named by `INCLUDE`.
+#[derive(Clone, Default)]
+pub struct Assembler {
+    predefined: BTreeMap<String, raw::u16>,
+    includes: Option<Includes>,
+}
+
+/// Reads the text of an included file by name.
+type Includes = Rc<dyn Fn(&str) -> Option<String>>;
+
+impl fmt::Debug for Assembler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Assembler")
+            .field("predefined", &self.predefined)
+            .field("includes", &self.includes.is_some())
+            .finish()
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Self { Self::default() }
+
+    /// Defines a symbol for every source assembled with this assembler, as though it had been
+    /// declared with `EQU`; useful for configuring addresses from the host.
+    pub fn define(&mut self, name: &str, value: raw::u16) -> &mut Self {
+        self.predefined.insert(name.to_ascii_uppercase(), value);
+        self
+    }
+
+    /// Supplies the text of the files named by `INCLUDE` statements, or `None` for a file that
+    /// can't be read. Without this, any `INCLUDE` fails.
+    pub fn includes(&mut self, read: impl Fn(&str) -> Option<String> + 'static) -> &mut Self {
+        self.includes = Some(Rc::new(read));
+        self
+    }
+
+    /// Reads the files named by `INCLUDE` statements from a directory.
+    #[cfg(feature="std")]
+    pub fn include_from(&mut self, directory: impl Into<std::path::PathBuf>) -> &mut Self {
+        let directory = directory.into();
+        self.includes(move |name| std::fs::read_to_string(directory.join(name)).ok())
+    }
+
+    /// Assembles a complete source text. The first pass assigns addresses to every label (and
+    /// is repeated while that resolves further `EQU` symbols); the second pass evaluates every
+    /// operand and encodes the program.
+    pub fn assemble(&self, source: &str) -> Result<Assembly, Error> {
+        let mut symbols = self.predefined.iter()
+            .map(|(name, value)| (name.clone(), (*value as i32, Definition::Fixed)))
+            .collect::<BTreeMap<_, _>>();
+        let mut waiting = usize::MAX;
+        loop {
+            let pass = self.pass(source, &mut symbols, false)?;
+            if pass.pending == 0 || pass.pending >= waiting { break; }
+            waiting = pass.pending;
+        }
+        let pass = self.pass(source, &mut symbols, true)?;
+        let Pass { segments, ops, entry, .. } = pass;
+        let origin = segments.iter().map(|(start, _)| *start).min().unwrap_or(0);
+        let end = segments.iter().map(|(start, block)| *start as usize + block.len()).max().unwrap_or(origin as usize);
+        let mut image = vec![0; end - origin as usize];
+        for (start, block) in segments {
+            let start = (start - origin) as usize;
+            image[start..start + block.len()].copy_from_slice(&block);
+        }
+        let symbols = symbols.into_iter().map(|(name, (value, _))| (name, value as raw::u16)).collect();
+        Ok(Assembly { origin, image, symbols, ops, entry })
+    }
+
+    fn pass<'s>(&'s self, source: &str, symbols: &'s mut BTreeMap<String, (i32, Definition)>, last: bool) -> Result<Pass<'s>, Error> {
+        let mut pass = Pass {
+            assembler: self, last, here: 0, symbols, defined: BTreeSet::new(), pending: 0, unresolved: Cell::new(false),
+            segments: Vec::new(), ops: Vec::new(), entry: None, ended: false,
+            reader: Reader::new(source), conditions: Vec::new(), macros: BTreeMap::new(), locals: 0,
+        };
+        while let Some(line) = pass.reader.next(&mut pass.conditions) {
+            pass.line(&line).map_err(|kind| pass.error(kind))?;
+            if pass.ended { break; }
+        }
+        if !pass.ended && !pass.conditions.is_empty() {
+            return Err(pass.error(ErrorKind::Unterminated(String::from("IF"))));
+        }
+        Ok(pass)
+    }
+}
+
+/// Assembles a source text with a default `Assembler`.
+pub fn assemble(source: &str) -> Result<Assembly, Error> {
+    Assembler::new().assemble(source)
+}
+
+#[cfg(test)]
+mod tests;
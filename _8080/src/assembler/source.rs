@@ -0,0 +1,143 @@
+use crate::prelude::{*, vec::Vec, collections::BTreeMap, string::ToString};
+use super::{ErrorKind, expression::{is_name_part, is_name_start}};
+
+/// How deeply files, macro expansions and repeat blocks can nest before the assembler assumes
+/// that a macro or include calls itself without end.
+const DEPTH: usize = 64;
+
+/// A macro defined with `MACRO`, kept as unexpanded text.
+#[derive(Debug, Clone)]
+pub(super) struct Macro {
+    pub parameters: Vec<String>,
+    pub body: Vec<String>,
+}
+
+/// A block of lines being read: a source file, or the expansion of a macro or repeat block.
+struct Frame {
+    lines: Vec<String>,
+    next: usize,
+    /// The name of an included file; `None` for the main source and for expansions.
+    file: Option<String>,
+    expansion: bool,
+    /// How many conditional blocks were open when the frame started.
+    conditions: usize,
+}
+
+/// The stack of blocks that statements are read from.
+pub(super) struct Reader {
+    frames: Vec<Frame>,
+}
+
+impl Reader {
+    pub fn new(source: &str) -> Self {
+        let lines = source.lines().map(str::to_string).collect();
+        Self { frames: vec![Frame { lines, next: 0, file: None, expansion: false, conditions: 0 }] }
+    }
+
+    fn push(&mut self, frame: Frame) -> Result<(), ErrorKind> {
+        if self.frames.len() >= DEPTH {
+            return Err(ErrorKind::Nesting);
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    /// Continues reading from an included file until it ends.
+    pub fn include(&mut self, name: &str, text: &str, conditions: usize) -> Result<(), ErrorKind> {
+        let lines = text.lines().map(str::to_string).collect();
+        self.push(Frame { lines, next: 0, file: Some(name.to_string()), expansion: false, conditions })
+    }
+
+    /// Continues reading from the expansion of a macro or repeat block until it ends.
+    pub fn expand(&mut self, lines: Vec<String>, conditions: usize) -> Result<(), ErrorKind> {
+        self.push(Frame { lines, next: 0, file: None, expansion: true, conditions })
+    }
+
+    /// Reads the next line, leaving finished blocks. Conditionals left open inside a finished
+    /// expansion are closed with it.
+    pub fn next<T>(&mut self, conditions: &mut Vec<T>) -> Option<String> {
+        loop {
+            let frame = self.frames.last_mut()?;
+            if let Some(line) = frame.lines.get(frame.next) {
+                frame.next += 1;
+                return Some(line.clone());
+            }
+            if self.frames.len() == 1 {
+                return None;
+            }
+            let frame = self.frames.pop()?;
+            if frame.expansion {
+                conditions.truncate(frame.conditions);
+            }
+        }
+    }
+
+    /// Reads the next line of the current block only, as the body of a definition.
+    pub fn take(&mut self) -> Option<String> {
+        let frame = self.frames.last_mut()?;
+        let line = frame.lines.get(frame.next)?.clone();
+        frame.next += 1;
+        Some(line)
+    }
+
+    /// Abandons the innermost expansion, for `EXITM`.
+    pub fn exit<T>(&mut self, conditions: &mut Vec<T>) -> Result<(), ErrorKind> {
+        let Some(at) = self.frames.iter().rposition(|frame| frame.expansion) else {
+            return Err(ErrorKind::Unmatched(String::from("EXITM")));
+        };
+        conditions.truncate(self.frames[at].conditions);
+        self.frames.truncate(at);
+        Ok(())
+    }
+
+    /// The file and (1-based) line that the statement being assembled came from; lines of an
+    /// expansion are reported at the statement that expanded them.
+    pub fn location(&self) -> (Option<String>, usize) {
+        self.frames.iter().rev()
+            .find(|frame| !frame.expansion)
+            .map_or((None, 0), |frame| (frame.file.clone(), frame.next))
+    }
+}
+
+/// Replaces names in a line of a macro body by the text bound to them. Outside of quotes every
+/// bound name is replaced; inside quotes, only names joined to their neighbours with `&` are.
+/// An `&` beside a replaced name is removed, so that `LOOP&N` can build a name from a parameter.
+/// Comments are left as they are.
+pub(super) fn substitute(line: &str, bindings: &BTreeMap<String, String>) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut quote = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if quote == Some(c) => { quote = None; text.push(c); }
+            '\'' | '"' if quote.is_none() => { quote = Some(c); text.push(c); }
+            ';' if quote.is_none() => { text.push_str(&line[start..]); break; }
+            c if c.is_ascii_alphanumeric() || is_name_start(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(at, next)) = chars.peek() {
+                    if !is_name_part(next) { break; }
+                    end = at + next.len_utf8();
+                    chars.next();
+                }
+                let name = &line[start..end];
+                let joined_before = text.ends_with('&');
+                let joined_after = chars.peek().is_some_and(|&(_, next)| next == '&');
+                match bindings.get(&name.to_ascii_uppercase()) {
+                    Some(value) if !c.is_ascii_digit() && (quote.is_none() || joined_before || joined_after) => {
+                        if joined_before { text.pop(); }
+                        text.push_str(value);
+                        if joined_after { chars.next(); }
+                    }
+                    _ => text.push_str(name),
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Removes the angle brackets that let a macro argument contain commas.
+pub(super) fn argument(text: &str) -> &str {
+    text.strip_prefix('<').and_then(|text| text.strip_suffix('>')).unwrap_or(text)
+}
@@ -0,0 +1,217 @@
+use super::*;
+use crate::chip::{access::{*, Internal::*}, opcode::Op::*};
+
+#[test]
+fn instructions() {
+    let program = assemble("
+        ORG 100H
+START:  LXI SP,STACK    ; set up the stack
+        MVI B,3
+        MOV A,M
+        CALL PRINT
+        JNZ START
+        HLT
+PRINT   ADD B
+        RET
+STACK   EQU 0F000H
+    ").unwrap();
+    assert_eq!(program.origin, 0x0100);
+    assert_eq!(program.image, [
+        0x31, 0x00, 0xF0,
+        0x06, 0x03,
+        0x7E,
+        0xCD, 0x0D, 0x01,
+        0xC2, 0x00, 0x01,
+        0x76,
+        0x80,
+        0xC9,
+    ]);
+    assert_eq!(program.symbols["START"], 0x0100);
+    assert_eq!(program.symbols["PRINT"], 0x010D);
+    assert_eq!(program.symbols["STACK"], 0xF000);
+    assert_eq!(program.ops[0], (0x0100, LoadExtendedWith { to: StackPointer, value: Wrapping(0xF000) }));
+    assert_eq!(program.ops[2], (0x0105, Move { to: Byte::Single(Register::A), from: Byte::Indirect }));
+}
+
+#[test]
+fn round_trip() {
+    let source = "
+        NOP
+        STAX D
+        LDAX B
+        DAD SP
+        PUSH PSW
+        POP H
+        RPE
+        CM 1234H
+        JC 0000H
+        RST 7
+        SBB M
+        XRI 80H
+        IN 10H
+        OUT 0FFH
+        MOV E,L
+    ";
+    let program = assemble(source).unwrap();
+    let text: crate::vec::Vec<_> = crate::chip::opcode::disassemble(&program.image, program.origin)
+        .map(|line| crate::string::ToString::to_string(&line.op.unwrap()))
+        .collect();
+    let expected: crate::vec::Vec<_> = source.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn data() {
+    let program = assemble("
+        ORG 0
+        DB 'Hi', 0DH, 0AH, '$'
+        DB 'A'+80H, -1, 'it''s'
+        DW 1234H, TABLE
+        DS 2
+TABLE:  DB HIGH 0ABCDH, LOW 0ABCDH
+        END TABLE
+        DB 99
+    ").unwrap();
+    assert_eq!(program.image, [
+        b'H', b'i', 0x0D, 0x0A, b'$',
+        0xC1, 0xFF, b'i', b't', b'\'', b's',
+        0x34, 0x12, 0x11, 0x00,
+        0x00, 0x00,
+        0xAB, 0xCD,
+    ]);
+    assert_eq!(program.entry, Some(0x0011));
+}
+
+#[test]
+fn expressions() {
+    let program = assemble("
+SIZE    EQU     NEXT-FIRST
+COUNT   SET     3
+COUNT   SET     COUNT*2+1
+FIRST:  DB      SIZE, COUNT, 10 MOD 3, 1 SHL 4, 100Q, 1010B, 5 GT 4, NOT 0 AND 0FH
+NEXT:   DW      $, (2+3)*4, 7 EQ 8, 0FFFFH+1, -(-1)
+        MVI     A,0FF80H
+    ").unwrap();
+    assert_eq!(program.image, [8, 7, 1, 16, 64, 10, 0xFF, 0x0F, 0x08, 0x00, 20, 0x00, 0, 0, 0, 0, 1, 0, 0x3E, 0x80]);
+}
+
+#[test]
+fn errors() {
+    let error = |source| assemble(source).unwrap_err();
+    assert_eq!(error("  MOV A"), Error { file: None, line: 1, kind: ErrorKind::Operands(String::from("MOV")) });
+    assert_eq!(error("\n  JMP NOWHERE"), Error { file: None, line: 2, kind: ErrorKind::Undefined(String::from("NOWHERE")) });
+    assert_eq!(error("X: NOP\nX: NOP"), Error { file: None, line: 2, kind: ErrorKind::Duplicate(String::from("X")) });
+    assert_eq!(error("  MVI A,300"), Error { file: None, line: 1, kind: ErrorKind::OutOfRange(300) });
+    assert_eq!(error("  LDAX H"), Error { file: None, line: 1, kind: ErrorKind::BadOperand(String::from("H")) });
+    assert_eq!(error("  FOO 3"), Error { file: None, line: 1, kind: ErrorKind::UnknownOperation(String::from("FOO")) });
+    assert_eq!(error("  ORG LATER\nLATER: NOP"), Error { file: None, line: 1, kind: ErrorKind::Forward(String::from("LATER")) });
+    assert_eq!(error("  DB 19Q"), Error { file: None, line: 1, kind: ErrorKind::BadNumber(String::from("19Q")) });
+    assert_eq!(error("  ORG 0FFFEH\n  LXI H,0"), Error { file: None, line: 2, kind: ErrorKind::PastEnd });
+    let deep = String::from("  DW ") + &"(".repeat(100) + "1" + &")".repeat(100);
+    assert_eq!(error(&deep), Error { file: None, line: 1, kind: ErrorKind::Nesting });
+    assert_eq!(error(&(String::from("  DB ") + &"-".repeat(100) + "1")), Error { file: None, line: 1, kind: ErrorKind::Nesting });
+}
+
+#[test]
+fn predefined() {
+    let mut assembler = Assembler::new();
+    assembler.define("bdos", 0x0005);
+    let program = assembler.assemble("  CALL BDOS").unwrap();
+    assert_eq!(program.image, [0xCD, 0x05, 0x00]);
+    assert!(program.ops.iter().all(|(_, op)| *op == Call { sub: Wrapping(0x0005) }));
+}
+
+#[test]
+fn macros() {
+    let program = assemble("
+PRINT   MACRO   TEXT, PORT
+        LOCAL   NEXT, DONE
+        LXI     H,MSG&PORT
+NEXT:   MOV     A,M
+        ORA     A
+        JZ      DONE
+        OUT     PORT
+        INX     H
+        JMP     NEXT
+MSG&PORT: DB    '&TEXT', 0
+DONE:
+        ENDM
+        ORG     100H
+        PRINT   <HI, THERE>, 2
+        PRINT   OK, 3
+    ").unwrap();
+    assert_eq!(program.symbols["??0001"], 0x0103);
+    assert_eq!(program.symbols["MSG2"], 0x010E);
+    assert_eq!(program.symbols["??0002"], 0x0118);
+    assert_eq!(program.symbols["??0003"], 0x011B);
+    assert_eq!(program.symbols["MSG3"], 0x0126);
+    assert_eq!(&program.image[0x00..0x03], [0x21, 0x0E, 0x01]);
+    assert_eq!(&program.image[0x08..0x0A], [0xD3, 0x02]);
+    assert_eq!(&program.image[0x0E..0x18], b"HI, THERE\0");
+    assert_eq!(&program.image[0x26..], b"OK\0");
+}
+
+#[test]
+fn conditionals() {
+    let source = "
+COUNT   SET     7
+SAVE    MACRO   R
+        IFNDEF  QUIET
+        PUSH    R
+        EXITM
+        ENDIF
+        NOP
+        ENDM
+        IF      SIZE GT 4
+        DB      1
+        IF      0
+        DB      2
+        ELSE
+        DB      3
+        ENDIF
+        ELSE
+        DB      4
+        IFE     0
+        DB      5
+        ENDIF
+        ENDIF
+        SAVE    B
+        REPT    3
+        DB      COUNT
+COUNT   SET     COUNT+1
+        ENDM
+    ";
+    let mut assembler = Assembler::new();
+    assembler.define("SIZE", 8);
+    assert_eq!(assembler.assemble(source).unwrap().image, [1, 3, 0xC5, 7, 8, 9]);
+    assembler.define("SIZE", 2).define("QUIET", 0);
+    assert_eq!(assembler.assemble(source).unwrap().image, [4, 5, 0x00, 7, 8, 9]);
+}
+
+#[test]
+fn includes() {
+    let mut assembler = Assembler::new();
+    assembler.includes(|name| match name {
+        "EQUATES.LIB" => Some(String::from("BDOS EQU 5\n  INCLUDE 'CALLS.LIB'")),
+        "CALLS.LIB" => Some(String::from("\nPRINT: MVI C,9\n  JMP BDOS\n  JMP MISSING")),
+        _ => None,
+    });
+    let error = assembler.assemble("  INCLUDE EQUATES.LIB\n  CALL PRINT").unwrap_err();
+    assert_eq!(error, Error { file: Some(String::from("CALLS.LIB")), line: 4, kind: ErrorKind::Undefined(String::from("MISSING")) });
+    assert_eq!(error.to_string(), "CALLS.LIB, line 4: undefined symbol `MISSING`");
+    assembler.define("MISSING", 0);
+    let program = assembler.assemble("  INCLUDE EQUATES.LIB\n  CALL PRINT").unwrap();
+    assert_eq!(program.image, [0x0E, 0x09, 0xC3, 0x05, 0x00, 0xC3, 0x00, 0x00, 0xCD, 0x00, 0x00]);
+    assert_eq!(assemble("\n  INCLUDE X.LIB").unwrap_err(), Error { file: None, line: 2, kind: ErrorKind::Include(String::from("X.LIB")) });
+}
+
+#[test]
+fn block_errors() {
+    let error = |source| assemble(source).unwrap_err();
+    assert_eq!(error("  IF 1\n  NOP"), Error { file: None, line: 2, kind: ErrorKind::Unterminated(String::from("IF")) });
+    assert_eq!(error("M MACRO\n  NOP"), Error { file: None, line: 2, kind: ErrorKind::Unterminated(String::from("MACRO")) });
+    assert_eq!(error("  NOP\n  ENDIF"), Error { file: None, line: 2, kind: ErrorKind::Unmatched(String::from("ENDIF")) });
+    assert_eq!(error("  ENDM"), Error { file: None, line: 1, kind: ErrorKind::Unmatched(String::from("ENDM")) });
+    assert_eq!(error("M MACRO\n  M\n  ENDM\n  M"), Error { file: None, line: 4, kind: ErrorKind::Nesting });
+    assert_eq!(error("M MACRO X\n  MVI A,X\n  ENDM\n\n  M 300"), Error { file: None, line: 5, kind: ErrorKind::OutOfRange(300) });
+}
@@ -4,11 +4,12 @@ use super::access::{*, Register::*, Byte::*, Double::*, Internal::*, Word::*};
 
 pub mod opcode;
 use opcode::{Op, Op::*};
+pub mod timing;
 
 #[cfg(feature="open")]
-pub(super) type OpOutcome = Result<Option<NonZeroU8>, String>;
+pub(crate) type OpOutcome = Result<Option<NonZeroU8>, String>;
 #[cfg(not(feature="open"))]
-pub(super) type OpOutcome = Option<NonZeroU8>;
+pub(crate) type OpOutcome = Option<NonZeroU8>;
 
 impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
     fn from_pc(&self) -> impl Iterator<Item=u8> + '_ {
@@ -19,17 +20,28 @@ impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
     #[doc(hidden)]
     #[cfg(feature="open")]
 	pub fn execute(&mut self) -> OpOutcome {
-		if !self.chip.active { return Ok(NonZeroU8::new(1)) };
+        self.follow_tape();
+        self.execute_next()
+    }
+
+    /// Executes the next instruction, once the movie's tape, if any, has caught up with the
+    /// clock.
+    #[cfg(feature="open")]
+	pub(crate) fn execute_next(&mut self) -> OpOutcome {
+		if !self.chip.active {
+            self.clock += 1;
+            return Ok(NonZeroU8::new(1));
+        }
         let (op, len) = Op::extract(self.from_pc())
             .map_err(|e| panic!("Couldn't extract opcode from {e:X} at {:#06X}", self.chip.pc)).unwrap();
+        let at = self.chip.pc;
+        self.log_step(at.0, op);
         self.chip.pc += len as raw::u16;
-        let outcome = {
-        	let (chip, bus) = self.split_mut();
-        	op.execute_on(chip, bus)
-        };
+        let outcome = self.perform(op, at);
         if outcome.is_err() {
             self.chip.active = false;
         };
+        if let Ok(Some(cycles)) = outcome { self.clock += cycles.get() as u64; }
 		let (chip, bus) = self.split_mut();
         if let Some(action) = bus.did_execute(chip, op)? {
             action.execute_on(chip, bus).unwrap();
@@ -51,40 +63,73 @@ impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
     /// For details of the chip operation and instruction set, see the 8080 Programmer's Manual.
     #[cfg(any(not(feature="open"), doc))]
 	pub fn execute(&mut self) -> OpOutcome {
-		if !self.chip.active { return NonZeroU8::new(1) };
+        self.follow_tape();
+        self.execute_next()
+    }
+
+    #[cfg(not(feature="open"))]
+	pub(crate) fn execute_next(&mut self) -> OpOutcome {
+		if !self.chip.active {
+            self.clock += 1;
+            return NonZeroU8::new(1);
+        }
         let (op, len) = Op::extract(self.from_pc())
             .map_err(|e| panic!("Couldn't extract opcode from {e:X?}")).unwrap();
+        let at = self.chip.pc;
+        self.log_step(at.0, op);
         self.chip.pc += len as raw::u16;
-        let elapsed = {
-        	let (chip, board) = self.split_mut();
-        	op.execute_on(chip, board)
-        };
-        if elapsed.is_none() { self.chip.active = false; }
+        let elapsed = self.perform(op, at);
+        match elapsed {
+            Some(cycles) => self.clock += cycles.get() as u64,
+            None => self.chip.active = false,
+        }
         elapsed
 	}
 
     /// This method submits an interrupt request containing any operation that can be contained
-    /// in one byte. If the core's interrupts flag is reset, no action will be taken and the
-    /// method will return `Ok(false)`. If the flag is set and the operation fits into a single
-    /// byte (a technical requirement of the original chip), it will reset the interrupts flag
-    /// (disabling interrupts until further notice; interrupt vectors should be written to set
-    /// the flag before returning) and execute the supplied instruction, then return `Ok(true)`.
+    /// in one byte, or a `CALL` (which an 8259 interrupt controller supplies in three bytes).
+    /// If the core's interrupts flag is reset, no action will be taken and the method will
+    /// return `Ok(false)`. If the flag is set and the operation is usable, it will reset the
+    /// interrupts flag (disabling interrupts until further notice; interrupt vectors should be
+    /// written to set the flag before returning) and execute the supplied instruction, then
+    /// return `Ok(true)`.
     ///
-    /// If the operation cannot fit into a single byte, the operation will return a
+    /// If the operation is any other multi-byte instruction, the operation will return a
     /// `Err(NotUsable(_))` value containing the submitted operation and take no further action.
+    ///
+    /// While the machine plays back a movie, only the interrupt recorded at the current cycle
+    /// is accepted, and any other returns `Ok(false)`.
     pub fn interrupt(&mut self, op: Op) -> Result<bool, opcode::Error> {
-        if op.len() == 1 {
-            Ok(self.chip.interrupts && {
-                self.chip.active = true;
-                self.chip.interrupts = false;
-                let _ = op.execute_on(&mut self.chip, self.board.borrow_mut());
-                true
-            })
+        if op.len() == 1 || matches!(op, Call{..}) {
+            let clock = self.clock;
+            if !self.admit(op) { return Ok(false); }
+            let accepted = self.accept(op);
+            self.log_interrupt(op, clock, accepted);
+            Ok(accepted)
         } else {
             Err(opcode::Error::NotUsable(op))
         }
     }
 
+    /// Executes an interrupting operation if the core's interrupts flag is set, returning
+    /// whether it was.
+    pub(crate) fn accept(&mut self, op: Op) -> bool {
+        self.chip.interrupts && {
+            self.chip.active = true;
+            self.chip.interrupts = false;
+            let at = self.chip.pc;
+            let outcome = if self.timed {
+                timing::execute(op, at, &mut self.chip, self.board.borrow_mut(), true)
+            } else {
+                op.execute_on(&mut self.chip, self.board.borrow_mut())
+            };
+            #[cfg(feature="open")]
+            let outcome = outcome.ok().flatten();
+            self.clock += outcome.map_or(0, |cycles| cycles.get() as u64);
+            true
+        }
+    }
+
     /// This method is a convenience shorthand for `interrupt` that assumes the desired
     /// operation is a RST action, saving the address of the next instruction of the stack
     /// and jumping to one of the addresses 0x00, 0x80, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38, 0x40 or 0x48.
@@ -94,6 +139,27 @@ impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
             _ => Err(opcode::OutOfRange)
         }
     }
+
+    /// This method is a convenience shorthand for `interrupt` that supplies a CALL to the given
+    /// address, as an 8259 interrupt controller does in 8080 mode. It returns whether the
+    /// processor accepted the interrupt.
+    pub fn call_to(&mut self, address: raw::u16) -> bool {
+        self.interrupt(Call{sub: Wrapping(address)}).unwrap_or(false)
+    }
+
+    /// This method moves the program counter to the supplied address, so that the next
+    /// operation executed is the one stored there, as when a loader starts a program. It also
+    /// wakes the core if it was halted.
+    pub fn start_at(&mut self, address: raw::u16) {
+        self.chip.pc = Wrapping(address);
+        self.chip.active = true;
+    }
+}
+
+/// Executes an operation fetched from `at`, reporting its machine cycles to the Harness if
+/// `timed` is set.
+pub(crate) fn dispatch<B: Harness + ?Sized>(op: Op, at: u16, chip: &mut State, board: &mut B, timed: bool) -> OpOutcome {
+    if timed { timing::execute(op, at, chip, board, false) } else { op.execute_on(chip, board) }
 }
 
 fn subtract(base: u8, by: u8) -> (u8, bool, bool) {
@@ -0,0 +1,330 @@
+use crate::prelude::{*, fmt::{self, Display, Formatter}};
+use crate::chip::access::{*, Word::*, Internal::*, Double::*};
+use super::{Op, Op::*, Test, Flag::*};
+
+/// The assembly language dialect used to render operations as text. The 8080's own Intel
+/// mnemonics are the default; Zilog mnemonics render the same operations the way the Z80
+/// documentation spells the 8080 subset of its instruction set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// Intel mnemonics, such as `MVI B,03H` or `MOV A,M`.
+    #[default]
+    Intel,
+    /// Zilog mnemonics, such as `LD B,03h` or `LD A,(HL)`.
+    Zilog,
+}
+
+/// A value paired with the syntax it should be written in; this is what `Op::formatted` and
+/// `Line::formatted` return, so the same value can be printed in either dialect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Formatted<T> {
+    pub item: T,
+    pub syntax: Syntax,
+}
+
+/// A value written as a hexadecimal constant: upper-case digits, an `H` (Intel) or `h` (Zilog)
+/// suffix and a leading `0` when the first digit would otherwise be a letter (`0FFH`, `1234h`).
+struct Hex<T>(T, Syntax);
+
+impl Syntax {
+    fn suffix(self) -> char {
+        match self {
+            Self::Intel => 'H',
+            Self::Zilog => 'h',
+        }
+    }
+}
+
+impl Display for Hex<raw::u8> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let pad = if self.0 >= 0xA0 { "0" } else { "" };
+        write!(f, "{pad}{:02X}{}", self.0, self.1.suffix())
+    }
+}
+
+impl Display for Hex<raw::u16> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let pad = if self.0 >= 0xA000 { "0" } else { "" };
+        write!(f, "{pad}{:04X}{}", self.0, self.1.suffix())
+    }
+}
+
+fn register(name: Register) -> &'static str {
+    match name {
+        Register::A => "A",
+        Register::B => "B",
+        Register::C => "C",
+        Register::D => "D",
+        Register::E => "E",
+        Register::H => "H",
+        Register::L => "L",
+    }
+}
+
+/// Operand text for a byte location; the memory byte addressed by `HL` is `M` in Intel syntax
+/// and `(HL)` in Zilog syntax.
+struct Location(Byte, Syntax);
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1) {
+            (Byte::Single(name), _) => f.write_str(register(name)),
+            (Byte::Indirect, Syntax::Intel) => f.write_str("M"),
+            (Byte::Indirect, Syntax::Zilog) => f.write_str("(HL)"),
+            (Byte::RAM(address), Syntax::Intel) => Hex(address.0, Syntax::Intel).fmt(f),
+            (Byte::RAM(address), Syntax::Zilog) => write!(f, "({})", Hex(address.0, Syntax::Zilog)),
+        }
+    }
+}
+
+fn pair(name: Double, syntax: Syntax) -> &'static str {
+    match (name, syntax) {
+        (BC, Syntax::Intel) => "B",
+        (DE, Syntax::Intel) => "D",
+        (HL, Syntax::Intel) => "H",
+        (BC, Syntax::Zilog) => "BC",
+        (DE, Syntax::Zilog) => "DE",
+        (HL, Syntax::Zilog) => "HL",
+    }
+}
+
+fn wide(name: Internal, syntax: Syntax) -> &'static str {
+    match name {
+        Wide(name) => pair(name, syntax),
+        StackPointer => "SP",
+        ProgramCounter => "PC",
+    }
+}
+
+fn stacked(name: Word, syntax: Syntax) -> &'static str {
+    match (name, syntax) {
+        (OnBoard(name), _) => wide(name, syntax),
+        (ProgramStatus, Syntax::Intel) => "PSW",
+        (ProgramStatus, Syntax::Zilog) => "AF",
+        (Word::RAM(_) | Stack, _) => "?",
+    }
+}
+
+fn condition(test: Test) -> &'static str {
+    match test {
+        Test::Not(Zero) => "NZ",
+        Test::Is(Zero) => "Z",
+        Test::Not(Carry) => "NC",
+        Test::Is(Carry) => "C",
+        Test::Not(EvenParity) => "PO",
+        Test::Is(EvenParity) => "PE",
+        Test::Not(Negative) => "P",
+        Test::Is(Negative) => "M",
+    }
+}
+
+impl Op {
+    /// Pairs the operation with an assembly syntax for printing, as in
+    /// `println!("{}", op.formatted(Syntax::Zilog))`.
+    pub fn formatted(self, syntax: Syntax) -> Formatted<Op> {
+        Formatted { item: self, syntax }
+    }
+
+    fn intel(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let at = |byte| Location(byte, Syntax::Intel);
+        let byte = |value: u8| Hex(value.0, Syntax::Intel);
+        let word = |value: u16| Hex(value.0, Syntax::Intel);
+        let wide = |name| wide(name, Syntax::Intel);
+        match *self {
+            NOP(_) => f.write_str("NOP"),
+            Add { from, carry } => write!(f, "{} {}", if carry { "ADC" } else { "ADD" }, at(from)),
+            AddTo { value, carry } => write!(f, "{} {}", if carry { "ACI" } else { "ADI" }, byte(value)),
+            And { from } => write!(f, "ANA {}", at(from)),
+            AndWith { value } => write!(f, "ANI {}", byte(value)),
+            Call { sub } => write!(f, "CALL {}", word(sub)),
+            CallIf(test, sub) => write!(f, "C{} {}", condition(test), word(sub)),
+            CarryFlag(set) => f.write_str(if set { "STC" } else { "CMC" }),
+            Compare { from } => write!(f, "CMP {}", at(from)),
+            CompareWith { value } => write!(f, "CPI {}", byte(value)),
+            ComplementAccumulator => f.write_str("CMA"),
+            DecimalAddAdjust => f.write_str("DAA"),
+            DecrementByte { register } => write!(f, "DCR {}", at(register)),
+            DecrementWord { register } => write!(f, "DCX {}", wide(register)),
+            Interrupts(enabled) => f.write_str(if enabled { "EI" } else { "DI" }),
+            DoubleAdd { register } => write!(f, "DAD {}", wide(register)),
+            ExchangeDoubleWithHilo => f.write_str("XCHG"),
+            ExchangeTopWithHilo => f.write_str("XTHL"),
+            ExclusiveOr { from } => write!(f, "XRA {}", at(from)),
+            ExclusiveOrWith { value } => write!(f, "XRI {}", byte(value)),
+            Halt => f.write_str("HLT"),
+            In(port) => write!(f, "IN {}", Hex(port, Syntax::Intel)),
+            IncrementByte { register } => write!(f, "INR {}", at(register)),
+            IncrementWord { register } => write!(f, "INX {}", wide(register)),
+            Jump { to } => write!(f, "JMP {}", word(to)),
+            JumpIf(test, to) => write!(f, "J{} {}", condition(test), word(to)),
+            LoadAccumulator { address } => write!(f, "LDA {}", word(address)),
+            LoadAccumulatorIndirect { register } => write!(f, "LDAX {}", pair(register, Syntax::Intel)),
+            LoadExtendedWith { to, value } => write!(f, "LXI {},{}", wide(to), word(value)),
+            LoadHilo { address } => write!(f, "LHLD {}", word(address)),
+            Move { to, from } => write!(f, "MOV {},{}", at(to), at(from)),
+            MoveData { value, to } => write!(f, "MVI {},{}", at(to), byte(value)),
+            Or { from } => write!(f, "ORA {}", at(from)),
+            OrWith { value } => write!(f, "ORI {}", byte(value)),
+            Out(port) => write!(f, "OUT {}", Hex(port, Syntax::Intel)),
+            Pop(target) => write!(f, "POP {}", stacked(target, Syntax::Intel)),
+            ProgramCounterFromHilo => f.write_str("PCHL"),
+            Push(source) => write!(f, "PUSH {}", stacked(source, Syntax::Intel)),
+            Reset { vector } => write!(f, "RST {vector}"),
+            Return => f.write_str("RET"),
+            ReturnIf(test) => write!(f, "R{}", condition(test)),
+            RotateLeftCarrying => f.write_str("RLC"),
+            RotateRightCarrying => f.write_str("RRC"),
+            RotateAccumulatorLeft => f.write_str("RAL"),
+            RotateAccumulatorRight => f.write_str("RAR"),
+            StackPointerFromHilo => f.write_str("SPHL"),
+            StoreAccumulator { address } => write!(f, "STA {}", word(address)),
+            StoreAccumulatorIndirect { register } => write!(f, "STAX {}", pair(register, Syntax::Intel)),
+            StoreHilo { address } => write!(f, "SHLD {}", word(address)),
+            Subtract { from, carry } => write!(f, "{} {}", if carry { "SBB" } else { "SUB" }, at(from)),
+            SubtractBy { value, carry } => write!(f, "{} {}", if carry { "SBI" } else { "SUI" }, byte(value)),
+        }
+    }
+
+    fn zilog(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let at = |byte| Location(byte, Syntax::Zilog);
+        let byte = |value: u8| Hex(value.0, Syntax::Zilog);
+        let word = |value: u16| Hex(value.0, Syntax::Zilog);
+        let wide = |name| wide(name, Syntax::Zilog);
+        let pair = |name| pair(name, Syntax::Zilog);
+        match *self {
+            NOP(_) => f.write_str("NOP"),
+            Add { from, carry } => write!(f, "{} A,{}", if carry { "ADC" } else { "ADD" }, at(from)),
+            AddTo { value, carry } => write!(f, "{} A,{}", if carry { "ADC" } else { "ADD" }, byte(value)),
+            And { from } => write!(f, "AND {}", at(from)),
+            AndWith { value } => write!(f, "AND {}", byte(value)),
+            Call { sub } => write!(f, "CALL {}", word(sub)),
+            CallIf(test, sub) => write!(f, "CALL {},{}", condition(test), word(sub)),
+            CarryFlag(set) => f.write_str(if set { "SCF" } else { "CCF" }),
+            Compare { from } => write!(f, "CP {}", at(from)),
+            CompareWith { value } => write!(f, "CP {}", byte(value)),
+            ComplementAccumulator => f.write_str("CPL"),
+            DecimalAddAdjust => f.write_str("DAA"),
+            DecrementByte { register } => write!(f, "DEC {}", at(register)),
+            DecrementWord { register } => write!(f, "DEC {}", wide(register)),
+            Interrupts(enabled) => f.write_str(if enabled { "EI" } else { "DI" }),
+            DoubleAdd { register } => write!(f, "ADD HL,{}", wide(register)),
+            ExchangeDoubleWithHilo => f.write_str("EX DE,HL"),
+            ExchangeTopWithHilo => f.write_str("EX (SP),HL"),
+            ExclusiveOr { from } => write!(f, "XOR {}", at(from)),
+            ExclusiveOrWith { value } => write!(f, "XOR {}", byte(value)),
+            Halt => f.write_str("HALT"),
+            In(port) => write!(f, "IN A,({})", Hex(port, Syntax::Zilog)),
+            IncrementByte { register } => write!(f, "INC {}", at(register)),
+            IncrementWord { register } => write!(f, "INC {}", wide(register)),
+            Jump { to } => write!(f, "JP {}", word(to)),
+            JumpIf(test, to) => write!(f, "JP {},{}", condition(test), word(to)),
+            LoadAccumulator { address } => write!(f, "LD A,({})", word(address)),
+            LoadAccumulatorIndirect { register } => write!(f, "LD A,({})", pair(register)),
+            LoadExtendedWith { to, value } => write!(f, "LD {},{}", wide(to), word(value)),
+            LoadHilo { address } => write!(f, "LD HL,({})", word(address)),
+            Move { to, from } => write!(f, "LD {},{}", at(to), at(from)),
+            MoveData { value, to } => write!(f, "LD {},{}", at(to), byte(value)),
+            Or { from } => write!(f, "OR {}", at(from)),
+            OrWith { value } => write!(f, "OR {}", byte(value)),
+            Out(port) => write!(f, "OUT ({}),A", Hex(port, Syntax::Zilog)),
+            Pop(target) => write!(f, "POP {}", stacked(target, Syntax::Zilog)),
+            ProgramCounterFromHilo => f.write_str("JP (HL)"),
+            Push(source) => write!(f, "PUSH {}", stacked(source, Syntax::Zilog)),
+            Reset { vector } => write!(f, "RST {}", Hex(vector << 3, Syntax::Zilog)),
+            Return => f.write_str("RET"),
+            ReturnIf(test) => write!(f, "RET {}", condition(test)),
+            RotateLeftCarrying => f.write_str("RLCA"),
+            RotateRightCarrying => f.write_str("RRCA"),
+            RotateAccumulatorLeft => f.write_str("RLA"),
+            RotateAccumulatorRight => f.write_str("RRA"),
+            StackPointerFromHilo => f.write_str("LD SP,HL"),
+            StoreAccumulator { address } => write!(f, "LD ({}),A", word(address)),
+            StoreAccumulatorIndirect { register } => write!(f, "LD ({}),A", pair(register)),
+            StoreHilo { address } => write!(f, "LD ({}),HL", word(address)),
+            Subtract { from, carry: false } => write!(f, "SUB {}", at(from)),
+            Subtract { from, carry: true } => write!(f, "SBC A,{}", at(from)),
+            SubtractBy { value, carry: false } => write!(f, "SUB {}", byte(value)),
+            SubtractBy { value, carry: true } => write!(f, "SBC A,{}", byte(value)),
+        }
+    }
+}
+
+/// Formats the operation as canonical Intel 8080 assembly, such as `MVI B,03H` or `JNZ 0100H`.
+/// Use `Op::formatted` to choose another syntax.
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.intel(f)
+    }
+}
+
+impl Display for Formatted<Op> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.syntax {
+            Syntax::Intel => self.item.intel(f),
+            Syntax::Zilog => self.item.zilog(f),
+        }
+    }
+}
+
+/// One decoded line of a disassembly listing: the address of the instruction, the raw bytes
+/// it was decoded from and the operation itself. Bytes that don't begin a valid instruction
+/// are reported one at a time with no operation, and are listed as `DB` data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line<'a> {
+    pub address: raw::u16,
+    pub bytes: &'a [raw::u8],
+    pub op: Option<Op>,
+}
+
+impl<'a> Line<'a> {
+    /// Pairs the line with an assembly syntax for printing its mnemonic.
+    pub fn formatted(self, syntax: Syntax) -> Formatted<Line<'a>> {
+        Formatted { item: self, syntax }
+    }
+}
+
+/// Formats the line as `address  bytes  mnemonic`, such as `0100  06 03     MVI B,03H`.
+impl Display for Line<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.formatted(Syntax::Intel).fmt(f)
+    }
+}
+
+impl Display for Formatted<Line<'_>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Line { address, bytes, op } = self.item;
+        write!(f, "{address:04X} ")?;
+        for byte in bytes {
+            write!(f, " {byte:02X}")?;
+        }
+        for _ in bytes.len()..3 {
+            f.write_str("   ")?;
+        }
+        match op {
+            Some(op) => write!(f, "  {}", op.formatted(self.syntax)),
+            None => {
+                let data = match self.syntax { Syntax::Intel => "DB", Syntax::Zilog => "DEFB" };
+                write!(f, "  {data} {}", Hex(bytes[0], self.syntax))
+            }
+        }
+    }
+}
+
+/// Decodes a block of machine code that is loaded at `origin`, producing one `Line` per
+/// instruction in address order. Decoding never stops early: undefined opcodes and instructions
+/// truncated by the end of the block are reported as single data bytes.
+#[cfg(any(feature="open", doc, test))]
+pub fn disassemble(bytes: &[raw::u8], origin: raw::u16) -> impl Iterator<Item = Line<'_>> {
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        let rest = bytes.get(offset..).filter(|rest| !rest.is_empty())?;
+        let (op, len) = match Op::extract(rest.iter().copied().map(Wrapping)) {
+            Ok((op, len)) => (Some(op), len),
+            Err(_) => (None, 1),
+        };
+        debug_assert!(op.is_none_or(|op| op.len() as usize == len));
+        let line = Line { address: origin.wrapping_add(offset as raw::u16), bytes: &rest[..len], op };
+        offset += len;
+        Some(line)
+    })
+}
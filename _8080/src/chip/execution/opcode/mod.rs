@@ -1,6 +1,14 @@
 use crate::prelude::{*, convert::TryFrom, fmt::UpperHex};
 use crate::chip::access::{*, Byte::*, Register::*, Word::*, Double::*, Internal::*};
 
+mod disassembly;
+#[cfg(any(feature="open", doc, test))]
+pub use disassembly::{disassemble, Line, Syntax};
+#[cfg(not(any(feature="open", doc, test)))]
+pub(crate) use disassembly::Line;
+#[cfg(any(feature="open", doc))]
+pub use disassembly::Formatted;
+
 /// A single action on the processor. See the 8080 Programmer's manual for details and operation effects.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Op {
@@ -101,9 +109,9 @@ impl From<Byte> for raw::u8 {
             Byte::Indirect => 6,
             Single(A) => 7,
             #[cfg(target_endian="little")]
-            Single(reg) => reg as raw::u8,
-            #[cfg(target_endian="big")]
             Single(reg) => reg as raw::u8 ^ 0x01,
+            #[cfg(target_endian="big")]
+            Single(reg) => reg as raw::u8,
             Byte::RAM(_) => panic!("No encoding for direct RAM references"),
         }
     }
@@ -463,13 +471,13 @@ impl Op {
         use Op::*;
         match self {
             Call{..} | CallIf(..) | Jump{..} | JumpIf(..) | LoadExtendedWith{..} | 
-            ReturnIf(..) | StoreAccumulator{..} | LoadAccumulator {..} | LoadHilo{..} | StoreHilo {..}
+            StoreAccumulator{..} | LoadAccumulator {..} | LoadHilo{..} | StoreHilo {..}
                 => 3,
             AddTo{..} | AndWith{..} | ExclusiveOrWith{..} | OrWith{..} | SubtractBy{..} | CompareWith{..} | MoveData{..} |
             Out(..) | In(..)
                 => 2,
             NOP(..) | Push(..) | Reset{..} | ExchangeDoubleWithHilo | Return | Halt | Pop(..) | ExchangeTopWithHilo | 
-            Move{..} | RotateLeftCarrying | RotateRightCarrying | RotateAccumulatorLeft | RotateAccumulatorRight | 
+            Move{..} | ReturnIf(..) | RotateLeftCarrying | RotateRightCarrying | RotateAccumulatorLeft | RotateAccumulatorRight | 
             IncrementByte {..} | DecrementByte {..} | Add{..}  | Subtract{..} | And{..} | ExclusiveOr{..} | Or{..} | 
             Compare{..} | IncrementWord{..} | DecrementWord {..} | Interrupts(..) | 
             LoadAccumulatorIndirect {..} | StoreAccumulatorIndirect{..} | 
@@ -541,7 +549,7 @@ impl Into<[raw::u8;4]> for Op {
                 }
             Call { sub } => { let address = sub.0.to_le_bytes(); [ 3, b11111111::Call, address[0], address[1] ]}
             CallIf(test, sub) 
-                => { let address = sub.0.to_le_bytes(); [ 3, b11_000_111::CallIf | (u8::from(test) << 3), address[0], address[1] ]}
+                => { let address = sub.0.to_le_bytes(); [ 3, b11_000_111::CallIf | u8::from(test), address[0], address[1] ]}
             CarryFlag(set) => [ 1, if set { b11111111::SetCarry } else { b11111111::ComplementCarry }, 0, 0 ],
             ComplementAccumulator => [ 1, b11111111::ComplementAccumulator, 0, 0 ],
             DecimalAddAdjust => [ 1, b11111111::DecimalAddAdjust, 0, 0 ],
@@ -557,7 +565,7 @@ impl Into<[raw::u8;4]> for Op {
             Interrupts(accepted) => [ 1, if accepted { b11111111:: EnableInterrupts } else { b11111111::DisableInterrupts }, 0, 0 ],
             Jump { to } => { let bytes = to.0.to_le_bytes(); [ 3, b11111111::Jump, bytes[0], bytes[1] ] }
             JumpIf(test, to) 
-                => { let bytes = to.0.to_le_bytes(); [ 3, b11_000_111::JumpIf | (u8::from(test) << 4), bytes[0], bytes[1] ]}
+                => { let bytes = to.0.to_le_bytes(); [ 3, b11_000_111::JumpIf | u8::from(test), bytes[0], bytes[1] ]}
             LoadAccumulator { address } => { let bytes = address.0.to_le_bytes(); [ 3, b11111111::LoadAccumulatorDirect, bytes[0], bytes[1] ] }
             LoadAccumulatorIndirect { register } => [ 1, b111_0_1111::LoadAccumulatorIndirect | ((u8::from(OnBoard(Wide(register))) & 0x01) << 4), 0, 0 ],
             LoadExtendedWith { to, value } 
@@ -572,7 +580,7 @@ impl Into<[raw::u8;4]> for Op {
             Push(source) => [ 1, b11_00_1111::Push | (u8::from(source) << 4), 0, 0 ],
             Reset { vector } => [ 1, b11_000_111::Reset | (vector << 3), 0, 0 ],
             Return => [ 1, b11111111::Return, 0, 0 ],
-            ReturnIf(test) => [ 1, b11_000_111::ReturnIf | (u8::from(test) << 3), 0, 0 ],
+            ReturnIf(test) => [ 1, b11_000_111::ReturnIf | u8::from(test), 0, 0 ],
             RotateAccumulatorLeft => [ 1, b11111111::RotateAccumulatorLeft, 0, 0 ],
             RotateAccumulatorRight => [ 1, b11111111::RotateAccumulatorRight, 0, 0 ],
             RotateLeftCarrying => [ 1, b11111111::RotateLeftCarrying, 0, 0 ],
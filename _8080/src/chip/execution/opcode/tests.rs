@@ -247,4 +247,73 @@ fn all() {
             Err(err) => panic!("{err:X}"),
         };
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn mnemonics() {
+    use crate::string::ToString;
+    let text = |code: &[raw::u8]| decode(code).unwrap().0.to_string();
+    assert_eq!(text(&[0x06, 0x03]), "MVI B,03H");
+    assert_eq!(text(&[0x36, 0xFF]), "MVI M,0FFH");
+    assert_eq!(text(&[0x7E]), "MOV A,M");
+    assert_eq!(text(&[0x31, 0x00, 0xF0]), "LXI SP,0F000H");
+    assert_eq!(text(&[0xC2, 0x34, 0x12]), "JNZ 1234H");
+    assert_eq!(text(&[0xE4, 0x00, 0x01]), "CPO 0100H");
+    assert_eq!(text(&[0xF8]), "RM");
+    assert_eq!(text(&[0xF5]), "PUSH PSW");
+    assert_eq!(text(&[0x1A]), "LDAX D");
+    assert_eq!(text(&[0x39]), "DAD SP");
+    assert_eq!(text(&[0xD7]), "RST 2");
+    assert_eq!(text(&[0xDB, 0x10]), "IN 10H");
+    assert_eq!(text(&[0x9E]), "SBB M");
+    assert_eq!(text(&[0x3F]), "CMC");
+}
+
+#[test]
+fn listing() {
+    use crate::string::ToString;
+    let code = [0x3E, 0x0D, 0xC8, 0x08, 0xCD, 0x05, 0x00, 0xC3];
+    let lines: crate::vec::Vec<_> = disassemble(&code, 0x0100).collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[1].address, 0x0102);
+    assert_eq!(lines[1].op, Some(ReturnIf(Is(Zero))));
+    assert_eq!(lines[2].op, None);
+    assert_eq!(lines[3].bytes, &[0xCD, 0x05, 0x00]);
+    assert_eq!(lines[0].to_string(), "0100  3E 0D     MVI A,0DH");
+    assert_eq!(lines[2].to_string(), "0103  08        DB 08H");
+    assert_eq!(lines[3].to_string(), "0104  CD 05 00  CALL 0005H");
+    assert_eq!(lines[4].to_string(), "0107  C3        DB 0C3H");
+}
+
+#[test]
+fn zilog() {
+    use crate::string::ToString;
+    let text = |code: &[raw::u8]| decode(code).unwrap().0.formatted(Syntax::Zilog).to_string();
+    assert_eq!(text(&[0x06, 0x03]), "LD B,03h");
+    assert_eq!(text(&[0x7E]), "LD A,(HL)");
+    assert_eq!(text(&[0x3A, 0x00, 0xC0]), "LD A,(0C000h)");
+    assert_eq!(text(&[0x12]), "LD (DE),A");
+    assert_eq!(text(&[0x22, 0x34, 0x12]), "LD (1234h),HL");
+    assert_eq!(text(&[0xCA, 0x00, 0x01]), "JP Z,0100h");
+    assert_eq!(text(&[0xE9]), "JP (HL)");
+    assert_eq!(text(&[0xF1]), "POP AF");
+    assert_eq!(text(&[0x29]), "ADD HL,HL");
+    assert_eq!(text(&[0xCE, 0x01]), "ADC A,01h");
+    assert_eq!(text(&[0x96]), "SUB (HL)");
+    assert_eq!(text(&[0xDF]), "RST 18h");
+    assert_eq!(text(&[0xD3, 0xFE]), "OUT (0FEh),A");
+    assert_eq!(text(&[0x17]), "RLA");
+    let code = [0x76, 0xDD];
+    let lines: crate::vec::Vec<_> = disassemble(&code, 0).map(|line| line.formatted(Syntax::Zilog).to_string()).collect();
+    assert_eq!(lines, ["0000  76        HALT", "0001  DD        DEFB 0DDh"]);
+}
+
+#[test]
+fn encode() {
+    for code in 0u8..=255u8 {
+        if let Ok((op, len)) = decode(&[code, 0x34, 0x12]) {
+            let bytes: [raw::u8;4] = op.into();
+            assert_eq!(&bytes[..=len], &[len as raw::u8, code, 0x34, 0x12][..=len], "{op:?}");
+        }
+    }
+}
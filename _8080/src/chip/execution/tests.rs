@@ -1,5 +1,5 @@
 use super::*;
-use crate::SimpleBoard;
+use crate::{SimpleBoard, vec::Vec};
 use core::cell::UnsafeCell;
 
 use opcode::{Test::*, Flag::*};
@@ -508,4 +508,124 @@ fn internals() {
     assert!(!chip.c);
     Interrupts(false).execute_on(&mut chip, &mut env).unwrap();
     assert!(!chip.interrupts);
-}
\ No newline at end of file
+}
+
+#[test]
+fn interrupt_call() {
+    let mut machine = Machine::new(SimpleBoard::default());
+    machine[0x0000] = Wrapping(0xFB);
+    assert!(!machine.call_to(0x1234));
+    let _ = machine.execute();
+    assert!(matches!(machine.interrupt(Jump{to: Wrapping(0x1234)}), Err(opcode::Error::NotUsable(_))));
+    assert!(machine.call_to(0x1234));
+    assert_eq!(machine.chip.pc.0, 0x1234);
+    assert_eq!(machine.read_word(Wrapping(0xFFFE)).0, 0x0001);
+    assert!(!machine.call_to(0x2000));
+}
+
+struct Probe {
+    board: SimpleBoard,
+    cycles: Vec<timing::BusCycle>,
+    waits: raw::u8,
+}
+
+impl Probe {
+    fn new(program: &[raw::u8]) -> Self {
+        let mut board = SimpleBoard::default();
+        for (index, byte) in program.iter().enumerate() { board[index as raw::u16] = Wrapping(*byte); }
+        Self { board, cycles: Vec::new(), waits: 0 }
+    }
+
+    /// The kind, address, data, offset and length of each cycle reported since the last call.
+    fn reported(&mut self) -> Vec<(timing::CycleKind, raw::u16, raw::u8, raw::u8, raw::u8)> {
+        self.cycles.drain(..).map(|cycle| (cycle.kind, cycle.address, cycle.data, cycle.offset, cycle.states)).collect()
+    }
+}
+
+impl Harness for Probe {
+    fn read(&self, from: u16) -> u8 { self.board.read(from) }
+    fn write(&mut self, to: u16, value: u8) { self.board.write(to, value) }
+    fn input(&mut self, port: raw::u8) -> u8 { self.board.input(port) }
+    fn output(&mut self, port: raw::u8, value: u8) { self.board.output(port, value) }
+    fn bus_cycle(&mut self, cycle: timing::BusCycle) -> raw::u8 {
+        self.cycles.push(cycle);
+        if matches!(cycle.kind, timing::CycleKind::Write | timing::CycleKind::StackWrite) { self.waits } else { 0 }
+    }
+}
+
+#[test]
+fn bus_cycles() {
+    use timing::CycleKind::*;
+    let mut machine = Machine::new(Probe::new(&[
+        0x31, 0x00, 0x01,   // LXI SP,0100H
+        0xCD, 0x10, 0x00,   // CALL 0010H
+    ]));
+    machine.board.board[0x0010] = Wrapping(0x09);  // DAD B
+    machine.board.board[0x0011] = Wrapping(0xD3);  // OUT 42H
+    machine.board.board[0x0012] = Wrapping(0x42);
+    machine.set_cycle_accurate(true);
+    let _ = machine.execute();
+    assert_eq!(machine.reported(), [(Fetch, 0x0000, 0x31, 0, 4), (Read, 0x0001, 0x00, 4, 3), (Read, 0x0002, 0x01, 7, 3)]);
+    let _ = machine.execute();
+    assert_eq!(machine.reported(), [
+        (Fetch, 0x0003, 0xCD, 0, 5), (Read, 0x0004, 0x10, 5, 3), (Read, 0x0005, 0x00, 8, 3),
+        (StackWrite, 0x00FF, 0x00, 11, 3), (StackWrite, 0x00FE, 0x06, 14, 3),
+    ]);
+    let _ = machine.execute();
+    assert_eq!(machine.reported(), [(Fetch, 0x0010, 0x09, 0, 4), (Internal, 0, 0, 4, 3), (Internal, 0, 0, 7, 3)]);
+    machine.chip[A] = Wrapping(0x5A);
+    let _ = machine.execute();
+    assert_eq!(machine.reported(), [(Fetch, 0x0011, 0xD3, 0, 4), (Read, 0x0012, 0x42, 4, 3), (Output, 0x4242, 0x5A, 7, 3)]);
+    machine.set_cycle_accurate(false);
+    let _ = machine.execute();
+    assert!(machine.reported().is_empty());
+}
+
+#[test]
+fn wait_states() {
+    use timing::CycleKind::*;
+    let mut machine = Machine::new(Probe::new(&[
+        0x77,   // MOV M,A
+        0xFB,   // EI
+    ]));
+    machine.chip[HL] = Wrapping(0x2000);
+    machine.chip.sp = Wrapping(0x0100);
+    machine.waits = 2;
+    machine.set_cycle_accurate(true);
+    let _ = machine.execute();
+    assert_eq!(machine.clock(), 9);
+    assert_eq!(machine.reported(), [(Fetch, 0x0000, 0x77, 0, 4), (Write, 0x2000, 0x00, 4, 3)]);
+    let _ = machine.execute();
+    let _ = machine.reported();
+    let clock = machine.clock();
+    assert!(matches!(machine.reset_to(7), Ok(true)));
+    assert_eq!(machine.clock() - clock, 11 + 4);
+    assert_eq!(machine.reported(), [
+        (Acknowledge, 0x0002, 0xFF, 0, 5), (StackWrite, 0x00FF, 0x00, 5, 3), (StackWrite, 0x00FE, 0x02, 10, 3),
+    ]);
+}
+
+#[test]
+fn cycle_totals() {
+    for code in 0..=raw::u8::MAX {
+        let program = [code, 0x34, 0x12];
+        if Op::extract(program.map(Wrapping)).is_err() { continue; }
+        let mut plain = Machine::new(SimpleBoard::default());
+        let mut timed = Machine::new(Probe::new(&program));
+        for (index, byte) in program.into_iter().enumerate() { plain[index as raw::u16] = Wrapping(byte); }
+        for machine in [&mut plain.chip, &mut timed.chip] {
+            machine.sp = Wrapping(0x2000);
+            machine[HL] = Wrapping(0x3000);
+        }
+        timed.set_cycle_accurate(true);
+        let (_, _) = (plain.execute(), timed.execute());
+        let expected = plain.clock() as raw::u8;
+        assert_eq!(timed.clock(), plain.clock(), "{code:02X}");
+        let mut offset = 0;
+        for cycle in timed.reported() {
+            assert_eq!(cycle.3, offset, "{code:02X}");
+            offset += cycle.4;
+        }
+        assert_eq!(offset, expected, "{code:02X}");
+    }
+}
@@ -0,0 +1,193 @@
+//! T-state-accurate execution, which reports each machine cycle of an instruction to the
+//! Harness as it happens.
+//!
+//! The 8080 divides every instruction into machine cycles of 3 to 5 T-states (periods of its
+//! clock), and most of them move one byte over the bus: the first fetches the opcode, and the
+//! others read operands, read and write memory or the stack, or transfer a byte through a port.
+//! A Harness that shares the bus with something else, such as video circuits or a DMA
+//! controller, needs to know when each of those transfers happens within the instruction, and
+//! may need to hold the processor with wait states while the bus is busy. `execute` only says
+//! how many T-states a whole instruction took, so a `Machine` in T-state-accurate mode also
+//! calls `Harness::bus_cycle` for each machine cycle, in order.
+
+use crate::prelude::*;
+use core::{cell::{Cell, RefCell}, num::NonZeroU8};
+use super::{OpOutcome, opcode::{Op, Op::*}};
+use crate::chip::access::Byte::Single;
+
+/// What a machine cycle does on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleKind {
+    /// The first cycle of an instruction, which fetches its opcode from memory.
+    Fetch,
+    /// A read from memory, of an operand or of data.
+    Read,
+    /// A write to memory.
+    Write,
+    /// A read from the stack.
+    StackRead,
+    /// A write to the stack.
+    StackWrite,
+    /// A read from an input port, whose number is on both halves of the address bus.
+    Input,
+    /// A write to an output port, whose number is on both halves of the address bus.
+    Output,
+    /// A byte of an instruction supplied by an interrupting device instead of memory. The
+    /// program counter is on the address bus, but memory isn't read.
+    Acknowledge,
+    /// The cycle in which the processor halts, with the address of the next instruction on
+    /// the address bus.
+    Halt,
+    /// A cycle spent inside the processor, which leaves the bus idle.
+    Internal,
+}
+
+/// One machine cycle of an instruction, as reported to `Harness::bus_cycle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusCycle {
+    pub kind: CycleKind,
+    /// The address on the bus, or 0 for an internal cycle.
+    pub address: raw::u16,
+    /// The byte transferred, or 0 for a cycle that doesn't transfer one.
+    pub data: raw::u8,
+    /// The T-states from the start of the instruction to the start of the cycle, including any
+    /// wait states inserted before it.
+    pub offset: raw::u8,
+    /// The T-states the cycle takes, not counting wait states.
+    pub states: raw::u8,
+}
+
+/// The T-states of the opcode fetch, which takes 5 instead of 4 when the instruction has
+/// internal work to do before its next cycle.
+fn opening(op: Op) -> raw::u8 {
+    match op {
+        Move{to: Single(_), from: Single(_)} | IncrementByte{register: Single(_)} | DecrementByte{register: Single(_)} |
+        IncrementWord{..} | DecrementWord{..} | StackPointerFromHilo | ProgramCounterFromHilo | ExchangeDoubleWithHilo |
+        ReturnIf(..) | Reset{..} | Push(..) | Call{..} | CallIf(..)
+            => 5,
+        _ => 4,
+    }
+}
+
+/// A Harness that passes every access on to the board it wraps, reporting each as a machine
+/// cycle after it has been made.
+struct Timed<'a, H: Harness + ?Sized> {
+    board: RefCell<&'a mut H>,
+    /// Whether the instruction's memory accesses go to the stack.
+    stack: bool,
+    /// Whether the instruction is `XTHL`, whose last write takes 5 T-states.
+    exchange: bool,
+    /// The T-states so far, with and without wait states.
+    offset: Cell<raw::u8>,
+    states: Cell<raw::u8>,
+}
+
+impl<H: Harness + ?Sized> Timed<'_, H> {
+    fn report(&self, kind: CycleKind, address: raw::u16, data: raw::u8, states: raw::u8) {
+        let offset = self.offset.get();
+        let waits = RefCell::borrow_mut(&self.board).bus_cycle(BusCycle { kind, address, data, offset, states });
+        self.offset.set(offset.saturating_add(states).saturating_add(waits));
+        self.states.set(self.states.get().saturating_add(states));
+    }
+
+    fn store(&mut self, to: u16, value: u8, states: raw::u8) {
+        self.board.get_mut().write(to, value);
+        let kind = if self.stack { CycleKind::StackWrite } else { CycleKind::Write };
+        self.report(kind, to.0, value.0, states);
+    }
+
+    /// Reports the cycles after the last transfer, which the processor spends halting or
+    /// working inside, and returns the T-states taken with wait states.
+    fn finish(&self, op: Op, chip: &State, cycles: raw::u8) -> NonZeroU8 {
+        let mut left = cycles.saturating_sub(self.states.get());
+        if op == Halt && left > 0 {
+            self.report(CycleKind::Halt, chip.pc.0, 0, left);
+        }
+        while op != Halt && left > 0 {
+            let states = if left >= 6 { 3 } else { left };
+            self.report(CycleKind::Internal, 0, 0, states);
+            left -= states;
+        }
+        NonZeroU8::new(self.offset.get().max(cycles)).unwrap_or(NonZeroU8::MIN)
+    }
+}
+
+impl<H: Harness + ?Sized> Harness for Timed<'_, H> {
+    fn read(&self, from: u16) -> u8 {
+        let value = RefCell::borrow(&self.board).read(from);
+        let kind = if self.stack { CycleKind::StackRead } else { CycleKind::Read };
+        self.report(kind, from.0, value.0, 3);
+        value
+    }
+
+    fn read_word(&self, from: u16) -> u16 {
+        Wrapping(raw::u16::from_le_bytes([self.read(from).0, self.read(from + Wrapping(1)).0]))
+    }
+
+    fn write(&mut self, to: u16, value: u8) { self.store(to, value, 3) }
+
+    /// Writes the bytes in the order the processor does: the stack is written from the top
+    /// down, and other memory from the bottom up.
+    fn write_word(&mut self, to: u16, value: u16) {
+        let [low, high] = value.0.to_le_bytes();
+        if self.stack {
+            self.store(to + Wrapping(1), Wrapping(high), 3);
+            self.store(to, Wrapping(low), if self.exchange { 5 } else { 3 });
+        } else {
+            self.store(to, Wrapping(low), 3);
+            self.store(to + Wrapping(1), Wrapping(high), 3);
+        }
+    }
+
+    fn input(&mut self, port: raw::u8) -> u8 {
+        let value = self.board.get_mut().input(port);
+        self.report(CycleKind::Input, raw::u16::from_le_bytes([port, port]), value.0, 3);
+        value
+    }
+
+    fn output(&mut self, port: raw::u8, value: u8) {
+        self.board.get_mut().output(port, value);
+        self.report(CycleKind::Output, raw::u16::from_le_bytes([port, port]), value.0, 3);
+    }
+}
+
+/// Executes an operation that was fetched from `at`, or supplied by an interrupting device
+/// while the program counter was `at`, reporting each of its machine cycles to the Harness.
+pub(super) fn execute<H: Harness + ?Sized>(op: Op, at: u16, chip: &mut State, board: &mut H, acknowledge: bool) -> OpOutcome {
+    let [len, code @ ..]: [raw::u8; 4] = op.into();
+    let mut timed = Timed {
+        board: RefCell::new(board),
+        stack: matches!(op, Push(..) | Pop(..) | Call{..} | CallIf(..) | Reset{..} | Return | ReturnIf(..) | ExchangeTopWithHilo),
+        exchange: op == ExchangeTopWithHilo,
+        offset: Cell::new(0),
+        states: Cell::new(0),
+    };
+    for (index, byte) in code.into_iter().take(len as usize).enumerate() {
+        let (kind, address) = match (acknowledge, index) {
+            (true, _) => (CycleKind::Acknowledge, at),
+            (false, 0) => (CycleKind::Fetch, at),
+            (false, _) => (CycleKind::Read, at + Wrapping(index as raw::u16)),
+        };
+        timed.report(kind, address.0, byte, if index == 0 { opening(op) } else { 3 });
+    }
+    let outcome = op.execute_on(chip, &mut timed);
+    #[cfg(feature="open")]
+    let cycles = outcome?;
+    #[cfg(not(feature="open"))]
+    let cycles = outcome;
+    let cycles = cycles.map(|cycles| timed.finish(op, chip, cycles.get()));
+    #[cfg(feature="open")]
+    let cycles = Ok(cycles);
+    cycles
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Turns T-state-accurate execution on or off. While it is on, `execute` and `interrupt`
+    /// report each machine cycle of an instruction to `Harness::bus_cycle` as it happens, and
+    /// add the wait states the Harness asks for to the cycles the instruction takes. It is off
+    /// to begin with, since the reports slow execution down.
+    pub fn set_cycle_accurate(&mut self, on: bool) { self.timed = on; }
+
+    /// Whether T-state-accurate execution is on.
+    pub fn is_cycle_accurate(&self) -> bool { self.timed }
+}
@@ -1,11 +1,15 @@
 use crate::prelude::*;
+use crate::snapshot::{self, Snapshot};
 
 pub mod access;
 mod execution;
-pub use execution::opcode;
+pub use execution::{opcode, timing};
+pub(crate) use execution::{dispatch, OpOutcome};
 
 /// This struct stores the internal registers and flags of the 8080 CPU.
 #[repr(C)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize), serde(into="Saved", from="Saved"))]
 #[cfg_attr(feature="open", disclose)]
 pub struct State {
 	pc: u16,
@@ -27,6 +31,65 @@ impl State {
 	}
 }
 
+/// The registers and flags of a State, named so that a saved State doesn't depend on the
+/// order the registers are stored in on the host. It is kept apart from the prelude, whose
+/// `u8` would be taken for the primitive in the code that serde derives.
+mod saved {
+	#[derive(Clone, Copy)]
+	#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+	pub(super) struct Saved {
+		pub a: u8, pub b: u8, pub c: u8, pub d: u8, pub e: u8, pub h: u8, pub l: u8,
+		pub flags: u8,
+		pub pc: u16, pub sp: u16,
+		pub active: bool, pub interrupts: bool,
+	}
+}
+use saved::Saved;
+
+impl From<State> for Saved {
+	fn from(chip: State) -> Self {
+		use access::Register::*;
+		Self {
+			a: chip[A].0, b: chip[B].0, c: chip[C].0, d: chip[D].0, e: chip[E].0, h: chip[H].0, l: chip[L].0,
+			flags: chip.flags(), pc: chip.pc.0, sp: chip.sp.0, active: chip.active, interrupts: chip.interrupts,
+		}
+	}
+}
+
+impl From<Saved> for State {
+	fn from(saved: Saved) -> Self {
+		use access::Register::*;
+		let mut chip = State::new();
+		for (register, value) in [(A, saved.a), (B, saved.b), (C, saved.c), (D, saved.d), (E, saved.e), (H, saved.h), (L, saved.l)] {
+			chip[register] = Wrapping(value);
+		}
+		chip.extract_flags(saved.flags);
+		(chip.pc, chip.sp) = (Wrapping(saved.pc), Wrapping(saved.sp));
+		(chip.active, chip.interrupts) = (saved.active, saved.interrupts);
+		chip
+	}
+}
+
+impl Snapshot for State {
+	fn save(&self, out: &mut vec::Vec<raw::u8>) {
+		let saved = Saved::from(self.clone());
+		[saved.a, saved.b, saved.c, saved.d, saved.e, saved.h, saved.l, saved.flags].save(out);
+		[saved.pc, saved.sp].save(out);
+		[saved.active, saved.interrupts].save(out);
+	}
+	fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+		let (mut bytes, mut words, mut bits) = ([0; 8], [0; 2], [false; 2]);
+		bytes.restore(data)?;
+		words.restore(data)?;
+		bits.restore(data)?;
+		let [a, b, c, d, e, h, l, flags] = bytes;
+		let [pc, sp] = words;
+		let [active, interrupts] = bits;
+		*self = Saved { a, b, c, d, e, h, l, flags, pc, sp, active, interrupts }.into();
+		Ok(())
+	}
+}
+
 #[cfg(feature="open")]
 impl<H: Harness + ?Sized, C: BorrowMut<H>> Iterator for Machine<H, C> {
 	type Item = Result<raw::u8, String>;
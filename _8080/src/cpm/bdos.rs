@@ -0,0 +1,208 @@
+use crate::prelude::{*, vec::Vec, collections::VecDeque};
+use super::{fcb, Board, Storage};
+
+/// The size of a CP/M record.
+const RECORD: usize = 128;
+/// The code CP/M uses to pad the last record of a text file.
+const END_OF_FILE: raw::u8 = 0x1A;
+
+/// The result of a file function that failed.
+const FAILED: raw::u8 = 0xFF;
+
+impl<S: Storage> Board<S> {
+    fn print(&mut self, c: raw::u8) { self.console.push(c); }
+
+    /// Answers a BDOS call, or returns `None` if it must wait for console input.
+    pub(super) fn bdos(&mut self, function: raw::u8, de: raw::u16) -> Option<raw::u16> {
+        let e = de as raw::u8;
+        let result = match function {
+            1 => {
+                let c = self.keys.pop_front()?;
+                if c.is_ascii_graphic() || b" \r\n\t\x08".contains(&c) { self.print(c); }
+                c
+            }
+            2 => { self.print(e); 0 }
+            6 if e == 0xFF => self.keys.pop_front().unwrap_or(0),
+            6 => { self.print(e); 0 }
+            9 => {
+                let mut at = de;
+                for _ in 0..0x10000 {
+                    if self.ram[at as usize] == b'$' { break; }
+                    self.print(self.ram[at as usize]);
+                    at = at.wrapping_add(1);
+                }
+                0
+            }
+            10 => return self.read_line(de),
+            11 => if self.keys.is_empty() { 0x00 } else { 0xFF },
+            12 => return Some(0x0022),
+            13 => { self.dma = 0x0080; self.drive = 0; 0 }
+            14 => { self.drive = e; 0 }
+            15..=23 | 33..=36 => self.file(function, de),
+            24 => return Some(0x0001),
+            25 => self.drive,
+            26 => { self.dma = de; 0 }
+            32 if e == 0xFF => self.user,
+            32 => { self.user = e & 0x0F; 0 }
+            _ => 0,
+        };
+        Some(result as raw::u16)
+    }
+
+    /// Reads a line into a console buffer once a whole line has been typed. Backspace and
+    /// delete remove the character before them, and text beyond the buffer's size is dropped.
+    fn read_line(&mut self, buffer: raw::u16) -> Option<raw::u16> {
+        let end = self.keys.iter().position(|&c| c == b'\r')?;
+        let mut line = Vec::new();
+        for c in self.keys.drain(..=end).take(end) {
+            match c {
+                0x08 | 0x7F => { line.pop(); }
+                c => line.push(c),
+            }
+        }
+        line.truncate(self.ram[buffer as usize] as usize);
+        self.ram[buffer.wrapping_add(1) as usize] = line.len() as raw::u8;
+        self.store(buffer.wrapping_add(2), &line);
+        self.console.extend_from_slice(&line);
+        self.print(b'\r');
+        Some(0)
+    }
+
+    /// The names on the drive that match the (possibly wildcard) name in an FCB.
+    fn matching(&self, pattern: &[raw::u8]) -> VecDeque<String> {
+        self.storage.files().into_iter()
+            .filter(|name| fcb::matches(pattern, &fcb::padded(name)))
+            .collect()
+    }
+
+    /// Writes a directory entry for a file into the DMA buffer, for the search functions.
+    fn entry(&mut self, name: &str) {
+        let size = self.storage.size(name).unwrap_or(0);
+        let mut entry = [0; 32];
+        entry[fcb::NAME].copy_from_slice(&fcb::padded(name));
+        entry[fcb::COUNT] = size.div_ceil(RECORD).min(fcb::EXTENT_RECORDS) as raw::u8;
+        self.store(self.dma, &entry);
+    }
+
+    /// Carries out a file function on the FCB at `address`.
+    fn file(&mut self, function: raw::u8, address: raw::u16) -> raw::u8 {
+        let mut block = self.bytes(address, fcb::SIZE);
+        let name = fcb::name(&block[fcb::NAME]);
+        let result = match function {
+            15 => match self.storage.size(&name) {
+                Some(size) => {
+                    block[fcb::MODULE] = 0;
+                    let record = position(&block);
+                    seek(&mut block, size, record);
+                    0
+                }
+                None => FAILED,
+            }
+            16 => if self.storage.size(&name).is_some() { 0 } else { FAILED },
+            17 => {
+                self.found = if block[fcb::DRIVE] == b'?' { self.matching(b"???????????") } else { self.matching(&block[fcb::NAME]) };
+                self.search()
+            }
+            18 => self.search(),
+            19 => {
+                let names = self.matching(&block[fcb::NAME]);
+                let deleted = names.iter().filter(|name| self.storage.delete(name)).count();
+                if deleted == 0 { FAILED } else { 0 }
+            }
+            20 | 33 => {
+                if function == 33 {
+                    let Some(record) = random(&block) else { return 6 };
+                    let size = self.storage.size(&name).unwrap_or(0);
+                    seek(&mut block, size, record);
+                }
+                let record = position(&block);
+                let mut data = [END_OF_FILE; RECORD];
+                let result = match self.storage.read(&name, record * RECORD, &mut data) {
+                    Some(0) | None => 1,
+                    Some(_) => {
+                        self.store(self.dma, &data);
+                        if function == 20 {
+                            let size = self.storage.size(&name).unwrap_or(0);
+                            seek(&mut block, size, record + 1);
+                        }
+                        0
+                    }
+                };
+                self.store(address, &block);
+                return result;
+            }
+            21 | 34 => {
+                if function == 34 {
+                    let Some(record) = random(&block) else { return 6 };
+                    let size = self.storage.size(&name).unwrap_or(0);
+                    seek(&mut block, size, record);
+                }
+                let record = position(&block);
+                let data = self.bytes(self.dma, RECORD);
+                if !self.storage.write(&name, record * RECORD, &data) {
+                    return 2;
+                }
+                let size = self.storage.size(&name).unwrap_or(0);
+                seek(&mut block, size, record + (function == 21) as usize);
+                0
+            }
+            22 => if self.storage.create(&name) {
+                block[fcb::MODULE] = 0;
+                block[fcb::COUNT] = 0;
+                0
+            } else {
+                FAILED
+            }
+            23 => {
+                let to = fcb::name(&block[fcb::RENAME + 1..fcb::RENAME + 12]);
+                if self.storage.rename(&name, &to) { 0 } else { FAILED }
+            }
+            35 => {
+                let records = self.storage.size(&name).map_or(0, |size| size.div_ceil(RECORD));
+                block[fcb::RANDOM..fcb::SIZE].copy_from_slice(&(records as u32).to_le_bytes()[..3]);
+                0
+            }
+            36 => {
+                let record = position(&block);
+                block[fcb::RANDOM..fcb::SIZE].copy_from_slice(&(record as u32).to_le_bytes()[..3]);
+                0
+            }
+            _ => 0,
+        };
+        self.store(address, &block);
+        result
+    }
+
+    /// Reports the next file found by a search, or `FAILED` once there are no more.
+    fn search(&mut self) -> raw::u8 {
+        match self.found.pop_front() {
+            Some(name) => { self.entry(&name); 0 }
+            None => FAILED,
+        }
+    }
+}
+
+/// The record that sequential access in an FCB will use next.
+fn position(block: &[raw::u8]) -> usize {
+    let extent = (block[fcb::MODULE] as usize & 0x3F) * 32 + (block[fcb::EXTENT] as usize & 0x1F);
+    extent * fcb::EXTENT_RECORDS + block[fcb::CURRENT] as usize
+}
+
+/// The record named by the random record field of an FCB, or `None` if it is out of range.
+fn random(block: &[raw::u8]) -> Option<usize> {
+    match block[fcb::RANDOM..fcb::SIZE] {
+        [low, high, 0] => Some(raw::u16::from_le_bytes([low, high]) as usize),
+        _ => None,
+    }
+}
+
+/// Sets an FCB's extent, module and current record so that sequential access continues at
+/// `record`, and its record count to the records of the file in that extent.
+fn seek(block: &mut [raw::u8], size: usize, record: usize) {
+    let extent = record / fcb::EXTENT_RECORDS;
+    block[fcb::EXTENT] = (extent % 32) as raw::u8;
+    block[fcb::MODULE] = (extent / 32) as raw::u8;
+    block[fcb::CURRENT] = (record % fcb::EXTENT_RECORDS) as raw::u8;
+    let before = extent * fcb::EXTENT_RECORDS;
+    block[fcb::COUNT] = size.div_ceil(RECORD).saturating_sub(before).min(fcb::EXTENT_RECORDS) as raw::u8;
+}
@@ -0,0 +1,306 @@
+//! A board that boots a real CP/M 2.2 system from disk images, by answering calls to the BIOS
+//! jump table from the host.
+//!
+//! The CCP and BDOS are read from the system tracks of the disk in drive A: on every cold and
+//! warm boot, as CP/M's own boot loader would, so they run unchanged and reach the disks only
+//! through the BIOS. The BIOS itself is a jump table whose entries pass their number out of
+//! `TRAP_PORT` (like the routines of the BDOS-level `Board`), along with the tables the BDOS
+//! needs for each drive: a disk parameter header, the disk parameter block for IBM 3740 disks
+//! and the sector translation table.
+
+use crate::prelude::{*, vec::Vec, collections::VecDeque};
+use crate::assembler::Assembler;
+use super::{disk::{self, Disk}, Status, TRAP_PORT};
+
+/// Where the CCP of a 64K system goes; its BDOS and BIOS follow it.
+pub const CCP: raw::u16 = 0xE400;
+/// The size of the CCP and BDOS together.
+pub const SYSTEM_SIZE: usize = 0x1600;
+/// The offset of the BDOS entry point from the start of the CCP.
+const BDOS_ENTRY: raw::u16 = 0x0806;
+/// The number of drives, A: through D:.
+pub const DRIVES: usize = 4;
+/// The size of a disk parameter header.
+const HEADER: raw::u16 = 16;
+
+const SYSTEM: &str = "
+CODE    EQU     CELLS
+ARGBC   EQU     CELLS+1
+ARGDE   EQU     CELLS+3
+RESULT  EQU     CELLS+5
+
+        ORG     0
+        JMP     BIOS            ; cold boot
+
+        ORG     BIOS
+        JMP     BOOT
+        JMP     WBOOT
+        JMP     E2
+        JMP     E3
+        JMP     E4
+        JMP     E5
+        JMP     E6
+        JMP     E7
+        JMP     E8
+        JMP     E9
+        JMP     E10
+        JMP     E11
+        JMP     E12
+        JMP     E13
+        JMP     E14
+        JMP     E15
+        JMP     E16
+
+ENTRY   MACRO   N
+E&N:    MVI     A,N
+        JMP     ENTER
+        ENDM
+
+        ENTRY   2
+        ENTRY   3
+        ENTRY   4
+        ENTRY   5
+        ENTRY   6
+        ENTRY   7
+        ENTRY   8
+        ENTRY   9
+        ENTRY   10
+        ENTRY   11
+        ENTRY   12
+        ENTRY   13
+        ENTRY   14
+        ENTRY   15
+        ENTRY   16
+
+BOOT:   XRA     A
+        JMP     LOAD
+WBOOT:  MVI     A,1
+LOAD:   LXI     SP,80H
+        CALL    ENTER           ; nonzero if there is no disk to load from
+        ORA     A
+        JNZ     STOP
+        LDA     4
+        MOV     C,A
+        JMP     CCP
+STOP:   HLT
+        JMP     STOP
+
+ENTER:  STA     CODE
+        MOV     H,B
+        MOV     L,C
+        SHLD    ARGBC
+        XCHG
+        SHLD    ARGDE
+        XCHG
+RETRY:  LDA     CODE
+        OUT     TRAP
+        IN      TRAP            ; nonzero while the board waits for console input
+        ORA     A
+        JNZ     RETRY
+        LHLD    RESULT
+        MOV     A,L
+        RET
+
+HEADERS:
+HEADER  MACRO   N
+        DW      XLT, 0, 0, 0, DIRBUF, DPB, CSV&N, ALV&N
+        ENDM
+        HEADER  0
+        HEADER  1
+        HEADER  2
+        HEADER  3
+
+DPB:    DS      15
+XLT:    DS      26
+DIRBUF: DS      128
+
+SCRATCH MACRO   N
+ALV&N:  DS      31
+CSV&N:  DS      16
+        ENDM
+        SCRATCH 0
+        SCRATCH 1
+        SCRATCH 2
+        SCRATCH 3
+
+CELLS:  DS      7
+";
+
+/// A Harness that emulates a 64K CP/M 2.2 machine with up to four 8" drives, a console and a
+/// printer, for running a CCP and BDOS from a boot disk.
+///
+/// Its `status` is `Ready` until the first boot and `Running` or `Waiting` (for console input)
+/// afterwards. It becomes `Finished` if a boot finds no disk in drive A:, and the processor halts.
+pub struct Bios {
+    ram: Box<[raw::u8]>,
+    drives: [Option<Disk>; DRIVES],
+    keys: VecDeque<raw::u8>,
+    console: Vec<raw::u8>,
+    printer: Vec<raw::u8>,
+    ccp: raw::u16,
+    headers: raw::u16,
+    cells: raw::u16,
+    drive: usize,
+    track: raw::u16,
+    sector: raw::u16,
+    dma: raw::u16,
+    reply: raw::u8,
+    status: Status,
+}
+
+impl Default for Bios {
+    fn default() -> Self { Self::new() }
+}
+
+impl Bios {
+    /// Creates a board for a system built for 64K, with its CCP at `CCP`, and no disks.
+    pub fn new() -> Self { Self::with_ccp(CCP) }
+
+    /// Creates a board for a system built with its CCP at `ccp`, and no disks. The BIOS starts
+    /// `SYSTEM_SIZE` bytes after the CCP and needs about 0x240 bytes, so `ccp` can be at most `CCP`.
+    pub fn with_ccp(ccp: raw::u16) -> Self {
+        let system = Assembler::new()
+            .define("CCP", ccp).define("BIOS", ccp + SYSTEM_SIZE as raw::u16)
+            .define("TRAP", TRAP_PORT as raw::u16)
+            .assemble(SYSTEM)
+            .expect("the BIOS routines should fit above the CCP and BDOS");
+        let mut ram = vec![0; 0x10000].into_boxed_slice();
+        ram[..system.image.len()].copy_from_slice(&system.image);
+        let parameters = system.symbols["DPB"] as usize;
+        ram[parameters..parameters + disk::PARAMETERS.len()].copy_from_slice(&disk::PARAMETERS);
+        let translation = system.symbols["XLT"] as usize;
+        ram[translation..translation + disk::SKEW.len()].copy_from_slice(&disk::SKEW);
+        Self {
+            ram, drives: Default::default(), keys: VecDeque::new(), console: Vec::new(), printer: Vec::new(),
+            ccp, headers: system.symbols["HEADERS"], cells: system.symbols["CELLS"],
+            drive: 0, track: 0, sector: 1, dma: 0x0080, reply: 0, status: Status::Ready,
+        }
+    }
+
+    /// Puts a disk in a drive (0 for A: and so on), returning the disk that was there.
+    pub fn insert(&mut self, drive: usize, disk: Disk) -> Option<Disk> { self.drives[drive].replace(disk) }
+
+    /// Takes the disk out of a drive.
+    pub fn eject(&mut self, drive: usize) -> Option<Disk> { self.drives[drive].take() }
+
+    /// The disk in a drive, if there is one.
+    pub fn disk(&self, drive: usize) -> Option<&Disk> { self.drives.get(drive)?.as_ref() }
+
+    /// Queues text for the console, as though it were typed. Line ends become carriage returns.
+    pub fn send(&mut self, text: impl AsRef<[raw::u8]>) {
+        super::queue(&mut self.keys, text.as_ref());
+        if self.status == Status::Waiting { self.status = Status::Running; }
+    }
+
+    /// Takes everything written to the console since the last call.
+    pub fn receive(&mut self) -> Vec<raw::u8> { core::mem::take(&mut self.console) }
+
+    /// Takes everything written to the printer since the last call.
+    pub fn printed(&mut self) -> Vec<raw::u8> { core::mem::take(&mut self.printer) }
+
+    pub fn status(&self) -> Status { self.status }
+
+    fn word(&self, address: raw::u16) -> raw::u16 {
+        raw::u16::from_le_bytes([self.ram[address as usize], self.ram[address.wrapping_add(1) as usize]])
+    }
+
+    fn jump(&mut self, at: usize, to: raw::u16) {
+        self.ram[at] = 0xC3;
+        self.ram[at + 1..at + 3].copy_from_slice(&to.to_le_bytes());
+    }
+
+    /// Loads the CCP and BDOS from drive A: and sets up page zero, for a cold boot (which also
+    /// selects drive A:) or a warm boot; returns whether there was a disk to load from.
+    fn boot(&mut self, cold: bool) -> bool {
+        let Some(disk) = &self.drives[0] else { return false };
+        let ccp = self.ccp as usize;
+        self.ram[ccp..ccp + SYSTEM_SIZE].copy_from_slice(&disk.system()[..SYSTEM_SIZE]);
+        self.jump(0x0000, self.ccp + SYSTEM_SIZE as raw::u16 + 3);
+        self.jump(0x0005, self.ccp + BDOS_ENTRY);
+        if cold {
+            self.ram[0x0003] = 0;
+            self.ram[0x0004] = 0;
+        }
+        self.dma = 0x0080;
+        true
+    }
+
+    /// The disk sector chosen by the last `SELDSK`, `SETTRK` and `SETSEC` calls.
+    fn chosen(&mut self) -> Option<&mut [raw::u8]> {
+        self.drives[self.drive].as_mut()?.sector_mut(self.track as usize, self.sector as usize)
+    }
+
+    /// Answers a call to a BIOS entry, or returns `None` if it must wait for console input.
+    pub(super) fn bios(&mut self, entry: raw::u8, bc: raw::u16, de: raw::u16) -> Option<raw::u16> {
+        let c = bc as raw::u8;
+        Some(match entry {
+            0 | 1 => if self.boot(entry == 0) { 0 } else { 1 },
+            2 => if self.keys.is_empty() { 0x00 } else { 0xFF },
+            3 => self.keys.pop_front()? as raw::u16,
+            4 => { self.console.push(c); 0 }
+            5 => { self.printer.push(c); 0 }
+            7 => 0x1A,
+            8 => { self.track = 0; 0 }
+            9 => match self.drives.get(c as usize) {
+                Some(Some(_)) => { self.drive = c as usize; self.headers + c as raw::u16 * HEADER }
+                _ => 0,
+            }
+            10 => { self.track = bc; 0 }
+            11 => { self.sector = bc; 0 }
+            12 => { self.dma = bc; 0 }
+            13 => {
+                let Some(sector) = self.chosen() else { return Some(1) };
+                let data: [raw::u8; disk::SECTOR_SIZE] = (*sector).try_into().unwrap();
+                let dma = self.dma as usize;
+                match self.ram.get_mut(dma..dma + disk::SECTOR_SIZE) {
+                    Some(buffer) => { buffer.copy_from_slice(&data); 0 }
+                    None => 1,
+                }
+            }
+            14 => {
+                let dma = self.dma as usize;
+                let Some(data) = self.ram.get(dma..dma + disk::SECTOR_SIZE) else { return Some(1) };
+                let data: [raw::u8; disk::SECTOR_SIZE] = data.try_into().unwrap();
+                match self.chosen() {
+                    Some(sector) => { sector.copy_from_slice(&data); 0 }
+                    None => 1,
+                }
+            }
+            15 => 0xFF,
+            16 if de == 0 => bc + 1,
+            16 => self.ram[de.wrapping_add(bc) as usize] as raw::u16,
+            _ => 0,
+        })
+    }
+
+    /// Carries out a call passed out of the trap port.
+    fn trap(&mut self, entry: raw::u8) {
+        let (bc, de) = (self.word(self.cells + 1), self.word(self.cells + 3));
+        match self.bios(entry, bc, de) {
+            Some(result) => {
+                let at = self.cells as usize + 5;
+                self.ram[at..at + 2].copy_from_slice(&result.to_le_bytes());
+                self.reply = 0;
+                self.status = match entry {
+                    0 | 1 if result != 0 => Status::Finished,
+                    _ => Status::Running,
+                };
+            }
+            None => {
+                self.reply = 1;
+                self.status = Status::Waiting;
+            }
+        }
+    }
+}
+
+impl Harness for Bios {
+    fn read(&self, from: u16) -> u8 { Wrapping(self.ram[from.0 as usize]) }
+    fn write(&mut self, to: u16, value: u8) { self.ram[to.0 as usize] = value.0; }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        Wrapping(if port == TRAP_PORT { self.reply } else { 0xFF })
+    }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        if port == TRAP_PORT { self.trap(value.0); }
+    }
+}
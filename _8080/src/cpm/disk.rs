@@ -0,0 +1,87 @@
+//! Single-sided, single-density 8" disks in the IBM 3740 format that CP/M 2.2 was distributed
+//! on, held as the usual `.dsk` image: every sector of every track in order, with no headers.
+
+use crate::prelude::vec::Vec;
+use crate::prelude::*;
+
+/// The number of tracks on a disk.
+pub const TRACKS: usize = 77;
+/// The number of sectors on each track, numbered from 1.
+pub const SECTORS: usize = 26;
+/// The size of a sector, which is also the size of a CP/M record.
+pub const SECTOR_SIZE: usize = 128;
+/// The size of a whole disk image.
+pub const SIZE: usize = TRACKS * SECTORS * SECTOR_SIZE;
+/// The tracks reserved for the boot loader and the CCP and BDOS.
+pub const SYSTEM_TRACKS: usize = 2;
+
+/// The standard sector translation for data tracks: logical sector `n` (from 0) is found in
+/// physical sector `SKEW[n]`.
+pub const SKEW: [raw::u8; SECTORS] = [
+    1, 7, 13, 19, 25, 5, 11, 17, 23, 3, 9, 15, 21, 2, 8, 14, 20, 26, 6, 12, 18, 24, 4, 10, 16, 22,
+];
+
+/// The standard disk parameter block for this format, as the BDOS expects to find it in memory:
+/// 26 sectors per track, 1K blocks, 243 blocks, 64 directory entries (in the first two blocks),
+/// 16 directory records to check for a changed disk and two system tracks.
+pub const PARAMETERS: [raw::u8; 15] = [26, 0, 3, 7, 0, 242, 0, 63, 0, 0xC0, 0, 16, 0, 2, 0];
+
+/// The byte that fills the sectors of a freshly formatted disk, which also marks directory
+/// entries as unused.
+const EMPTY: raw::u8 = 0xE5;
+
+/// A disk, which is a `.dsk` image that can be read and written a sector at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disk {
+    image: Box<[raw::u8]>,
+}
+
+impl Default for Disk {
+    fn default() -> Self { Self::new() }
+}
+
+impl Disk {
+    /// Creates a freshly formatted disk, with an empty directory and nothing on the system tracks.
+    pub fn new() -> Self { Self { image: vec![EMPTY; SIZE].into_boxed_slice() } }
+
+    /// Creates a disk from a `.dsk` image, or returns `None` if the image isn't the size of a disk.
+    pub fn from_image(image: &[raw::u8]) -> Option<Self> {
+        (image.len() == SIZE).then(|| Self { image: image.into() })
+    }
+
+    /// The disk's image, to save as a `.dsk` file.
+    pub fn image(&self) -> &[raw::u8] { &self.image }
+
+    fn offset(track: usize, sector: usize) -> Option<usize> {
+        (track < TRACKS && (1..=SECTORS).contains(&sector)).then(|| (track * SECTORS + sector - 1) * SECTOR_SIZE)
+    }
+
+    /// The contents of a physical sector (numbered from 1), or `None` if there is no such sector.
+    pub fn sector(&self, track: usize, sector: usize) -> Option<&[raw::u8]> {
+        Self::offset(track, sector).map(|at| &self.image[at..at + SECTOR_SIZE])
+    }
+
+    /// The contents of a physical sector (numbered from 1) to write to, or `None` if there is no
+    /// such sector.
+    pub fn sector_mut(&mut self, track: usize, sector: usize) -> Option<&mut [raw::u8]> {
+        Self::offset(track, sector).map(|at| &mut self.image[at..at + SECTOR_SIZE])
+    }
+
+    /// The system tracks after the boot sector, which hold the CCP and BDOS.
+    pub fn system(&self) -> &[raw::u8] {
+        &self.image[SECTOR_SIZE..SYSTEM_TRACKS * SECTORS * SECTOR_SIZE]
+    }
+
+    /// Writes a CCP and BDOS image (usually 0x1600 bytes, starting with the CCP) to the system
+    /// tracks after the boot sector, as `SYSGEN` would. Returns `false` without writing anything
+    /// if it doesn't fit.
+    pub fn set_system(&mut self, system: &[raw::u8]) -> bool {
+        let end = SECTOR_SIZE + system.len();
+        if end > SYSTEM_TRACKS * SECTORS * SECTOR_SIZE { return false; }
+        self.image[SECTOR_SIZE..end].copy_from_slice(system);
+        true
+    }
+
+    /// Consumes the disk, returning its image.
+    pub fn into_image(self) -> Vec<raw::u8> { self.image.into_vec() }
+}
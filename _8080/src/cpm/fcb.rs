@@ -0,0 +1,76 @@
+//! The layout of a CP/M file control block, and conversions between its padded names and the
+//! `NAME.EXT` names of `Storage`.
+
+use crate::prelude::*;
+
+/// The size of an FCB used for random access, including the random record number.
+pub(super) const SIZE: usize = 36;
+/// The drive code: 0 for the current drive, or 1 for A: and so on.
+pub(super) const DRIVE: usize = 0;
+/// The eight characters of the name and three of the type, padded with spaces.
+pub(super) const NAME: core::ops::Range<usize> = 1..12;
+/// The extent number, counting 16K pieces of the file (modulo 32).
+pub(super) const EXTENT: usize = 12;
+/// The module number, counting groups of 32 extents.
+pub(super) const MODULE: usize = 14;
+/// The number of records used in the current extent.
+pub(super) const COUNT: usize = 15;
+/// Where the second name of a rename request goes.
+pub(super) const RENAME: usize = 16;
+/// The next record to read or write within the current extent.
+pub(super) const CURRENT: usize = 32;
+/// The record number used by random access, in three bytes, least significant first.
+pub(super) const RANDOM: usize = 33;
+
+/// The number of 128-byte records in an extent.
+pub(super) const EXTENT_RECORDS: usize = 128;
+
+/// Whether a host file name fits in an FCB.
+pub(super) fn is_valid(name: &str) -> bool {
+    let (base, kind) = name.split_once('.').unwrap_or((name, ""));
+    let fits = |part: &str, size| part.len() <= size && part.bytes().all(|c| c.is_ascii_graphic() && !b".:*?<>,;=[]".contains(&c));
+    !base.is_empty() && fits(base, 8) && fits(kind, 3)
+}
+
+/// Converts a `NAME.EXT` name to the padded form in an FCB.
+pub(super) fn padded(name: &str) -> [raw::u8; 11] {
+    let (base, kind) = name.split_once('.').unwrap_or((name, ""));
+    let mut padded = [b' '; 11];
+    for (slot, c) in padded[..8].iter_mut().zip(base.bytes()) { *slot = c.to_ascii_uppercase(); }
+    for (slot, c) in padded[8..].iter_mut().zip(kind.bytes()) { *slot = c.to_ascii_uppercase(); }
+    padded
+}
+
+/// Converts the padded name in an FCB to `NAME.EXT` form, ignoring attribute bits.
+pub(super) fn name(padded: &[raw::u8]) -> String {
+    let text = |part: &[raw::u8]| part.iter().map(|c| (c & 0x7F) as char).collect::<String>().trim_end().to_ascii_uppercase();
+    let (base, kind) = (text(&padded[..8]), text(&padded[8..11]));
+    if kind.is_empty() { base } else { [base, kind].join(".") }
+}
+
+/// Whether a padded name matches a pattern in which `?` stands for any character.
+pub(super) fn matches(pattern: &[raw::u8], padded: &[raw::u8; 11]) -> bool {
+    pattern.iter().zip(padded).all(|(want, have)| *want & 0x7F == b'?' || (want & 0x7F).eq_ignore_ascii_case(have))
+}
+
+/// Fills the name fields of an FCB from a command-line word such as `B:NAME.*`, as the CCP
+/// does for the default FCBs. A `*` fills the rest of its field with `?`.
+pub(super) fn parse(word: &str) -> [raw::u8; 12] {
+    let mut fcb = [b' '; 12];
+    let word = match word.as_bytes() {
+        [drive @ b'A'..=b'P', b':', ..] => { fcb[DRIVE] = drive - b'A' + 1; &word[2..] }
+        _ => { fcb[DRIVE] = 0; word }
+    };
+    let (base, kind) = word.split_once('.').unwrap_or((word, ""));
+    let fill = |field: &mut [raw::u8], text: &str| {
+        let mut wild = false;
+        for (at, slot) in field.iter_mut().enumerate() {
+            let c = text.as_bytes().get(at).copied();
+            wild |= c == Some(b'*');
+            *slot = if wild { b'?' } else { c.map_or(b' ', |c| c.to_ascii_uppercase()) };
+        }
+    };
+    fill(&mut fcb[1..9], base);
+    fill(&mut fcb[9..12], kind);
+    fcb
+}
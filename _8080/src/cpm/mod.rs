@@ -0,0 +1,310 @@
+//! A board that runs CP/M 2.2 programs without CP/M itself, by answering BDOS calls from
+//! the host.
+//!
+//! Memory holds the usual CP/M page zero (with the warm boot jump at 0x0000 and the BDOS jump at
+//! 0x0005), a BDOS entry point at `BDOS` and a BIOS jump table at `BIOS`. Both are short 8080
+//! routines that store the call's registers in memory and then pass the function number out of
+//! `TRAP_PORT`; the board carries out the call and leaves the result for the routine to load
+//! into `A` and `HL`. Files come from a `Storage`, which can be a map in memory or (with the
+//! `"std"` feature) a directory on the host, and the console is a pair of byte queues.
+//!
+//! A fresh `Machine` starts at 0x0000, where the warm boot routine launches the program
+//! loaded with `load_program` (with the stack set up so that a `RET` warm boots again). When the
+//! program warm boots, the board's `status` becomes `Finished` and the processor halts.
+//!
+//! Programs that go around the BDOS need the lower-level `Bios` board instead, which boots a
+//! real CCP and BDOS from an 8" `Disk` image.
+
+use crate::prelude::{*, vec::Vec, collections::VecDeque};
+use crate::{assembler::Assembler, image::{self, Loaded}};
+
+mod bdos;
+pub mod bios;
+pub mod disk;
+mod fcb;
+mod storage;
+
+pub use bios::Bios;
+pub use disk::Disk;
+pub use storage::Storage;
+#[cfg(feature="std")]
+pub use storage::Directory;
+
+/// The BDOS entry point, which is also the top of the memory available to programs.
+pub const BDOS: raw::u16 = 0xFC06;
+/// The start of the BIOS jump table.
+pub const BIOS: raw::u16 = 0xFE00;
+/// The port that the BDOS and BIOS routines use to pass calls to the board.
+pub const TRAP_PORT: raw::u8 = 0xFF;
+/// Where programs are loaded and started.
+pub const TPA: raw::u16 = 0x0100;
+
+/// The memory cells shared by the trap routines and the board: the function code, the `BC` and
+/// `DE` arguments and the result.
+const CELLS: raw::u16 = 0xFDF0;
+const ARGUMENT_BC: raw::u16 = CELLS + 1;
+const ARGUMENT_DE: raw::u16 = CELLS + 3;
+const RESULT: raw::u16 = CELLS + 5;
+
+/// The code passed out of the trap port by the boot routine; BIOS calls use this plus the
+/// entry's position in the jump table.
+const BIOS_CODE: raw::u8 = 0x80;
+
+const SYSTEM: &str = "
+CODE    EQU     CELLS
+ARGBC   EQU     CELLS+1
+ARGDE   EQU     CELLS+3
+RESULT  EQU     CELLS+5
+
+        ORG     0
+        JMP     BIOS+3          ; warm boot
+        DB      0, 0            ; IOBYTE and current drive
+        JMP     BDOS
+
+        ORG     BDOS
+        MOV     A,C
+        ORA     A               ; function 0 is a warm boot
+        JZ      0
+        CPI     CALLS           ; codes from CALLS up belong to the BIOS
+        JNC     NONE
+ENTER:  STA     CODE
+        MOV     H,B
+        MOV     L,C
+        SHLD    ARGBC
+        XCHG
+        SHLD    ARGDE
+        XCHG
+RETRY:  LDA     CODE
+        OUT     TRAP
+        IN      TRAP            ; nonzero while the board waits for console input
+        ORA     A
+        JNZ     RETRY
+        LHLD    RESULT
+        MOV     A,L
+        MOV     B,H
+        RET
+NONE:   XRA     A               ; unknown functions return 0
+        MOV     B,A
+        MOV     H,A
+        MOV     L,A
+        RET
+
+        ORG     BIOS
+        JMP     BOOT
+        JMP     BOOT
+        JMP     E2
+        JMP     E3
+        JMP     E4
+        JMP     E5
+        JMP     E6
+        JMP     E7
+        JMP     E8
+        JMP     E9
+        JMP     E10
+        JMP     E11
+        JMP     E12
+        JMP     E13
+        JMP     E14
+        JMP     E15
+        JMP     E16
+
+ENTRY   MACRO   N
+E&N:    MVI     A,CALLS+N
+        JMP     ENTER
+        ENDM
+
+        ENTRY   2
+        ENTRY   3
+        ENTRY   4
+        ENTRY   5
+        ENTRY   6
+        ENTRY   7
+        ENTRY   8
+        ENTRY   9
+        ENTRY   10
+        ENTRY   11
+        ENTRY   12
+        ENTRY   13
+        ENTRY   14
+        ENTRY   15
+        ENTRY   16
+
+BOOT:   MVI     A,CALLS
+        OUT     TRAP
+        IN      TRAP            ; nonzero when a program is ready to start
+        ORA     A
+        JZ      STOP
+        LXI     SP,BDOS
+        LXI     H,0
+        PUSH    H
+        JMP     TPA
+STOP:   HLT
+        JMP     STOP
+";
+
+/// What the board is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// No program has been loaded yet.
+    Empty,
+    /// A program is loaded and will start at the next warm boot.
+    Ready,
+    /// The program is running.
+    Running,
+    /// The program is waiting for console input; it resumes once input is sent.
+    Waiting,
+    /// The program has ended with a warm boot.
+    Finished,
+}
+
+/// A Harness that emulates a 64K CP/M 2.2 system around a single program.
+pub struct Board<S: Storage> {
+    ram: Box<[raw::u8]>,
+    storage: S,
+    keys: VecDeque<raw::u8>,
+    console: Vec<raw::u8>,
+    dma: raw::u16,
+    drive: raw::u8,
+    user: raw::u8,
+    reply: raw::u8,
+    status: Status,
+    found: VecDeque<String>,
+}
+
+impl<S: Storage> Board<S> {
+    /// Creates a board with its system routines in place, using `storage` as drive A:.
+    pub fn new(storage: S) -> Self {
+        let system = Assembler::new()
+            .define("BDOS", BDOS).define("BIOS", BIOS).define("TRAP", TRAP_PORT as raw::u16)
+            .define("CELLS", CELLS).define("CALLS", BIOS_CODE as raw::u16).define("TPA", TPA)
+            .assemble(SYSTEM)
+            .expect("the CP/M system routines should assemble");
+        let mut ram = vec![0; 0x10000].into_boxed_slice();
+        ram[..system.image.len()].copy_from_slice(&system.image);
+        Self {
+            ram, storage, keys: VecDeque::new(), console: Vec::new(), dma: 0x0080, drive: 0, user: 0,
+            reply: 0, status: Status::Empty, found: VecDeque::new(),
+        }
+    }
+
+    /// Loads a `.COM` program at 0x0100 to run at the next warm boot, and sets up the command
+    /// tail at 0x0080 and the default FCBs at 0x005C and 0x006C from `arguments` as the CCP would.
+    pub fn load_program(&mut self, program: &[raw::u8], arguments: &str) -> Result<Loaded, image::Error> {
+        if TPA as usize + program.len() > (BDOS & 0xFF00) as usize {
+            return Err(image::Error { line: 0, kind: image::ErrorKind::OutOfRange });
+        }
+        let loaded = image::binary::load(program, TPA, self)?;
+        let arguments = arguments.trim().to_ascii_uppercase();
+        let mut words = arguments.split_ascii_whitespace();
+        for address in [0x005C, 0x006C] {
+            let mut block = [0; 16];
+            block[..12].copy_from_slice(&fcb::parse(words.next().unwrap_or("")));
+            self.store(address, &block);
+        }
+        self.ram[0x007C] = 0;
+        let tail = if arguments.is_empty() { Vec::new() } else { [b" ", arguments.as_bytes()].concat() };
+        let tail = &tail[..tail.len().min(127)];
+        self.ram[0x0080] = tail.len() as raw::u8;
+        self.store(0x0081, tail);
+        self.ram[0x0081 + tail.len()] = 0;
+        self.dma = 0x0080;
+        self.status = Status::Ready;
+        Ok(loaded)
+    }
+
+    /// Queues text for the console, as though it were typed. Line ends become carriage returns.
+    pub fn send(&mut self, text: impl AsRef<[raw::u8]>) {
+        queue(&mut self.keys, text.as_ref());
+        if self.status == Status::Waiting { self.status = Status::Running; }
+    }
+
+    /// Takes everything written to the console since the last call.
+    pub fn receive(&mut self) -> Vec<raw::u8> { core::mem::take(&mut self.console) }
+
+    pub fn status(&self) -> Status { self.status }
+
+    /// The drive's storage.
+    pub fn storage(&mut self) -> &mut S { &mut self.storage }
+
+    fn word(&self, address: raw::u16) -> raw::u16 {
+        raw::u16::from_le_bytes([self.ram[address as usize], self.ram[address.wrapping_add(1) as usize]])
+    }
+
+    fn bytes(&self, address: raw::u16, count: usize) -> Vec<raw::u8> {
+        (0..count).map(|offset| self.ram[address.wrapping_add(offset as raw::u16) as usize]).collect()
+    }
+
+    fn store(&mut self, address: raw::u16, data: &[raw::u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.ram[address.wrapping_add(offset as raw::u16) as usize] = *byte;
+        }
+    }
+
+    /// Carries out a call passed out of the trap port.
+    fn trap(&mut self, code: raw::u8) {
+        if code == BIOS_CODE {
+            self.reply = (self.status == Status::Ready) as raw::u8;
+            self.status = if self.status == Status::Ready { Status::Running } else { Status::Finished };
+            return;
+        }
+        let (bc, de) = (self.word(ARGUMENT_BC), self.word(ARGUMENT_DE));
+        let reply = match code.checked_sub(BIOS_CODE) {
+            Some(entry) => self.bios(entry, bc),
+            None => self.bdos(code, de),
+        };
+        match reply {
+            Some(result) => {
+                self.store(RESULT, &result.to_le_bytes());
+                self.reply = 0;
+                if self.status == Status::Waiting { self.status = Status::Running; }
+            }
+            None => {
+                self.reply = 1;
+                self.status = Status::Waiting;
+            }
+        }
+    }
+
+    /// Answers a call to the BIOS jump table. Only the character devices do anything; there
+    /// are no disks to select, so disk calls fail.
+    fn bios(&mut self, entry: raw::u8, bc: raw::u16) -> Option<raw::u16> {
+        Some(match entry {
+            2 => if self.keys.is_empty() { 0x00 } else { 0xFF },
+            3 => self.keys.pop_front()? as raw::u16,
+            4 => { self.console.push(bc as raw::u8); 0 }
+            7 => 0x1A,
+            9 => 0x0000,
+            13 | 14 => 1,
+            15 => 0xFF,
+            16 => bc,
+            _ => 0,
+        })
+    }
+}
+
+/// Adds typed text to a keyboard queue, turning line ends into carriage returns.
+fn queue(keys: &mut VecDeque<raw::u8>, text: &[raw::u8]) {
+    let mut last = 0;
+    for &c in text {
+        match c {
+            b'\n' if last == b'\r' => (),
+            b'\n' => keys.push_back(b'\r'),
+            c => keys.push_back(c),
+        }
+        last = c;
+    }
+}
+
+impl<S: Storage> Harness for Board<S> {
+    fn read(&self, from: u16) -> u8 { Wrapping(self.ram[from.0 as usize]) }
+    fn write(&mut self, to: u16, value: u8) { self.ram[to.0 as usize] = value.0; }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        Wrapping(if port == TRAP_PORT { self.reply } else { 0xFF })
+    }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        if port == TRAP_PORT { self.trap(value.0); }
+    }
+}
+
+#[cfg(test)]
+mod tests;
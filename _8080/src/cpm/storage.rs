@@ -0,0 +1,139 @@
+use crate::prelude::{*, vec::Vec, collections::BTreeMap};
+#[cfg(feature="std")]
+extern crate std;
+
+/// The files behind the CP/M drive. Files are named in CP/M's `NAME.EXT` form, in upper case
+/// (or just `NAME` when there is no extension).
+pub trait Storage {
+    /// Lists the names of every file on the drive.
+    fn files(&self) -> Vec<String>;
+
+    /// Reads from a file, starting `offset` bytes in, into `buffer`; returns how many bytes were
+    /// read (fewer than asked for at the end of the file), or `None` if the file doesn't exist.
+    fn read(&mut self, name: &str, offset: usize, buffer: &mut [raw::u8]) -> Option<usize>;
+
+    /// Writes to an existing file, starting `offset` bytes in and extending the file (with
+    /// zeroes if `offset` is past its end) as needed; returns whether it succeeded.
+    fn write(&mut self, name: &str, offset: usize, data: &[raw::u8]) -> bool;
+
+    /// The length of a file in bytes, or `None` if the file doesn't exist.
+    fn size(&mut self, name: &str) -> Option<usize>;
+
+    /// Creates an empty file, replacing any file of the same name; returns whether it succeeded.
+    fn create(&mut self, name: &str) -> bool;
+
+    /// Deletes a file; returns whether it existed.
+    fn delete(&mut self, name: &str) -> bool;
+
+    /// Renames a file; returns whether it existed and could be renamed.
+    fn rename(&mut self, from: &str, to: &str) -> bool;
+}
+
+/// A drive held in memory, as a map from file names to their contents.
+impl Storage for BTreeMap<String, Vec<raw::u8>> {
+    fn files(&self) -> Vec<String> { self.keys().cloned().collect() }
+
+    fn read(&mut self, name: &str, offset: usize, buffer: &mut [raw::u8]) -> Option<usize> {
+        let file = self.get(name)?;
+        let data = file.get(offset..).unwrap_or_default();
+        let count = data.len().min(buffer.len());
+        buffer[..count].copy_from_slice(&data[..count]);
+        Some(count)
+    }
+
+    fn write(&mut self, name: &str, offset: usize, data: &[raw::u8]) -> bool {
+        let Some(file) = self.get_mut(name) else { return false };
+        if file.len() < offset + data.len() {
+            file.resize(offset + data.len(), 0);
+        }
+        file[offset..offset + data.len()].copy_from_slice(data);
+        true
+    }
+
+    fn size(&mut self, name: &str) -> Option<usize> { self.get(name).map(Vec::len) }
+
+    fn create(&mut self, name: &str) -> bool {
+        self.insert(name.into(), Vec::new());
+        true
+    }
+
+    fn delete(&mut self, name: &str) -> bool { self.remove(name).is_some() }
+
+    fn rename(&mut self, from: &str, to: &str) -> bool {
+        let Some(file) = self.remove(from) else { return false };
+        self.insert(to.into(), file);
+        true
+    }
+}
+
+/// A drive backed by a directory on the host. Host files are matched to CP/M names without
+/// regard to case, and files whose names don't fit CP/M's 8.3 form are left out.
+#[cfg(feature="std")]
+#[derive(Debug, Clone)]
+pub struct Directory {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature="std")]
+impl Directory {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self { Self { path: path.into() } }
+
+    /// Finds the host file for a CP/M name.
+    fn host(&self, name: &str) -> Option<std::path::PathBuf> {
+        std::fs::read_dir(&self.path).ok()?
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_str().is_some_and(|host| host.eq_ignore_ascii_case(name)))
+            .map(|entry| entry.path())
+    }
+}
+
+#[cfg(feature="std")]
+impl Storage for Directory {
+    fn files(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.path) else { return Vec::new() };
+        entries.filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_ascii_uppercase))
+            .filter(|name| super::fcb::is_valid(name))
+            .collect()
+    }
+
+    fn read(&mut self, name: &str, offset: usize, buffer: &mut [raw::u8]) -> Option<usize> {
+        use std::io::{Read, Seek, SeekFrom};
+        let mut file = std::fs::File::open(self.host(name)?).ok()?;
+        file.seek(SeekFrom::Start(offset as u64)).ok()?;
+        let mut count = 0;
+        while count < buffer.len() {
+            match file.read(&mut buffer[count..]) {
+                Ok(0) => break,
+                Ok(read) => count += read,
+                Err(_) => return None,
+            }
+        }
+        Some(count)
+    }
+
+    fn write(&mut self, name: &str, offset: usize, data: &[raw::u8]) -> bool {
+        use std::io::{Seek, SeekFrom, Write};
+        let Some(path) = self.host(name) else { return false };
+        let Ok(mut file) = std::fs::OpenOptions::new().write(true).open(path) else { return false };
+        file.seek(SeekFrom::Start(offset as u64)).is_ok() && file.write_all(data).is_ok()
+    }
+
+    fn size(&mut self, name: &str) -> Option<usize> {
+        std::fs::metadata(self.host(name)?).ok().map(|data| data.len() as usize)
+    }
+
+    fn create(&mut self, name: &str) -> bool {
+        let path = self.host(name).unwrap_or_else(|| self.path.join(name));
+        std::fs::File::create(path).is_ok()
+    }
+
+    fn delete(&mut self, name: &str) -> bool {
+        self.host(name).is_some_and(|path| std::fs::remove_file(path).is_ok())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> bool {
+        self.host(from).is_some_and(|path| std::fs::rename(path, self.path.join(to)).is_ok())
+    }
+}
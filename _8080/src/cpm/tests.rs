@@ -0,0 +1,325 @@
+use super::*;
+use crate::{Machine, assembler::{assemble, Assembler}, collections::BTreeMap};
+
+type Drive = BTreeMap<String, Vec<raw::u8>>;
+type System = Machine<Board<Drive>, Board<Drive>>;
+
+fn system(source: &str, arguments: &str) -> System {
+    let program = assemble(source).unwrap();
+    assert_eq!(program.origin, TPA);
+    let mut board = Board::new(Drive::new());
+    board.load_program(&program.image, arguments).unwrap();
+    Machine::new(board)
+}
+
+/// Runs until the program finishes or waits for input.
+fn run(system: &mut System) -> Status {
+    for _ in 0..100_000 {
+        let _ = system.execute();
+        if let status @ (Status::Finished | Status::Waiting) = system.status() {
+            return status;
+        }
+    }
+    panic!("program didn't finish");
+}
+
+#[test]
+fn hello() {
+    let mut system = system("
+        ORG     100H
+        MVI     C,9
+        LXI     D,TEXT
+        CALL    5
+        MVI     E,'!'
+        MVI     C,2
+        CALL    5
+        RET
+TEXT:   DB      'Hello, world$'
+    ", "");
+    assert_eq!(system.status(), Status::Ready);
+    assert_eq!(run(&mut system), Status::Finished);
+    assert_eq!(system.receive(), b"Hello, world!");
+    let _ = system.execute();
+    assert_eq!(system.status(), Status::Finished);
+}
+
+#[test]
+fn console() {
+    let source = "
+        ORG     100H
+        MVI     C,10
+        LXI     D,BUFFER
+        CALL    5
+        MVI     C,1
+        CALL    5
+        STA     KEY
+        MVI     C,11
+        CALL    5
+        STA     READY
+        MVI     C,12
+        CALL    5
+        SHLD    VERSION
+        MVI     C,0
+        CALL    5
+KEY:    DB      0
+READY:  DB      0FFH
+VERSION: DW     0
+BUFFER: DB      5
+        DS      6
+    ";
+    let symbols = assemble(source).unwrap().symbols;
+    let mut system = system(source, "");
+    assert_eq!(run(&mut system), Status::Waiting);
+    system.send("HI\x08ELLO THERE\r\n");
+    assert_eq!(run(&mut system), Status::Waiting);
+    system.send("x");
+    assert_eq!(run(&mut system), Status::Finished);
+    assert_eq!(system.receive(), b"HELLO\rx");
+    let peek = |name: &str| system.read(Wrapping(symbols[name])).0;
+    assert_eq!(peek("KEY"), b'x');
+    assert_eq!(peek("READY"), 0);
+    assert_eq!(peek("VERSION"), 0x22);
+    let buffer = symbols["BUFFER"];
+    let text = (1..7).map(|offset| system.read(Wrapping(buffer + offset)).0).collect::<Vec<_>>();
+    assert_eq!(text, b"\x05HELLO");
+}
+
+#[test]
+fn command_line() {
+    let mut board = Board::new(Drive::new());
+    board.load_program(&[0xC9], "b:file.* second.txt  extra").unwrap();
+    assert_eq!(board.bytes(0x005C, 12), b"\x02FILE    ???");
+    assert_eq!(board.bytes(0x006C, 12), b"\x00SECOND  TXT");
+    assert_eq!(board.bytes(0x0080, 29), b"\x1B B:FILE.* SECOND.TXT  EXTRA\0");
+    assert!(board.load_program(&[0; 0xFC00], "").is_err());
+}
+
+#[test]
+fn bios() {
+    let mut system = system("
+        ORG     100H
+        LHLD    1
+        LXI     D,9
+        DAD     D
+        MVI     C,'*'
+        LXI     D,BACK
+        PUSH    D
+        PCHL
+BACK:   RET
+    ", "");
+    assert_eq!(run(&mut system), Status::Finished);
+    assert_eq!(system.receive(), b"*");
+}
+
+#[test]
+fn unknown_functions() {
+    let mut system = system("
+        ORG     100H
+        MVI     C,80H
+        CALL    5
+        STA     200H
+        MVI     C,81H
+        CALL    5
+        SHLD    201H
+        MVI     C,2
+        MVI     E,'*'
+        CALL    5
+        RET
+    ", "");
+    system.ram[0x200..0x203].copy_from_slice(&[0xFF; 3]);
+    assert_eq!(run(&mut system), Status::Finished);
+    assert_eq!(system.receive(), b"*");
+    assert_eq!(system.ram[0x200..0x203], [0; 3]);
+}
+
+fn fcb(name: &str) -> [raw::u8; fcb::SIZE] {
+    let mut block = [0; fcb::SIZE];
+    block[..12].copy_from_slice(&fcb::parse(name));
+    block
+}
+
+#[test]
+fn files() {
+    let mut drive = Drive::new();
+    drive.insert(String::from("DATA.TXT"), (0..200).collect());
+    let mut board = Board::new(drive);
+    let at = 0x0200;
+    board.store(at, &fcb("data.txt"));
+    assert_eq!(board.bdos(15, at), Some(0));
+    assert_eq!(board.ram[at as usize + fcb::COUNT], 2);
+    assert_eq!(board.bdos(20, at), Some(0));
+    assert_eq!(board.bytes(0x0080, 2), [0, 1]);
+    assert_eq!(board.bdos(20, at), Some(0));
+    assert_eq!(board.bytes(0x0080 + 71, 2), [199, 0x1A]);
+    assert_eq!(board.bdos(20, at), Some(1));
+    assert_eq!(board.bdos(35, at), Some(0));
+    assert_eq!(board.bytes(at + fcb::RANDOM as raw::u16, 3), [2, 0, 0]);
+
+    let out = 0x0300;
+    board.store(out, &fcb("NEW.DAT"));
+    assert_eq!(board.bdos(22, out), Some(0));
+    assert_eq!(board.bdos(26, 0x0400), Some(0));
+    board.store(0x0400, &[b'A'; 128]);
+    assert_eq!(board.bdos(21, out), Some(0));
+    board.store(out + fcb::RANDOM as raw::u16, &[3, 0, 0]);
+    board.store(0x0400, &[b'B'; 128]);
+    assert_eq!(board.bdos(34, out), Some(0));
+    assert_eq!(board.bdos(36, out), Some(0));
+    assert_eq!(board.bytes(out + fcb::RANDOM as raw::u16, 3), [3, 0, 0]);
+    assert_eq!(board.bdos(16, out), Some(0));
+    assert_eq!(board.storage()["NEW.DAT"].len(), 512);
+    assert_eq!(board.storage()["NEW.DAT"][128..130], [0, 0]);
+    board.store(out + fcb::RANDOM as raw::u16, &[0, 0, 0]);
+    assert_eq!(board.bdos(33, out), Some(0));
+    assert_eq!(board.bytes(0x0400, 2), b"AA");
+    board.store(out + fcb::RANDOM as raw::u16, &[9, 0, 0]);
+    assert_eq!(board.bdos(33, out), Some(1));
+    board.store(out + fcb::RANDOM as raw::u16, &[0, 0, 1]);
+    assert_eq!(board.bdos(33, out), Some(6));
+
+    board.store(out + fcb::RENAME as raw::u16, &fcb::parse("LATER.DAT"));
+    assert_eq!(board.bdos(23, out), Some(0));
+    board.store(out, &fcb("*.*"));
+    assert_eq!(board.bdos(17, out), Some(0));
+    assert_eq!(board.bytes(0x0401, 11), b"DATA    TXT");
+    assert_eq!(board.bdos(18, out), Some(0));
+    assert_eq!(board.bytes(0x0401, 11), b"LATER   DAT");
+    assert_eq!(board.ram[0x0400 + fcb::COUNT], 4);
+    assert_eq!(board.bdos(18, out), Some(0xFF));
+    board.store(out, &fcb("DATA.???"));
+    assert_eq!(board.bdos(19, out), Some(0));
+    assert_eq!(board.bdos(19, out), Some(0xFF));
+    assert_eq!(board.bdos(15, out), Some(0xFF));
+    assert_eq!(board.storage().keys().collect::<Vec<_>>(), ["LATER.DAT"]);
+}
+
+#[cfg(feature="std")]
+#[test]
+fn directory() {
+    extern crate std;
+    let path = std::env::temp_dir().join(std::format!("lemurs-cpm-{}", std::process::id()));
+    std::fs::create_dir_all(&path).unwrap();
+    std::fs::write(path.join("Read.Me"), b"text").unwrap();
+    std::fs::write(path.join("much-too-long.name"), b"").unwrap();
+    let mut drive = Directory::new(&path);
+    assert_eq!(drive.files(), ["READ.ME"]);
+    let mut buffer = [0; 8];
+    assert_eq!(drive.read("READ.ME", 1, &mut buffer), Some(3));
+    assert_eq!(&buffer[..3], b"ext");
+    assert!(drive.create("NEW.TXT"));
+    assert!(drive.write("NEW.TXT", 2, b"hi"));
+    assert_eq!(drive.size("NEW.TXT"), Some(4));
+    assert!(drive.rename("NEW.TXT", "OLD.TXT"));
+    assert!(drive.delete("OLD.TXT"));
+    assert!(!drive.delete("OLD.TXT"));
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn disk() {
+    let mut disk = Disk::new();
+    assert!(disk.image().iter().all(|&byte| byte == 0xE5));
+    assert_eq!(disk.image().len(), 77 * 26 * 128);
+    assert!(disk.sector(0, 0).is_none());
+    assert!(disk.sector(0, 27).is_none());
+    assert!(disk.sector(77, 1).is_none());
+    disk.sector_mut(76, 26).unwrap().fill(1);
+    assert_eq!(disk.image()[disk::SIZE - 129..disk::SIZE - 127], [0xE5, 1]);
+    assert!(disk.set_system(&[2; bios::SYSTEM_SIZE]));
+    assert_eq!(disk.sector(0, 1).unwrap()[0], 0xE5);
+    assert_eq!(disk.sector(0, 2).unwrap()[0], 2);
+    assert_eq!(disk.sector(1, 19).unwrap()[127], 2);
+    assert_eq!(disk.sector(1, 20).unwrap()[0], 0xE5);
+    assert!(!disk.set_system(&[0; 51 * 128 + 1]));
+    assert_eq!(Disk::from_image(disk.image()), Some(disk.clone()));
+    assert_eq!(Disk::from_image(&disk.image()[1..]), None);
+}
+
+#[test]
+fn bios_boot() {
+    let system = Assembler::new().define("BIOS", bios::CCP + bios::SYSTEM_SIZE as raw::u16).assemble("
+        ORG     0E400H
+        MOV     A,C
+        STA     40H
+        MVI     C,'>'
+        CALL    BIOS+12
+        CALL    BIOS+9
+        MOV     C,A
+        CALL    BIOS+12
+        LXI     H,41H
+        INR     M
+        MVI     C,1
+        CALL    5
+        JMP     0
+    ").unwrap();
+    let mut disk = Disk::new();
+    let mut image = system.image;
+    image.resize(bios::SYSTEM_SIZE, 0);
+    disk.set_system(&image);
+
+    let mut system = Machine::new(Bios::new());
+    system.insert(0, disk);
+    assert_eq!(system.status(), Status::Ready);
+    let run = |system: &mut Machine<Bios, Bios>| {
+        for _ in 0..10_000 {
+            let _ = system.execute();
+            if let status @ (Status::Finished | Status::Waiting) = system.status() {
+                return status;
+            }
+        }
+        panic!("system didn't stop");
+    };
+    assert_eq!(run(&mut system), Status::Waiting);
+    assert_eq!(system.receive(), b">");
+    let peek = |system: &Machine<Bios, Bios>, address| system.read(Wrapping(address)).0;
+    assert_eq!((0..8).map(|address| peek(&system, address)).collect::<Vec<_>>(), [0xC3, 0x03, 0xFA, 0, 0, 0xC3, 0x06, 0xEC]);
+    system.send("a");
+    assert_eq!(run(&mut system), Status::Waiting);
+    assert_eq!(system.receive(), b"a>");
+    assert_eq!(peek(&system, 0x41), 1);
+    system.write(Wrapping(0x0004), Wrapping(0x12));
+    system.eject(0);
+    system.send("b");
+    assert_eq!(run(&mut system), Status::Finished);
+    assert_eq!(system.receive(), b"b");
+    assert_eq!(peek(&system, 0x40), 0);
+}
+
+#[test]
+fn bios_disk() {
+    let mut board = Bios::new();
+    assert_eq!(board.bios(9, 1, 0), Some(0));
+    board.insert(1, Disk::new());
+    let header = board.bios(9, 1, 0).unwrap();
+    assert_ne!(header, 0);
+    assert_eq!(board.bios(9, 0, 0), Some(0));
+    let word = |board: &Bios, address: raw::u16| board.read_word(Wrapping(address)).0;
+    let (translation, parameters) = (word(&board, header), word(&board, header + 10));
+    assert_eq!((0..15).map(|offset| board.read(Wrapping(parameters + offset)).0).collect::<Vec<_>>(), disk::PARAMETERS);
+    assert_eq!(board.bios(16, 1, translation), Some(7));
+    assert_eq!(board.bios(16, 25, translation), Some(22));
+    assert_eq!(board.bios(16, 1, 0), Some(2));
+
+    for offset in 0..128 { board.write(Wrapping(0x0100 + offset), Wrapping(offset as raw::u8)); }
+    assert_eq!(board.bios(10, 2, 0), Some(0));
+    assert_eq!(board.bios(11, 7, 0), Some(0));
+    assert_eq!(board.bios(12, 0x0100, 0), Some(0));
+    assert_eq!(board.bios(14, 0, 0), Some(0));
+    assert_eq!(board.disk(1).unwrap().sector(2, 7).unwrap()[..3], [0, 1, 2]);
+    assert_eq!(board.bios(12, 0x0200, 0), Some(0));
+    assert_eq!(board.bios(13, 0, 0), Some(0));
+    assert_eq!(board.read(Wrapping(0x027F)).0, 127);
+    assert_eq!(board.bios(11, 27, 0), Some(0));
+    assert_eq!(board.bios(13, 0, 0), Some(1));
+    assert_eq!(board.bios(8, 0, 0), Some(0));
+    assert_eq!(board.bios(11, 1, 0), Some(0));
+    assert_eq!(board.bios(13, 0, 0), Some(0));
+    assert_eq!(board.read(Wrapping(0x0200)).0, 0xE5);
+
+    board.bios(5, b'P' as raw::u16, 0);
+    assert_eq!(board.printed(), b"P");
+    assert_eq!(board.bios(3, 0, 0), None);
+    board.send("k");
+    assert_eq!(board.bios(2, 0, 0), Some(0xFF));
+    assert_eq!(board.bios(3, 0, 0), Some(b'k' as raw::u16));
+}
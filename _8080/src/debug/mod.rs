@@ -0,0 +1,254 @@
+//! A debugger, which runs a machine until it reaches a breakpoint or a watchpoint, or steps it
+//! one instruction, one call or one return at a time.
+//!
+//! A `Debugger` takes over a `Machine` and still gives access to it, so the registers, memory
+//! and Harness can be examined and changed between runs. A breakpoint stops the machine before
+//! it executes the instruction at an address, if the breakpoint's condition (if it has one)
+//! holds then; resuming from a breakpoint executes that instruction rather than stopping there
+//! again. A watchpoint stops the machine after an instruction that reads or writes memory in a
+//! range, or inputs from or outputs to a range of ports; it sees the accesses the instruction
+//! makes through the Harness, but not the fetching of the instruction itself, nor the accesses
+//! of an interrupt. Every run returns the `Reason` it stopped for.
+
+use crate::prelude::{*, vec::Vec};
+use crate::chip::{access::Internal, opcode::Op};
+use crate::image::bounds;
+use crate::run::{Stop, Summary};
+use core::ops::RangeBounds;
+
+/// A handle to a breakpoint or watchpoint, for removing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id(usize);
+
+/// A kind of access that a watchpoint watches for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Input,
+    Output,
+}
+
+/// An access that a watchpoint saw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub access: Access,
+    /// The memory address, or the port number.
+    pub address: raw::u16,
+    /// The value read or written.
+    pub value: raw::u8,
+}
+
+/// Why the debugger stopped the machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The machine reached a breakpoint at `address`, and its condition held.
+    Breakpoint { id: Id, address: raw::u16 },
+    /// The last instruction made an access that a watchpoint watches for; this is the first
+    /// such access it made.
+    Watchpoint { id: Id, hit: Hit },
+    /// A step finished.
+    Stepped,
+    /// The machine reached the address given to `run_to`.
+    Cursor,
+    /// `run_for` used up its budget of cycles.
+    Budget,
+    /// The processor halted, or was halted by the Harness.
+    Halted,
+    /// The processor couldn't go on; with the `"open"` feature, this carries the message the
+    /// Harness gave.
+    Failed(String),
+}
+
+/// A range of addresses or ports watched for one kind of access.
+#[derive(Debug, Clone, Copy)]
+struct Watchpoint {
+    id: Id,
+    access: Access,
+    start: usize,
+    end: usize,
+}
+
+/// The watchpoints on a machine, and the first access one of them saw during the last
+/// instruction.
+#[derive(Debug, Default)]
+pub(crate) struct Watches {
+    points: Vec<Watchpoint>,
+    pub(crate) hit: Option<(Id, Hit)>,
+}
+
+impl Watches {
+    pub(crate) fn check(&self, access: Access, address: raw::u16, value: raw::u8) -> Option<(Id, Hit)> {
+        let point = self.points.iter().find(|point| point.access == access && (point.start..point.end).contains(&(address as usize)))?;
+        Some((point.id, Hit { access, address, value }))
+    }
+}
+
+type Condition<H, C> = Box<dyn FnMut(&Machine<H, C>) -> bool>;
+
+struct Breakpoint<H: Harness + ?Sized, C: BorrowMut<H>> {
+    id: Id,
+    address: raw::u16,
+    condition: Option<Condition<H, C>>,
+}
+
+/// A machine under a debugger, along with its breakpoints; its watchpoints are kept on the
+/// machine, which checks them as it executes.
+pub struct Debugger<H: Harness + ?Sized, C: BorrowMut<H>> {
+    machine: Machine<H, C>,
+    breakpoints: Vec<Breakpoint<H, C>>,
+    issued: usize,
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Deref for Debugger<H, C> {
+    type Target = Machine<H, C>;
+    fn deref(&self) -> &Self::Target { &self.machine }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> DerefMut for Debugger<H, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.machine }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Debugger<H, C> {
+    /// Puts a machine under a debugger, with no breakpoints or watchpoints.
+    pub fn new(mut machine: Machine<H, C>) -> Self {
+        machine.watches = Some(Box::default());
+        Self { machine, breakpoints: Vec::new(), issued: 0 }
+    }
+
+    /// Takes the machine back from the debugger.
+    pub fn into_inner(mut self) -> Machine<H, C> {
+        self.machine.watches = None;
+        self.machine
+    }
+
+    fn issue(&mut self) -> Id {
+        self.issued += 1;
+        Id(self.issued)
+    }
+
+    /// Sets a breakpoint at an address.
+    pub fn break_at(&mut self, address: raw::u16) -> Id {
+        let id = self.issue();
+        self.breakpoints.push(Breakpoint { id, address, condition: None });
+        id
+    }
+
+    /// Sets a breakpoint at an address that only stops the machine if `condition` is true of
+    /// it when it gets there.
+    pub fn break_if(&mut self, address: raw::u16, condition: impl FnMut(&Machine<H, C>) -> bool + 'static) -> Id {
+        let id = self.issue();
+        self.breakpoints.push(Breakpoint { id, address, condition: Some(Box::new(condition)) });
+        id
+    }
+
+    /// Sets a watchpoint for one kind of access to a range of memory addresses, or, for input
+    /// and output, of ports.
+    pub fn watch(&mut self, access: Access, range: impl RangeBounds<raw::u16>) -> Id {
+        let id = self.issue();
+        let (start, end) = bounds(range);
+        if let Some(watches) = self.machine.watches.as_deref_mut() {
+            watches.points.push(Watchpoint { id, access, start, end });
+        }
+        id
+    }
+
+    /// Removes a breakpoint or watchpoint, returning whether there was one.
+    pub fn remove(&mut self, id: Id) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        let Some(watches) = self.machine.watches.as_deref_mut() else { return false };
+        let watched = watches.points.len();
+        watches.points.retain(|point| point.id != id);
+        self.breakpoints.len() < before || watches.points.len() < watched
+    }
+
+    /// Runs the machine until `until` gives a reason to stop before an instruction, or a
+    /// breakpoint or watchpoint stops it, or the processor can't go on. A halted processor
+    /// waits for events, but no more than `limit` cycles.
+    fn go(&mut self, limit: Option<usize>, mut until: impl FnMut(&Machine<H, C>, &Summary) -> Option<Reason>) -> Reason {
+        if let Some(watches) = self.machine.watches.as_deref_mut() { watches.hit = None; }
+        let breakpoints = &mut self.breakpoints;
+        let mut reason = None;
+        let summary = self.machine.drive(limit, |machine, summary| {
+            reason = machine.watches.as_ref().and_then(|watches| watches.hit)
+                .map(|(id, hit)| Reason::Watchpoint { id, hit })
+                .or_else(|| until(machine, summary));
+            if reason.is_none() && summary.instructions > 0 {
+                let pc = machine.chip[Internal::ProgramCounter].0;
+                reason = breakpoints.iter_mut()
+                    .filter(|breakpoint| breakpoint.address == pc)
+                    .find_map(|Breakpoint { id, address, condition }| {
+                        condition.as_mut().is_none_or(|condition| condition(machine)).then_some(Reason::Breakpoint { id: *id, address: *address })
+                    });
+            }
+            reason.is_some().then_some(Stop::Breakpoint)
+        });
+        reason.unwrap_or(match summary.stop {
+            Stop::Halted => Reason::Halted,
+            Stop::Error(message) => Reason::Failed(message),
+            Stop::Budget | Stop::Breakpoint => Reason::Budget,
+        })
+    }
+
+    /// Runs until a breakpoint or watchpoint stops the machine, or the processor halts or fails.
+    pub fn resume(&mut self) -> Reason { self.go(None, |_, _| None) }
+
+    /// Runs until at least `budget` processor cycles have passed, unless something else stops
+    /// the machine first.
+    pub fn run_for(&mut self, budget: usize) -> Reason {
+        self.go(Some(budget), |_, summary| (summary.cycles >= budget).then_some(Reason::Budget))
+    }
+
+    /// Runs until the machine is about to execute the instruction at `address`, unless
+    /// something else stops it first.
+    pub fn run_to(&mut self, address: raw::u16) -> Reason {
+        self.go(None, |machine, summary| {
+            (summary.instructions > 0 && machine.chip[Internal::ProgramCounter].0 == address).then_some(Reason::Cursor)
+        })
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self) -> Reason {
+        self.go(None, |_, summary| (summary.instructions > 0).then_some(Reason::Stepped))
+    }
+
+    /// Executes one instruction, but runs a call (or `RST`) through to its return, stopping
+    /// when the machine gets back to the instruction after it with the stack as it was.
+    pub fn step_over(&mut self) -> Reason {
+        let (pc, sp) = (self.chip[Internal::ProgramCounter].0, self.chip[Internal::StackPointer].0);
+        match self.op_at_pc() {
+            Some(op @ (Op::Call{..} | Op::CallIf(..) | Op::Reset{..})) => {
+                let after = pc.wrapping_add(op.len() as raw::u16);
+                self.go(None, |machine, summary| {
+                    let (pc, now) = (machine.chip[Internal::ProgramCounter].0, machine.chip[Internal::StackPointer].0);
+                    (summary.instructions > 0 && pc == after && now >= sp).then_some(Reason::Stepped)
+                })
+            }
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until the subroutine the machine is in returns, stopping after the return that
+    /// takes the stack above where it is now.
+    pub fn step_out(&mut self) -> Reason {
+        let sp = self.chip[Internal::StackPointer].0;
+        let mut returning = false;
+        self.go(None, move |machine, _| {
+            if returning && machine.chip[Internal::StackPointer].0 > sp { return Some(Reason::Stepped); }
+            returning = matches!(machine.op_at_pc(), Some(Op::Return | Op::ReturnIf(..)));
+            None
+        })
+    }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// The instruction at the program counter, if the bytes there make one.
+    fn op_at_pc(&self) -> Option<Op> {
+        let pc = self.chip[Internal::ProgramCounter];
+        Op::extract((0..3).map(|offset| self.read(pc + Wrapping(offset)))).ok().map(|(op, _)| op)
+    }
+}
+
+#[cfg(test)]
+mod tests;
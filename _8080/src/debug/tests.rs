@@ -0,0 +1,86 @@
+use super::*;
+use crate::{MemoryMap, assembler::assemble, chip::access::Register};
+
+fn debugger() -> Debugger<MemoryMap, MemoryMap> {
+    let mut memory = MemoryMap::new().ram(..);
+    memory.program(0, &assemble("
+        LXI     SP,100H
+        MVI     B,0
+LOOP:   CALL    BUMP
+        OUT     2
+        INR     B
+        JMP     LOOP
+BUMP:   LDA     80H
+        INR     A
+        STA     80H
+        CALL    INNER
+        RET
+INNER:  NOP
+        RET
+    ").unwrap().image);
+    Debugger::new(Machine::new(memory))
+}
+
+fn pc<H: Harness + ?Sized, C: BorrowMut<H>>(debugger: &Debugger<H, C>) -> raw::u16 {
+    debugger.chip[Internal::ProgramCounter].0
+}
+
+#[test]
+fn breakpoints() {
+    let mut debugger = debugger();
+    let out = debugger.break_at(0x08);
+    assert_eq!(debugger.resume(), Reason::Breakpoint { id: out, address: 0x08 });
+    assert_eq!(debugger.resume(), Reason::Breakpoint { id: out, address: 0x08 });
+    assert_eq!(debugger.chip[Register::B].0, 1);
+    assert!(debugger.remove(out));
+    assert!(!debugger.remove(out));
+
+    let third = debugger.break_if(0x0A, |machine| machine.chip[Register::B].0 == 3);
+    assert_eq!(debugger.resume(), Reason::Breakpoint { id: third, address: 0x0A });
+    assert_eq!(debugger.chip[Register::B].0, 3);
+    assert_eq!(debugger.run_for(100), Reason::Budget);
+    let machine = debugger.into_inner();
+    assert!(machine.watches.is_none());
+}
+
+#[test]
+fn watchpoints() {
+    let mut debugger = debugger();
+    let read = debugger.watch(Access::Read, 0x80..=0x80);
+    let write = debugger.watch(Access::Write, 0x80..0x81);
+    let output = debugger.watch(Access::Output, 2..=2);
+    assert_eq!(debugger.resume(), Reason::Watchpoint { id: read, hit: Hit { access: Access::Read, address: 0x80, value: 0 } });
+    assert_eq!(pc(&debugger), 0x11);
+    assert_eq!(debugger.resume(), Reason::Watchpoint { id: write, hit: Hit { access: Access::Write, address: 0x80, value: 1 } });
+    assert_eq!(pc(&debugger), 0x15);
+    assert_eq!(debugger.resume(), Reason::Watchpoint { id: output, hit: Hit { access: Access::Output, address: 2, value: 1 } });
+    debugger.remove(read);
+    debugger.remove(write);
+    let stack = debugger.watch(Access::Write, 0xFE..);
+    assert_eq!(debugger.resume(), Reason::Watchpoint { id: stack, hit: Hit { access: Access::Write, address: 0xFE, value: 0x08 } });
+    assert_eq!(pc(&debugger), 0x0E);
+}
+
+#[test]
+fn stepping() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.step(), Reason::Stepped);
+    assert_eq!(debugger.step(), Reason::Stepped);
+    assert_eq!(pc(&debugger), 0x05);
+    assert_eq!(debugger.step_over(), Reason::Stepped);
+    assert_eq!((pc(&debugger), debugger.read(Wrapping(0x80)).0), (0x08, 1));
+    assert_eq!(debugger.step_over(), Reason::Stepped);
+    assert_eq!(pc(&debugger), 0x0A);
+
+    assert_eq!(debugger.run_to(0x15), Reason::Cursor);
+    assert_eq!(debugger.step(), Reason::Stepped);
+    assert_eq!(pc(&debugger), 0x19);
+    assert_eq!(debugger.step_out(), Reason::Stepped);
+    assert_eq!(pc(&debugger), 0x18);
+    assert_eq!(debugger.step_out(), Reason::Stepped);
+    assert_eq!(pc(&debugger), 0x08);
+
+    let inner = debugger.break_at(0x19);
+    debugger.run_to(0x05);
+    assert_eq!(debugger.step_over(), Reason::Breakpoint { id: inner, address: 0x19 });
+}
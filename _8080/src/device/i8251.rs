@@ -0,0 +1,280 @@
+//! The Intel 8251 USART, with its serial line connected to host streams.
+
+use crate::prelude::{*, vec::Vec};
+use crate::snapshot::{self, Snapshot};
+use super::Device;
+extern crate std;
+use std::io::{Read, Write};
+
+/// Status register bits.
+const TX_READY: raw::u8 = 0x01;
+const RX_READY: raw::u8 = 0x02;
+const TX_EMPTY: raw::u8 = 0x04;
+const OVERRUN: raw::u8 = 0x10;
+const SYNC_DETECT: raw::u8 = 0x40;
+const DATA_SET_READY: raw::u8 = 0x80;
+
+/// Command instruction bits.
+const TX_ENABLE: raw::u8 = 0x01;
+const TERMINAL_READY: raw::u8 = 0x02;
+const RX_ENABLE: raw::u8 = 0x04;
+const ERROR_RESET: raw::u8 = 0x10;
+const REQUEST_TO_SEND: raw::u8 = 0x20;
+const INTERNAL_RESET: raw::u8 = 0x40;
+const ENTER_HUNT: raw::u8 = 0x80;
+
+/// What the next write to the control register will be taken as; sync characters are counted
+/// from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Mode,
+    Sync(usize),
+    Command,
+}
+
+/// An 8251 USART. Register 0 is the data register and register 1 is the control register
+/// (mode, sync characters and commands when written, status when read), so it is usually
+/// attached with a mask of 0xFE.
+///
+/// Characters written by the program are written to `output` once they have been shifted out
+/// of the transmitter, and characters for the receiver are read from `input` one at a time and
+/// arrive once they have been shifted in; an input that has nothing to read (it returns no
+/// bytes or an error) leaves the line idle. Shifting takes as long as the character would on a
+/// real line, counting start, parity and stop bits, at `clock` processor cycles for each cycle
+/// of the TxC and RxC clocks and the baud rate factor set by the mode instruction. The line
+/// itself never garbles a character, so there are no parity or framing errors, only overruns.
+///
+/// In synchronous mode, the receiver hunts for the sync characters after an enter hunt command
+/// (unless the mode selects external sync detection) and the characters it hunts through are
+/// lost; the transmitter doesn't fill idle time with sync characters.
+///
+/// A snapshot keeps the chip's registers and the characters in flight, but not the streams.
+#[derive(Debug)]
+pub struct I8251<R: Read, W: Write> {
+    input: R,
+    output: W,
+    clock: usize,
+    expect: Expect,
+    mode: raw::u8,
+    sync: [raw::u8; 2],
+    command: raw::u8,
+    buffer: Option<raw::u8>,
+    shifting: Option<(raw::u8, usize)>,
+    received: Option<raw::u8>,
+    incoming: Option<(raw::u8, usize)>,
+    errors: raw::u8,
+    hunting: bool,
+    previous: Option<raw::u8>,
+    detected: bool,
+    ready: bool,
+}
+
+impl<R: Read, W: Write> I8251<R, W> {
+    /// Creates a USART whose TxC and RxC clocks run once every `clock` processor cycles.
+    pub fn new(input: R, output: W, clock: usize) -> Self {
+        Self {
+            input, output, clock, expect: Expect::Mode, mode: 0, sync: [0; 2], command: 0,
+            buffer: None, shifting: None, received: None, incoming: None, errors: 0, hunting: false,
+            previous: None, detected: false, ready: true,
+        }
+    }
+
+    /// The streams behind the serial line.
+    pub fn get_mut(&mut self) -> (&mut R, &mut W) { (&mut self.input, &mut self.output) }
+
+    /// Unwraps the streams behind the serial line.
+    pub fn into_inner(self) -> (R, W) { (self.input, self.output) }
+
+    /// Sets the DSR input, which the program sees in the status register; it starts out set.
+    pub fn set_data_set_ready(&mut self, ready: bool) { self.ready = ready; }
+
+    /// Whether the program has set the DTR output.
+    pub fn data_terminal_ready(&self) -> bool { self.command & TERMINAL_READY != 0 }
+
+    /// Whether the program has set the RTS output.
+    pub fn request_to_send(&self) -> bool { self.command & REQUEST_TO_SEND != 0 }
+
+    fn synchronous(&self) -> bool { self.mode & 0x03 == 0 }
+
+    /// The number of data bits in a character.
+    fn length(&self) -> u32 { 5 + (self.mode >> 2 & 0x03) as u32 }
+
+    /// The number of processor cycles a character takes on the line.
+    fn character(&self) -> usize {
+        let parity = (self.mode >> 4 & 1) as usize;
+        let data = self.length() as usize;
+        let (factor, half_bits) = match self.mode & 0x03 {
+            0 => (1, 2 * (data + parity)),
+            rate => {
+                let stop = match self.mode >> 6 { 2 => 3, 3 => 4, _ => 2 };
+                ([1, 16, 64][rate as usize - 1], 2 * (1 + data + parity) + stop)
+            }
+        };
+        (self.clock * factor * half_bits).div_ceil(2).max(1)
+    }
+
+    fn mask(&self, value: raw::u8) -> raw::u8 { value & (0xFF >> (8 - self.length())) }
+
+    /// Starts shifting out the buffered character, if the transmitter is free to.
+    fn load(&mut self) {
+        if self.shifting.is_none() && self.command & TX_ENABLE != 0 {
+            if let Some(value) = self.buffer.take() {
+                self.shifting = Some((value, self.character()));
+            }
+        }
+    }
+
+    fn transmit(&mut self, mut cycles: usize) {
+        self.load();
+        while let Some((value, left)) = self.shifting {
+            if left > cycles {
+                self.shifting = Some((value, left - cycles));
+                return;
+            }
+            cycles -= left;
+            self.shifting = None;
+            let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+            self.load();
+        }
+    }
+
+    /// The number of sync characters the mode calls for.
+    fn syncs(&self) -> usize { if self.mode & 0x80 != 0 { 1 } else { 2 } }
+
+    /// Takes a character that has finished arriving, hunting for sync characters if need be.
+    fn arrive(&mut self, value: raw::u8) {
+        if self.hunting {
+            let found = match self.syncs() {
+                1 => value == self.mask(self.sync[0]),
+                _ => self.previous == Some(self.mask(self.sync[0])) && value == self.mask(self.sync[1]),
+            };
+            self.previous = Some(value);
+            if found {
+                self.hunting = false;
+                self.detected = true;
+            }
+            return;
+        }
+        if self.received.replace(value).is_some() {
+            self.errors |= OVERRUN;
+        }
+    }
+
+    fn receive(&mut self, mut cycles: usize) {
+        loop {
+            if self.command & RX_ENABLE == 0 { return; }
+            if self.incoming.is_none() {
+                let mut byte = [0];
+                if !matches!(self.input.read(&mut byte), Ok(1)) { return; }
+                self.incoming = Some((self.mask(byte[0]), self.character()));
+            }
+            let Some((value, left)) = self.incoming else { return };
+            if left > cycles {
+                self.incoming = Some((value, left - cycles));
+                return;
+            }
+            cycles -= left;
+            self.incoming = None;
+            self.arrive(value);
+        }
+    }
+
+    fn control(&mut self, value: raw::u8) {
+        match self.expect {
+            Expect::Mode => {
+                self.mode = value;
+                self.expect = if self.synchronous() { Expect::Sync(0) } else { Expect::Command };
+            }
+            Expect::Sync(index) => {
+                self.sync[index] = value;
+                self.expect = if index + 1 < self.syncs() { Expect::Sync(index + 1) } else { Expect::Command };
+            }
+            Expect::Command if value & INTERNAL_RESET != 0 => self.reset(),
+            Expect::Command => {
+                self.command = value;
+                if value & ERROR_RESET != 0 { self.errors = 0; }
+                if value & ENTER_HUNT != 0 && self.synchronous() && self.mode & 0x40 == 0 {
+                    self.hunting = true;
+                    self.previous = None;
+                    self.detected = false;
+                }
+                self.load();
+            }
+        }
+    }
+
+    fn status(&mut self) -> raw::u8 {
+        let mut status = self.errors;
+        if self.buffer.is_none() { status |= TX_READY; }
+        if self.received.is_some() { status |= RX_READY; }
+        if self.buffer.is_none() && self.shifting.is_none() { status |= TX_EMPTY; }
+        if self.detected { status |= SYNC_DETECT; }
+        if self.ready { status |= DATA_SET_READY; }
+        self.detected = false;
+        status
+    }
+}
+
+impl<R: Read, W: Write> Device for I8251<R, W> {
+    fn read(&mut self, register: raw::u8) -> raw::u8 {
+        match register & 1 {
+            0 => self.received.take().unwrap_or(0),
+            _ => self.status(),
+        }
+    }
+
+    fn write(&mut self, register: raw::u8, value: raw::u8) {
+        match register & 1 {
+            0 => {
+                self.buffer = Some(self.mask(value));
+                self.load();
+            }
+            _ => self.control(value),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.expect = Expect::Mode;
+        self.command = 0;
+        self.buffer = None;
+        self.shifting = None;
+        self.received = None;
+        self.incoming = None;
+        self.errors = 0;
+        self.hunting = false;
+        self.detected = false;
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.transmit(cycles);
+        self.receive(cycles);
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        let expect = match self.expect { Expect::Mode => 0, Expect::Sync(index) => 1 + index as raw::u8, Expect::Command => 3 };
+        [expect, self.mode, self.sync[0], self.sync[1], self.command, self.errors].save(out);
+        [self.buffer, self.received, self.previous].save(out);
+        [self.shifting, self.incoming].save(out);
+        [self.hunting, self.detected, self.ready].save(out);
+    }
+
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        let (mut bytes, mut held, mut moving, mut bits) = ([0; 6], [None; 3], [None; 2], [false; 3]);
+        bytes.restore(data)?;
+        held.restore(data)?;
+        moving.restore(data)?;
+        bits.restore(data)?;
+        let [expect, mode, sync0, sync1, command, errors] = bytes;
+        self.expect = match expect {
+            0 => Expect::Mode,
+            1 | 2 => Expect::Sync(expect as usize - 1),
+            3 => Expect::Command,
+            _ => return Err(snapshot::Error::Mismatch),
+        };
+        (self.mode, self.sync, self.command, self.errors) = (mode, [sync0, sync1], command, errors);
+        [self.buffer, self.received, self.previous] = held;
+        [self.shifting, self.incoming] = moving;
+        [self.hunting, self.detected, self.ready] = bits;
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+//! The Intel 8253 programmable interval timer.
+
+use crate::prelude::{*, vec::Vec};
+use crate::snapshot::{self, Snapshot};
+use super::{Device, Vector};
+
+/// How a counter's count is read and written, from bits 4 and 5 of its control word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Low,
+    High,
+    Both,
+}
+
+/// One of the timer's three counters.
+#[derive(Debug, Clone)]
+struct Counter {
+    mode: raw::u8,
+    access: Access,
+    bcd: bool,
+    /// The count most recently written, with 0 already turned into 65536 (or 10000 in BCD).
+    reload: u32,
+    count: u32,
+    /// Whether a count has been written since the control word.
+    written: bool,
+    /// Whether the count will be loaded on the next clock.
+    armed: bool,
+    running: bool,
+    out: bool,
+    gate: bool,
+    /// Whether the gate has risen since the last clock.
+    triggered: bool,
+    /// The low byte of a count being written in two parts.
+    low: Option<raw::u8>,
+    latch: Option<raw::u16>,
+    /// Whether the next read of a two-part count is of its high byte.
+    reading_high: bool,
+}
+
+impl Counter {
+    fn new() -> Self {
+        Self {
+            mode: 0, access: Access::Both, bcd: false, reload: 0x10000, count: 0, written: false,
+            armed: false, running: false, out: false, gate: true, triggered: false, low: None,
+            latch: None, reading_high: false,
+        }
+    }
+
+    fn program(&mut self, control: raw::u8) {
+        self.access = match control >> 4 & 3 {
+            1 => Access::Low,
+            2 => Access::High,
+            _ => Access::Both,
+        };
+        self.mode = match control >> 1 & 7 { mode @ 6..=7 => mode - 4, mode => mode };
+        self.bcd = control & 1 != 0;
+        self.out = self.mode != 0;
+        self.written = false;
+        self.armed = false;
+        self.running = false;
+        self.low = None;
+        self.latch = None;
+        self.reading_high = false;
+    }
+
+    /// The count as the program sees it.
+    fn value(&self) -> raw::u16 {
+        match self.bcd {
+            true => {
+                let count = self.count % 10000;
+                (0..4).rev().fold(0, |bcd, digit| bcd << 4 | (count / 10u32.pow(digit) % 10) as raw::u16)
+            }
+            false => self.count as raw::u16,
+        }
+    }
+
+    fn load(&mut self, value: raw::u16) {
+        let count = match self.bcd {
+            true => (0..4).fold(0, |count, digit| count * 10 + (value >> (12 - 4 * digit) & 0xF).min(9) as u32),
+            false => value as u32,
+        };
+        self.reload = match count { 0 if self.bcd => 10000, 0 => 0x10000, count => count };
+        self.written = true;
+        match self.mode {
+            0 => { self.out = false; self.armed = true; self.running = false; }
+            2 | 3 => self.armed |= !self.running,
+            4 => { self.armed = true; self.running = false; }
+            _ => (),
+        }
+    }
+
+    fn write(&mut self, value: raw::u8) {
+        match (self.access, self.low.take()) {
+            (Access::Low, _) => self.load(value as raw::u16),
+            (Access::High, _) => self.load((value as raw::u16) << 8),
+            (Access::Both, None) => self.low = Some(value),
+            (Access::Both, Some(low)) => self.load(raw::u16::from_le_bytes([low, value])),
+        }
+    }
+
+    fn read(&mut self) -> raw::u8 {
+        let [low, high] = self.latch.unwrap_or_else(|| self.value()).to_le_bytes();
+        match self.access {
+            Access::Low => { self.latch = None; low }
+            Access::High => { self.latch = None; high }
+            Access::Both if self.reading_high => { self.reading_high = false; self.latch = None; high }
+            Access::Both => { self.reading_high = true; low }
+        }
+    }
+
+    fn set_gate(&mut self, gate: bool) {
+        self.triggered |= gate && !self.gate;
+        self.gate = gate;
+        if !gate && matches!(self.mode, 2 | 3) { self.out = true; }
+    }
+
+    /// Takes the count from `reload`, as the count starts or restarts.
+    fn start(&mut self) {
+        self.count = match self.mode {
+            3 if self.out => self.reload + (self.reload & 1),
+            3 => (self.reload - (self.reload & 1)).max(2),
+            _ => self.reload,
+        };
+        self.armed = false;
+        self.running = true;
+    }
+
+    /// Applies one pulse of the counter's clock.
+    fn clock(&mut self) {
+        let triggered = core::mem::take(&mut self.triggered) && self.written;
+        match self.mode {
+            0 => {
+                if self.armed { return self.start(); }
+                if self.running && self.gate && self.low.is_none() {
+                    self.count = match self.count {
+                        0 if self.bcd => 9999,
+                        0 => 0xFFFF,
+                        count => count - 1,
+                    };
+                    self.out |= self.count == 0;
+                }
+            }
+            1 | 5 => {
+                if triggered {
+                    self.start();
+                    self.out = self.mode == 5;
+                    return;
+                }
+                if self.mode == 5 && !self.out { self.out = true; }
+                if self.running {
+                    self.count -= 1;
+                    if self.count == 0 {
+                        self.out = self.mode == 1;
+                        self.running = false;
+                    }
+                }
+            }
+            2 => {
+                if self.armed || triggered {
+                    self.out = true;
+                    return self.start();
+                }
+                if self.running && self.gate {
+                    self.count -= 1;
+                    match self.count {
+                        0 => { self.out = true; self.start(); }
+                        1 => self.out = false,
+                        _ => (),
+                    }
+                }
+            }
+            3 => {
+                if self.armed || triggered {
+                    self.out = true;
+                    return self.start();
+                }
+                if self.running && self.gate {
+                    self.count = self.count.saturating_sub(2);
+                    if self.count == 0 {
+                        self.out = !self.out;
+                        self.start();
+                    }
+                }
+            }
+            _ => {
+                if !self.out { self.out = true; }
+                if self.armed { return self.start(); }
+                if self.running && self.gate {
+                    self.count -= 1;
+                    if self.count == 0 {
+                        self.out = false;
+                        self.running = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An 8253 programmable interval timer. Registers 0 to 2 are the counters and register 3 is
+/// the control word, so it is usually attached with a mask of 0xFC.
+///
+/// The counters' clock inputs are driven from the processor's: each counter counts once
+/// every `divisor` processor cycles passed to `tick`, which `Machine::clock_devices` does as
+/// the machine runs. Their gate inputs start out high and are
+/// set with `set_gate`. A counter's OUT line can be connected to an interrupt with `connect`,
+/// which makes the timer request that interrupt each time the line rises.
+#[derive(Debug, Clone)]
+pub struct I8253 {
+    counters: [Counter; 3],
+    divisor: usize,
+    cycles: usize,
+    vectors: [Option<raw::u8>; 3],
+    pending: [bool; 3],
+}
+
+impl I8253 {
+    /// Creates a timer whose counters count once every `divisor` processor cycles.
+    pub fn new(divisor: usize) -> Self {
+        Self {
+            counters: [Counter::new(), Counter::new(), Counter::new()],
+            divisor: divisor.max(1), cycles: 0, vectors: [None; 3], pending: [false; 3],
+        }
+    }
+
+    /// Connects the OUT line of a counter (0 to 2) to the interrupt that restarts at `vector`
+    /// (0 to 7), or disconnects it. Panics if `counter` is 3 or more, as do `set_gate` and `out`.
+    pub fn connect(&mut self, counter: usize, vector: Option<raw::u8>) {
+        Self::check(counter);
+        self.vectors[counter] = vector;
+        self.pending[counter] = false;
+    }
+
+    /// Sets the gate input of a counter (0 to 2).
+    pub fn set_gate(&mut self, counter: usize, gate: bool) {
+        Self::check(counter);
+        self.counters[counter].set_gate(gate);
+    }
+
+    /// The level of the OUT line of a counter (0 to 2).
+    pub fn out(&self, counter: usize) -> bool {
+        Self::check(counter);
+        self.counters[counter].out
+    }
+
+    fn check(counter: usize) { assert!(counter < 3, "the 8253 has counters 0 to 2, not {counter}"); }
+
+    /// Applies one pulse of the counters' clocks.
+    fn clock(&mut self) {
+        for (index, counter) in self.counters.iter_mut().enumerate() {
+            let before = counter.out;
+            counter.clock();
+            if counter.out && !before && self.vectors[index].is_some() {
+                self.pending[index] = true;
+            }
+        }
+    }
+}
+
+impl Snapshot for Counter {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        [self.mode, self.access as raw::u8].save(out);
+        [self.reload, self.count].save(out);
+        [self.bcd, self.written, self.armed, self.running, self.out, self.gate, self.triggered, self.reading_high].save(out);
+        self.low.save(out);
+        self.latch.save(out);
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        let (mut bytes, mut counts, mut bits) = ([0; 2], [0; 2], [false; 8]);
+        bytes.restore(data)?;
+        counts.restore(data)?;
+        bits.restore(data)?;
+        let [mode, access] = bytes;
+        self.access = match access {
+            0 => Access::Low,
+            1 => Access::High,
+            2 => Access::Both,
+            _ => return Err(snapshot::Error::Mismatch),
+        };
+        let [reload, count] = counts;
+        if mode > 5 || !(1..=0x10000).contains(&reload) || count > 0x10001 {
+            return Err(snapshot::Error::Mismatch);
+        }
+        (self.mode, self.reload, self.count) = (mode, reload, count);
+        [self.bcd, self.written, self.armed, self.running, self.out, self.gate, self.triggered, self.reading_high] = bits;
+        // Only mode 0 counts on from 0; in the others a running counter stops or reloads there.
+        if self.running && self.count == 0 && self.mode != 0 { return Err(snapshot::Error::Mismatch); }
+        self.low.restore(data)?;
+        self.latch.restore(data)
+    }
+}
+
+impl Device for I8253 {
+    fn read(&mut self, register: raw::u8) -> raw::u8 {
+        match register & 3 {
+            3 => 0xFF,
+            counter => self.counters[counter as usize].read(),
+        }
+    }
+
+    fn write(&mut self, register: raw::u8, value: raw::u8) {
+        match register & 3 {
+            3 => match (value >> 6, value >> 4 & 3) {
+                (3, _) => (),
+                (counter, 0) => {
+                    let counter = &mut self.counters[counter as usize];
+                    counter.latch.get_or_insert(counter.value());
+                }
+                (counter, _) => self.counters[counter as usize].program(value),
+            }
+            counter => self.counters[counter as usize].write(value),
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self { vectors: self.vectors, ..Self::new(self.divisor) };
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.cycles += cycles;
+        while self.cycles >= self.divisor {
+            self.cycles -= self.divisor;
+            self.clock();
+        }
+    }
+
+    fn request(&self) -> Option<Vector> {
+        (0..3).find(|&index| self.pending[index]).and_then(|index| self.vectors[index]).map(Vector::Restart)
+    }
+
+    fn acknowledge(&mut self) {
+        if let Some(index) = (0..3).find(|&index| self.pending[index]) {
+            self.pending[index] = false;
+        }
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.counters.save(out);
+        self.cycles.save(out);
+        self.pending.save(out);
+    }
+
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        self.counters.restore(data)?;
+        self.cycles.restore(data)?;
+        self.pending.restore(data)
+    }
+}
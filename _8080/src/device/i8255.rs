@@ -0,0 +1,345 @@
+//! The Intel 8255 programmable peripheral interface.
+
+use crate::prelude::{*, vec::Vec};
+use crate::snapshot::{self, Snapshot};
+use super::{Device, Vector};
+
+/// Port C bits that carry the handshake signals of group A and group B.
+const INTR_B: raw::u8 = 0x01;
+const IBF_B: raw::u8 = 0x02;
+const STB_B: raw::u8 = 0x04;
+const INTR_A: raw::u8 = 0x08;
+const STB_A: raw::u8 = 0x10;
+const IBF_A: raw::u8 = 0x20;
+const ACK_A: raw::u8 = 0x40;
+const OBF_A: raw::u8 = 0x80;
+
+/// One of the PPI's three ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+    C,
+}
+
+/// An 8255 programmable peripheral interface. Registers 0 to 2 are ports A, B and C and
+/// register 3 is the control word, so it is usually attached with a mask of 0xFC. A control
+/// word with bit 7 clear sets or resets a single bit of port C instead of changing the modes.
+///
+/// The peripherals drive the input pins through `set_input`, and are told about the output
+/// pins through the callback given to `on_change`, which is called with a port and the new
+/// levels of its pins whenever the levels the PPI drives on that port change (pins the PPI
+/// isn't driving count as 0). The pins keep their levels until they are set again, and start
+/// out high.
+///
+/// In mode 1 and mode 2, the strobe and acknowledge inputs on port C work as they do on the
+/// chip: pulling STB low latches the port's input pins and sets IBF, and pulling ACK low empties
+/// the output buffer (in mode 2, that is when port A drives its pins). Letting either line rise
+/// again raises INTR, if the program has enabled it by setting the corresponding bit of port C.
+/// `strobe` and `take` pulse those lines for a peripheral, and the INTR lines can request an
+/// interrupt through `connect`. Reading port C in those modes returns the handshake status,
+/// with the interrupt enables in place of STB and ACK.
+pub struct I8255 {
+    control: raw::u8,
+    /// The output latches of the three ports; the interrupt enables live in port C's.
+    latches: [raw::u8; 3],
+    pins: [raw::u8; 3],
+    /// The input latches of ports A and B.
+    held: [raw::u8; 2],
+    /// Whether each input buffer (IBF) or output buffer (OBF) of ports A and B is full.
+    input_full: [bool; 2],
+    output_full: [bool; 2],
+    /// Whether each port has raised INTR for its input and output handshakes.
+    input_ready: [bool; 2],
+    output_done: [bool; 2],
+    vectors: [Option<raw::u8>; 2],
+    shown: [raw::u8; 3],
+    listener: Option<Box<dyn FnMut(Port, raw::u8)>>,
+}
+
+impl core::fmt::Debug for I8255 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("I8255")
+            .field("control", &self.control)
+            .field("latches", &self.latches)
+            .field("pins", &self.pins)
+            .field("held", &self.held)
+            .field("input_full", &self.input_full)
+            .field("output_full", &self.output_full)
+            .field("input_ready", &self.input_ready)
+            .field("output_done", &self.output_done)
+            .field("vectors", &self.vectors)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for I8255 {
+    fn default() -> Self { Self::new() }
+}
+
+impl I8255 {
+    /// Creates a PPI with every port an input in mode 0, as at power-on.
+    pub fn new() -> Self {
+        Self {
+            control: 0x9B, latches: [0; 3], pins: [0xFF; 3], held: [0; 2], input_full: [false; 2],
+            output_full: [false; 2], input_ready: [false; 2], output_done: [false; 2], vectors: [None; 2],
+            shown: [0; 3], listener: None,
+        }
+    }
+
+    /// Sets the callback that is told when the levels the PPI drives on a port change.
+    pub fn on_change(&mut self, listener: impl FnMut(Port, raw::u8) + 'static) {
+        self.listener = Some(Box::new(listener));
+    }
+
+    /// Connects the INTR line of port A (PC3) or port B (PC0) to the interrupt that restarts
+    /// at `vector` (0 to 7), or disconnects it. Port C has no INTR line of its own.
+    pub fn connect(&mut self, port: Port, vector: Option<raw::u8>) {
+        if let Some(index) = Self::group(port) { self.vectors[index] = vector; }
+    }
+
+    /// Sets the levels the peripherals drive on a port's pins.
+    pub fn set_input(&mut self, port: Port, value: raw::u8) {
+        let index = port as usize;
+        let before = self.pins[index];
+        self.pins[index] = value;
+        if port == Port::C {
+            self.handshake(before, value);
+            self.notify();
+        }
+    }
+
+    /// Sets the input pins of port A or port B and pulses the port's STB line, as a peripheral
+    /// does to hand over a byte in mode 1 or mode 2.
+    pub fn strobe(&mut self, port: Port, value: raw::u8) {
+        let stb = match port { Port::A => STB_A, Port::B => STB_B, Port::C => return };
+        self.set_input(port, value);
+        self.set_input(Port::C, self.pins[2] | stb);
+        self.set_input(Port::C, self.pins[2] & !stb);
+        self.set_input(Port::C, self.pins[2] | stb);
+    }
+
+    /// Pulses the ACK line of port A or port B, as a peripheral does to take a byte in mode 1 or
+    /// mode 2. Returns the byte, if the program had written one that wasn't taken yet.
+    pub fn take(&mut self, port: Port) -> Option<raw::u8> {
+        let (index, ack) = match port { Port::A => (0, ACK_A), Port::B => (1, STB_B), Port::C => return None };
+        let value = self.output_full[index].then_some(self.latches[index]);
+        self.set_input(Port::C, self.pins[2] | ack);
+        self.set_input(Port::C, self.pins[2] & !ack);
+        self.set_input(Port::C, self.pins[2] | ack);
+        value
+    }
+
+    /// The levels the PPI drives on a port's pins; pins it isn't driving count as 0.
+    pub fn outputs(&self, port: Port) -> raw::u8 {
+        match port {
+            Port::A => match self.mode_a() {
+                2 => if self.pins[2] & ACK_A == 0 { self.latches[0] } else { 0 }
+                _ if self.control & 0x10 == 0 => self.latches[0],
+                _ => 0,
+            }
+            Port::B if self.control & 0x02 == 0 => self.latches[1],
+            Port::B => 0,
+            Port::C => {
+                let (signals, _) = self.handshake_bits();
+                self.latches[2] & self.general_outputs() | self.status() & signals
+            }
+        }
+    }
+
+    /// The handshake group that goes with a port.
+    fn group(port: Port) -> Option<usize> {
+        match port { Port::A => Some(0), Port::B => Some(1), Port::C => None }
+    }
+
+    /// The mode of group A: 0, 1 or 2.
+    fn mode_a(&self) -> raw::u8 {
+        match self.control >> 5 & 3 { 0 => 0, 1 => 1, _ => 2 }
+    }
+
+    fn mode_b(&self) -> raw::u8 { self.control >> 2 & 1 }
+
+    /// Whether the input and output handshakes of a group are in use.
+    fn handshakes(&self, index: usize) -> (bool, bool) {
+        match index {
+            0 => match self.mode_a() {
+                0 => (false, false),
+                1 => (self.control & 0x10 != 0, self.control & 0x10 == 0),
+                _ => (true, true),
+            }
+            _ => (self.mode_b() == 1 && self.control & 0x02 != 0, self.mode_b() == 1 && self.control & 0x02 == 0),
+        }
+    }
+
+    /// The port C bits that the handshakes drive, and those they read.
+    fn handshake_bits(&self) -> (raw::u8, raw::u8) {
+        let (mut signals, mut strobes) = (0, 0);
+        let (input, output) = self.handshakes(0);
+        if input { signals |= INTR_A | IBF_A; strobes |= STB_A; }
+        if output { signals |= INTR_A | OBF_A; strobes |= ACK_A; }
+        if self.mode_b() == 1 { signals |= INTR_B | IBF_B; strobes |= STB_B; }
+        (signals, strobes)
+    }
+
+    /// The port C bits that are outputs of mode 0.
+    fn general_outputs(&self) -> raw::u8 {
+        let (signals, strobes) = self.handshake_bits();
+        let mut outputs = 0;
+        if self.control & 0x08 == 0 { outputs |= 0xF0; }
+        if self.control & 0x01 == 0 { outputs |= 0x0F; }
+        outputs & !(signals | strobes)
+    }
+
+    /// Whether the program has enabled a group's INTR for its input and output handshakes.
+    fn enabled(&self, index: usize) -> (bool, bool) {
+        let c = self.latches[2];
+        match index {
+            0 => (c & STB_A != 0, c & ACK_A != 0),
+            _ => (c & STB_B != 0, c & STB_B != 0),
+        }
+    }
+
+    fn intr(&self, index: usize) -> bool {
+        let (input, output) = self.handshakes(index);
+        let (input_enabled, output_enabled) = self.enabled(index);
+        input && input_enabled && self.input_ready[index] || output && output_enabled && self.output_done[index]
+    }
+
+    /// The handshake signals, at their places in port C. OBF is active low.
+    fn status(&self) -> raw::u8 {
+        let mut status = 0;
+        if self.intr(0) { status |= INTR_A; }
+        if self.input_full[0] { status |= IBF_A; }
+        if !self.output_full[0] { status |= OBF_A; }
+        if self.intr(1) { status |= INTR_B; }
+        if match self.handshakes(1) { (_, true) => !self.output_full[1], _ => self.input_full[1] } {
+            status |= IBF_B;
+        }
+        status
+    }
+
+    /// Follows the STB and ACK lines as the peripherals change port C.
+    fn handshake(&mut self, before: raw::u8, after: raw::u8) {
+        let (_, strobes) = self.handshake_bits();
+        for (index, stb, ack) in [(0, STB_A, ACK_A), (1, STB_B, STB_B)] {
+            let (input, output) = self.handshakes(index);
+            if input && strobes & stb != 0 {
+                if before & stb != 0 && after & stb == 0 {
+                    self.held[index] = self.pins[index];
+                    self.input_full[index] = true;
+                } else if before & stb == 0 && after & stb != 0 && self.input_full[index] {
+                    self.input_ready[index] = true;
+                }
+            }
+            if output && strobes & ack != 0 {
+                if before & ack != 0 && after & ack == 0 {
+                    self.output_full[index] = false;
+                } else if before & ack == 0 && after & ack != 0 && !self.output_full[index] {
+                    self.output_done[index] = true;
+                }
+            }
+        }
+    }
+
+    /// Calls the callback for every port whose output levels have changed.
+    fn notify(&mut self) {
+        for port in [Port::A, Port::B, Port::C] {
+            let value = self.outputs(port);
+            if core::mem::replace(&mut self.shown[port as usize], value) != value {
+                if let Some(listener) = &mut self.listener { listener(port, value); }
+            }
+        }
+    }
+
+    fn read_port(&mut self, index: usize) -> raw::u8 {
+        let input = match index {
+            0 => self.mode_a() == 2 || self.control & 0x10 != 0,
+            _ => self.control & 0x02 != 0,
+        };
+        if !input { return self.latches[index]; }
+        if !self.handshakes(index).0 { return self.pins[index]; }
+        self.input_full[index] = false;
+        self.input_ready[index] = false;
+        self.held[index]
+    }
+
+    fn read_c(&self) -> raw::u8 {
+        let (signals, strobes) = self.handshake_bits();
+        let outputs = self.general_outputs();
+        let inputs = !(signals | strobes | outputs);
+        self.latches[2] & (outputs | strobes) | self.pins[2] & inputs | self.status() & signals
+    }
+}
+
+impl Device for I8255 {
+    fn read(&mut self, register: raw::u8) -> raw::u8 {
+        let value = match register & 3 {
+            3 => 0xFF,
+            2 => self.read_c(),
+            port => self.read_port(port as usize),
+        };
+        self.notify();
+        value
+    }
+
+    fn write(&mut self, register: raw::u8, value: raw::u8) {
+        match register & 3 {
+            3 if value & 0x80 != 0 => {
+                self.control = value;
+                self.latches = [0; 3];
+                self.input_full = [false; 2];
+                self.output_full = [false; 2];
+                self.input_ready = [false; 2];
+                self.output_done = [false; 2];
+            }
+            3 => {
+                let bit = 1 << (value >> 1 & 7);
+                self.latches[2] = if value & 1 != 0 { self.latches[2] | bit } else { self.latches[2] & !bit };
+            }
+            2 => {
+                let outputs = self.general_outputs();
+                self.latches[2] = self.latches[2] & !outputs | value & outputs;
+            }
+            port => {
+                let index = port as usize;
+                self.latches[index] = value;
+                if self.handshakes(index).1 {
+                    self.output_full[index] = true;
+                    self.output_done[index] = false;
+                }
+            }
+        }
+        self.notify();
+    }
+
+    fn reset(&mut self) {
+        let listener = self.listener.take();
+        *self = Self { pins: self.pins, vectors: self.vectors, listener, ..Self::new() };
+        self.notify();
+    }
+
+    fn request(&self) -> Option<Vector> {
+        (0..2).filter(|&index| self.intr(index)).find_map(|index| self.vectors[index]).map(Vector::Restart)
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.control.save(out);
+        [self.latches, self.pins].save(out);
+        self.held.save(out);
+        [self.input_full, self.output_full, self.input_ready, self.output_done].save(out);
+    }
+
+    /// Restores the device, then tells the callback about any pins whose levels it changed.
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        self.control.restore(data)?;
+        let mut ports = [[0; 3]; 2];
+        ports.restore(data)?;
+        [self.latches, self.pins] = ports;
+        self.held.restore(data)?;
+        let mut flags = [[false; 2]; 4];
+        flags.restore(data)?;
+        [self.input_full, self.output_full, self.input_ready, self.output_done] = flags;
+        self.notify();
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+//! The Intel 8259 programmable interrupt controller, in its 8080 mode.
+
+use crate::prelude::{*, vec::Vec};
+use crate::snapshot::{self, Snapshot};
+use super::{Device, Vector};
+
+/// Bits of the first initialization command word.
+const NEEDS_ICW4: raw::u8 = 0x01;
+const SINGLE: raw::u8 = 0x02;
+const INTERVAL_4: raw::u8 = 0x04;
+const LEVEL_TRIGGERED: raw::u8 = 0x08;
+/// Bit of the fourth initialization command word that ends interrupts as they are acknowledged.
+const AUTO_EOI: raw::u8 = 0x02;
+
+/// Which initialization command word the controller expects next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Init {
+    Needed,
+    Icw2,
+    Icw3,
+    Icw4,
+    Done,
+}
+
+/// An 8259 programmable interrupt controller. Register 0 takes ICW1, OCW2 and OCW3 and reads
+/// the request, in-service or poll register; register 1 takes the other command words and
+/// reads the interrupt mask. It is usually attached with a mask of 0xFE.
+///
+/// The interrupt sources drive its eight request lines through `set_line`. Once it has been
+/// initialized, it requests an interrupt whenever an unmasked request outranks every interrupt
+/// in service, supplying a `CALL` to the routine for that level, and marks the interrupt as in
+/// service when the processor accepts it. Priorities can be fixed or rotating, and special
+/// mask mode, automatic EOI and polling are supported; cascading is not, so the controller is
+/// always a single master (ICW3 is accepted and ignored), and 8086 mode is ignored too.
+#[derive(Debug, Clone)]
+pub struct I8259 {
+    init: Init,
+    icw1: raw::u8,
+    base: raw::u8,
+    icw4: raw::u8,
+    lines: raw::u8,
+    requests: raw::u8,
+    service: raw::u8,
+    mask: raw::u8,
+    /// The level with the lowest priority; the next level up has the highest.
+    lowest: raw::u8,
+    read_service: bool,
+    poll: bool,
+    special_mask: bool,
+    rotate_automatically: bool,
+}
+
+impl Default for I8259 {
+    fn default() -> Self { Self::new() }
+}
+
+impl I8259 {
+    /// Creates a controller that is waiting to be initialized.
+    pub fn new() -> Self {
+        Self {
+            init: Init::Needed, icw1: 0, base: 0, icw4: 0, lines: 0, requests: 0, service: 0, mask: 0,
+            lowest: 7, read_service: false, poll: false, special_mask: false, rotate_automatically: false,
+        }
+    }
+
+    /// Sets the level of a request line (0 to 7). In edge-triggered mode a rising line records a
+    /// request, which lasts until it is acknowledged; in level-triggered mode a request lasts as
+    /// long as the line is high. Panics if `line` is 8 or more.
+    pub fn set_line(&mut self, line: usize, high: bool) {
+        assert!(line < 8, "the 8259 has request lines 0 to 7, not {line}");
+        let bit = 1 << line;
+        let rising = high && self.lines & bit == 0;
+        self.lines = if high { self.lines | bit } else { self.lines & !bit };
+        if self.icw1 & LEVEL_TRIGGERED != 0 {
+            self.requests = (self.requests & !bit) | (self.lines & bit);
+        } else if rising {
+            self.requests |= bit;
+        }
+    }
+
+    /// The levels in `bits`, from the highest priority to the lowest.
+    fn ranked(&self, bits: raw::u8) -> impl Iterator<Item = raw::u8> {
+        let lowest = self.lowest;
+        (1..=8).map(move |step| (lowest + step) % 8).filter(move |level| bits & 1 << level != 0)
+    }
+
+    /// The level that would interrupt the processor now, if any.
+    fn pending(&self) -> Option<raw::u8> {
+        if self.init != Init::Done { return None; }
+        let candidates = self.requests & !self.mask;
+        if self.special_mask {
+            return self.ranked(candidates & !self.service).next();
+        }
+        let highest = self.ranked(candidates | self.service).next()?;
+        (self.service & 1 << highest == 0).then_some(highest)
+    }
+
+    /// Marks a level as in service, as the processor acknowledges it.
+    fn accept(&mut self, level: raw::u8) {
+        if self.icw1 & LEVEL_TRIGGERED == 0 { self.requests &= !(1 << level); }
+        if self.icw4 & AUTO_EOI != 0 {
+            if self.rotate_automatically { self.lowest = level; }
+        } else {
+            self.service |= 1 << level;
+        }
+    }
+
+    /// The address of the routine for a level.
+    fn address(&self, level: raw::u8) -> raw::u16 {
+        let low = match self.icw1 & INTERVAL_4 {
+            0 => self.icw1 & 0xC0 | level << 3,
+            _ => self.icw1 & 0xE0 | level << 2,
+        };
+        raw::u16::from_le_bytes([low, self.base])
+    }
+
+    /// Carries out the EOI and rotation commands of OCW2.
+    fn command(&mut self, value: raw::u8) {
+        let level = value & 7;
+        let highest = self.ranked(self.service).next();
+        match value >> 5 {
+            0b001 => if let Some(level) = highest { self.service &= !(1 << level); }
+            0b011 => self.service &= !(1 << level),
+            0b101 => if let Some(level) = highest {
+                self.service &= !(1 << level);
+                self.lowest = level;
+            }
+            0b100 => self.rotate_automatically = true,
+            0b000 => self.rotate_automatically = false,
+            0b111 => {
+                self.service &= !(1 << level);
+                self.lowest = level;
+            }
+            0b110 => self.lowest = level,
+            _ => (),
+        }
+    }
+}
+
+impl Device for I8259 {
+    fn read(&mut self, register: raw::u8) -> raw::u8 {
+        match register & 1 {
+            0 if core::mem::take(&mut self.poll) => match self.pending() {
+                Some(level) => {
+                    self.accept(level);
+                    0x80 | level
+                }
+                None => 0,
+            }
+            0 if self.read_service => self.service,
+            0 => self.requests,
+            _ => self.mask,
+        }
+    }
+
+    fn write(&mut self, register: raw::u8, value: raw::u8) {
+        match (register & 1, self.init) {
+            (0, _) if value & 0x10 != 0 => {
+                *self = Self { lines: self.lines, icw1: value, init: Init::Icw2, ..Self::new() };
+                if value & LEVEL_TRIGGERED != 0 { self.requests = self.lines; }
+            }
+            (0, _) if value & 0x08 != 0 => {
+                if value & 0x02 != 0 { self.read_service = value & 0x01 != 0; }
+                if value & 0x40 != 0 { self.special_mask = value & 0x20 != 0; }
+                self.poll = value & 0x04 != 0;
+            }
+            (0, _) => self.command(value),
+            (_, Init::Icw2) => {
+                self.base = value;
+                self.init = match self.icw1 {
+                    icw1 if icw1 & SINGLE == 0 => Init::Icw3,
+                    icw1 if icw1 & NEEDS_ICW4 != 0 => Init::Icw4,
+                    _ => Init::Done,
+                };
+            }
+            (_, Init::Icw3) => self.init = if self.icw1 & NEEDS_ICW4 != 0 { Init::Icw4 } else { Init::Done },
+            (_, Init::Icw4) => {
+                self.icw4 = value;
+                self.init = Init::Done;
+            }
+            (_, _) => self.mask = value,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self { lines: self.lines, ..Self::new() };
+    }
+
+    fn request(&self) -> Option<Vector> {
+        self.pending().map(|level| Vector::Call(self.address(level)))
+    }
+
+    fn acknowledge(&mut self) {
+        if let Some(level) = self.pending() { self.accept(level); }
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        [self.init as raw::u8, self.icw1, self.base, self.icw4, self.lines, self.requests, self.service, self.mask, self.lowest].save(out);
+        [self.read_service, self.poll, self.special_mask, self.rotate_automatically].save(out);
+    }
+
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        let (mut bytes, mut bits) = ([0; 9], [false; 4]);
+        bytes.restore(data)?;
+        bits.restore(data)?;
+        let [init, icw1, base, icw4, lines, requests, service, mask, lowest] = bytes;
+        self.init = match init {
+            0 => Init::Needed,
+            1 => Init::Icw2,
+            2 => Init::Icw3,
+            3 => Init::Icw4,
+            4 => Init::Done,
+            _ => return Err(snapshot::Error::Mismatch),
+        };
+        (self.icw1, self.base, self.icw4, self.lines, self.requests, self.service, self.mask, self.lowest) =
+            (icw1, base, icw4, lines, requests, service, mask, lowest & 7);
+        [self.read_service, self.poll, self.special_mask, self.rotate_automatically] = bits;
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! The Motorola MC6850 ACIA, with its serial line connected to host streams.
+
+use crate::prelude::{*, vec::Vec};
+use crate::snapshot::{self, Snapshot};
+use super::{Device, Vector};
+extern crate std;
+use std::io::{Read, Write};
+
+/// Status register bits.
+const RX_FULL: raw::u8 = 0x01;
+const TX_EMPTY: raw::u8 = 0x02;
+const OVERRUN: raw::u8 = 0x20;
+const IRQ: raw::u8 = 0x80;
+
+/// Control register bits.
+const MASTER_RESET: raw::u8 = 0x03;
+const TRANSMIT: raw::u8 = 0x60;
+const TX_INTERRUPT: raw::u8 = 0x20;
+const RX_INTERRUPT: raw::u8 = 0x80;
+
+/// An MC6850 ACIA, as used on the MITS 88-2SIO. Register 0 is the control register when
+/// written and the status register when read, and register 1 is the data register, so it is
+/// usually attached with a mask of 0xFE.
+///
+/// The chip starts out held in master reset, as a program finds it at power-on, and does
+/// nothing until a control word other than master reset is written.
+///
+/// Characters written by the program are written to `output` once they have been shifted out
+/// of the transmitter, and characters for the receiver are read from `input` one at a time and
+/// arrive once they have been shifted in; an input that has nothing to read (it returns no
+/// bytes or an error) leaves the line idle. Shifting takes as long as the character would on a
+/// real line, at `clock` processor cycles for each cycle of the transmit and receive clocks and
+/// the divide ratio set by the control word. The line never garbles a character and the modem
+/// lines are always ready, so the only error is an overrun. The IRQ output can request an
+/// interrupt through `connect`.
+///
+/// A snapshot keeps the chip's registers and the characters in flight, but not the streams.
+#[derive(Debug)]
+pub struct Mc6850<R: Read, W: Write> {
+    input: R,
+    output: W,
+    clock: usize,
+    control: raw::u8,
+    buffer: Option<raw::u8>,
+    shifting: Option<(raw::u8, usize)>,
+    received: Option<raw::u8>,
+    incoming: Option<(raw::u8, usize)>,
+    overrun: bool,
+    vector: Option<raw::u8>,
+}
+
+impl<R: Read, W: Write> Mc6850<R, W> {
+    /// Creates an ACIA whose transmit and receive clocks run once every `clock` processor cycles.
+    pub fn new(input: R, output: W, clock: usize) -> Self {
+        Self {
+            input, output, clock, control: MASTER_RESET, buffer: None, shifting: None, received: None,
+            incoming: None, overrun: false, vector: None,
+        }
+    }
+
+    /// The streams behind the serial line.
+    pub fn get_mut(&mut self) -> (&mut R, &mut W) { (&mut self.input, &mut self.output) }
+
+    /// Unwraps the streams behind the serial line.
+    pub fn into_inner(self) -> (R, W) { (self.input, self.output) }
+
+    /// Connects the IRQ output to the interrupt that restarts at `vector` (0 to 7), or
+    /// disconnects it.
+    pub fn connect(&mut self, vector: Option<raw::u8>) { self.vector = vector; }
+
+    /// Whether the program has set the RTS output, which is active low on the chip.
+    pub fn request_to_send(&self) -> bool { self.control & TRANSMIT != 0x40 }
+
+    fn resetting(&self) -> bool { self.control & MASTER_RESET == MASTER_RESET }
+
+    /// The number of data bits in a character.
+    fn length(&self) -> u32 { if self.control & 0x10 != 0 { 8 } else { 7 } }
+
+    /// The number of processor cycles a character takes on the line.
+    fn character(&self) -> usize {
+        let (parity, stop) = match self.control >> 2 & 7 {
+            0 | 1 => (1, 2),
+            2 | 3 => (1, 1),
+            4 => (0, 2),
+            5 => (0, 1),
+            _ => (1, 1),
+        };
+        let factor = [1, 16, 64][(self.control & 3).min(2) as usize];
+        (self.clock * factor * (1 + self.length() as usize + parity + stop)).max(1)
+    }
+
+    fn mask(&self, value: raw::u8) -> raw::u8 { value & (0xFF >> (8 - self.length())) }
+
+    /// Starts shifting out the buffered character, if the transmitter is free to.
+    fn load(&mut self) {
+        if self.shifting.is_none() && !self.resetting() {
+            if let Some(value) = self.buffer.take() {
+                self.shifting = Some((value, self.character()));
+            }
+        }
+    }
+
+    fn transmit(&mut self, mut cycles: usize) {
+        self.load();
+        while let Some((value, left)) = self.shifting {
+            if left > cycles {
+                self.shifting = Some((value, left - cycles));
+                return;
+            }
+            cycles -= left;
+            self.shifting = None;
+            let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+            self.load();
+        }
+    }
+
+    fn receive(&mut self, mut cycles: usize) {
+        loop {
+            if self.resetting() { return; }
+            if self.incoming.is_none() {
+                let mut byte = [0];
+                if !matches!(self.input.read(&mut byte), Ok(1)) { return; }
+                self.incoming = Some((self.mask(byte[0]), self.character()));
+            }
+            let Some((value, left)) = self.incoming else { return };
+            if left > cycles {
+                self.incoming = Some((value, left - cycles));
+                return;
+            }
+            cycles -= left;
+            self.incoming = None;
+            match self.received {
+                Some(_) => self.overrun = true,
+                None => self.received = Some(value),
+            }
+        }
+    }
+
+    fn interrupting(&self) -> bool {
+        self.control & RX_INTERRUPT != 0 && (self.received.is_some() || self.overrun)
+            || self.control & TRANSMIT == TX_INTERRUPT && self.buffer.is_none()
+    }
+
+    fn status(&self) -> raw::u8 {
+        if self.resetting() { return 0; }
+        let mut status = 0;
+        if self.received.is_some() { status |= RX_FULL; }
+        if self.buffer.is_none() { status |= TX_EMPTY; }
+        if self.overrun { status |= OVERRUN; }
+        if self.interrupting() { status |= IRQ; }
+        status
+    }
+}
+
+impl<R: Read, W: Write> Device for Mc6850<R, W> {
+    fn read(&mut self, register: raw::u8) -> raw::u8 {
+        match register & 1 {
+            0 => self.status(),
+            _ => {
+                self.overrun = false;
+                self.received.take().unwrap_or(0)
+            }
+        }
+    }
+
+    fn write(&mut self, register: raw::u8, value: raw::u8) {
+        match register & 1 {
+            0 => {
+                self.control = value;
+                if self.resetting() { self.reset(); } else { self.load(); }
+            }
+            _ if self.resetting() => (),
+            _ => {
+                self.buffer = Some(self.mask(value));
+                self.load();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.control = MASTER_RESET;
+        self.buffer = None;
+        self.shifting = None;
+        self.received = None;
+        self.incoming = None;
+        self.overrun = false;
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.transmit(cycles);
+        self.receive(cycles);
+    }
+
+    fn request(&self) -> Option<Vector> {
+        self.vector.filter(|_| !self.resetting() && self.interrupting()).map(Vector::Restart)
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.control.save(out);
+        [self.buffer, self.received].save(out);
+        [self.shifting, self.incoming].save(out);
+        self.overrun.save(out);
+    }
+
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        self.control.restore(data)?;
+        let (mut held, mut moving) = ([None; 2], [None; 2]);
+        held.restore(data)?;
+        moving.restore(data)?;
+        [self.buffer, self.received] = held;
+        [self.shifting, self.incoming] = moving;
+        self.overrun.restore(data)
+    }
+}
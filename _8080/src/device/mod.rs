@@ -0,0 +1,246 @@
+//! Peripheral chips that sit on the 8080's I/O ports, and a bus that connects them to a board.
+//!
+//! A `Device` answers reads and writes to its own registers, numbered from 0, without knowing
+//! which ports it was wired to. A `PortBus` wraps any Harness (usually a `MemoryMap`) and
+//! decodes port numbers for it: each device is attached at a port and a mask, and a port
+//! reaches the device when it matches the attached port in every bit of the mask; the bits
+//! outside the mask choose the register. Ports that no device claims go to the wrapped Harness.
+//!
+//! Devices count time only when the bus is ticked. `Machine::clock_devices` does that from the
+//! machine's cycle clock as the run loops go, and passes on the interrupts the devices request;
+//! a front end that calls `execute` itself can instead call `tick` and `service` after each
+//! instruction.
+//!
+//! Devices are attached by value, so a board that needs to reach a device afterwards (to feed
+//! it input from the host, say) can attach an `Rc<RefCell<_>>` and keep a clone.
+
+use crate::prelude::{*, vec::Vec};
+use core::cell::RefCell;
+use crate::rc::Rc;
+use crate::{run::Event, snapshot::{self, Snapshot}};
+
+#[cfg(feature="std")]
+pub mod i8251;
+#[cfg(feature="std")]
+pub use i8251::I8251;
+pub mod i8253;
+pub use i8253::I8253;
+pub mod i8255;
+pub use i8255::I8255;
+pub mod i8259;
+pub use i8259::I8259;
+#[cfg(feature="std")]
+pub mod mc6850;
+#[cfg(feature="std")]
+pub use mc6850::Mc6850;
+
+/// The instruction a device supplies to the processor when its interrupt is accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vector {
+    /// An `RST` instruction, which calls the address 8 times the number (0 to 7).
+    Restart(raw::u8),
+    /// A `CALL` instruction to an address, as an 8259 supplies in 8080 mode.
+    Call(raw::u16),
+}
+
+/// A peripheral with registers on the I/O ports.
+pub trait Device {
+    /// Reads one of the device's registers.
+    fn read(&mut self, register: raw::u8) -> raw::u8;
+
+    /// Writes one of the device's registers.
+    fn write(&mut self, register: raw::u8, value: raw::u8);
+
+    /// Returns the device to the state it has at power-on. The default does nothing.
+    fn reset(&mut self) {}
+
+    /// Advances the device by a number of processor clock cycles, for devices that do things
+    /// over time. The default does nothing.
+    fn tick(&mut self, cycles: usize) { let _ = cycles; }
+
+    /// The interrupt the device is requesting, if any. The default requests none.
+    fn request(&self) -> Option<Vector> { None }
+
+    /// Tells the device that the processor has accepted the interrupt it requested. The
+    /// default does nothing.
+    fn acknowledge(&mut self) {}
+
+    /// Appends the device's state to `out`, for a snapshot of the bus it is attached to. How
+    /// it is wired (its interrupt vector, say) isn't part of its state. The default saves
+    /// nothing, for devices with no state worth keeping.
+    fn save(&self, out: &mut Vec<raw::u8>) { let _ = out; }
+
+    /// Restores the state that `save` appended from the start of `data`, and moves `data`
+    /// past it. The default takes nothing.
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> { let _ = data; Ok(()) }
+}
+
+impl<D: Device + ?Sized> Device for Box<D> {
+    fn read(&mut self, register: raw::u8) -> raw::u8 { (**self).read(register) }
+    fn write(&mut self, register: raw::u8, value: raw::u8) { (**self).write(register, value) }
+    fn reset(&mut self) { (**self).reset() }
+    fn tick(&mut self, cycles: usize) { (**self).tick(cycles) }
+    fn request(&self) -> Option<Vector> { (**self).request() }
+    fn acknowledge(&mut self) { (**self).acknowledge() }
+    fn save(&self, out: &mut Vec<raw::u8>) { (**self).save(out) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> { (**self).restore(data) }
+}
+
+impl<D: Device + ?Sized> Device for Rc<RefCell<D>> {
+    fn read(&mut self, register: raw::u8) -> raw::u8 { (**self).borrow_mut().read(register) }
+    fn write(&mut self, register: raw::u8, value: raw::u8) { (**self).borrow_mut().write(register, value) }
+    fn reset(&mut self) { (**self).borrow_mut().reset() }
+    fn tick(&mut self, cycles: usize) { (**self).borrow_mut().tick(cycles) }
+    fn request(&self) -> Option<Vector> { RefCell::borrow(self).request() }
+    fn acknowledge(&mut self) { (**self).borrow_mut().acknowledge() }
+    fn save(&self, out: &mut Vec<raw::u8>) { RefCell::borrow(self).save(out) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> { (**self).borrow_mut().restore(data) }
+}
+
+/// A device along with the ports that reach it.
+struct Slot {
+    port: raw::u8,
+    mask: raw::u8,
+    device: Box<dyn Device>,
+}
+
+/// A Harness that adds devices on the I/O ports to another Harness, which still handles
+/// memory and any ports that no device claims.
+pub struct PortBus<H: Harness> {
+    board: H,
+    slots: Vec<Slot>,
+}
+
+impl<H: Harness> PortBus<H> {
+    /// Wraps a board, with no devices attached yet.
+    pub fn new(board: H) -> Self { Self { board, slots: Vec::new() } }
+
+    /// Attaches a device to the ports that match `port` in the bits set in `mask`; the bits
+    /// outside the mask choose the register. A device attached later takes the ports it shares
+    /// with one attached earlier.
+    pub fn attach(mut self, port: raw::u8, mask: raw::u8, device: impl Device + 'static) -> Self {
+        self.slots.push(Slot { port: port & mask, mask, device: Box::new(device) });
+        self
+    }
+
+    /// The device claiming a port, and the register the port chooses.
+    fn decode(&mut self, port: raw::u8) -> Option<(&mut dyn Device, raw::u8)> {
+        let slot = self.slots.iter_mut().rev().find(|slot| port & slot.mask == slot.port)?;
+        Some((&mut *slot.device, port & !slot.mask))
+    }
+
+    /// Resets every device.
+    pub fn reset(&mut self) {
+        for slot in &mut self.slots { slot.device.reset(); }
+    }
+
+    /// Advances every device by a number of processor clock cycles.
+    pub fn tick(&mut self, cycles: usize) {
+        for slot in &mut self.slots { slot.device.tick(cycles); }
+    }
+
+    /// The first device, in the order they were attached, that is requesting an interrupt, along
+    /// with the vector it requests.
+    fn requesting(&self) -> Option<(usize, Vector)> {
+        self.slots.iter().enumerate().find_map(|(index, slot)| Some((index, slot.device.request()?)))
+    }
+
+    /// The vector of the first interrupt requested by a device, if any.
+    pub fn request(&self) -> Option<Vector> { self.requesting().map(|(_, vector)| vector) }
+
+    /// Tells the device whose interrupt `request` returned that the processor has accepted it.
+    pub fn acknowledge(&mut self) {
+        if let Some((index, _)) = self.requesting() { self.slots[index].device.acknowledge(); }
+    }
+
+    /// Unwraps the board.
+    pub fn into_inner(self) -> H { self.board }
+}
+
+/// Saves the wrapped board and then each device, in the order they were attached.
+impl<H: Harness + Snapshot> Snapshot for PortBus<H> {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.board.save(out);
+        for slot in &self.slots { slot.device.save(out); }
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        self.board.restore(data)?;
+        self.slots.iter_mut().try_for_each(|slot| slot.device.restore(data))
+    }
+}
+
+impl<H: Harness, C: BorrowMut<PortBus<H>>> Machine<PortBus<H>, C> {
+    /// Passes the first interrupt requested by a device on the bus to the processor. If the
+    /// processor accepts it, the device is told so and the method returns `true`; otherwise
+    /// the request stays in place to try again later.
+    pub fn service(&mut self) -> bool {
+        let Some(vector) = self.request() else { return false };
+        let accepted = self.deliver(vector);
+        if accepted { self.acknowledge(); }
+        accepted
+    }
+
+    /// Clocks the devices on the bus from the machine's clock, so that the run loops keep them
+    /// going: every `period` cycles, a scheduled event ticks them by the cycles that have
+    /// passed since it last ran and then calls `service`. A shorter period delivers their
+    /// interrupts sooner. Cancelling the returned event stops the clocking.
+    pub fn clock_devices(&mut self, period: u64) -> Event { self.clock_bus(period, |bus| bus) }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Schedules the event behind `clock_devices`, for a bus that `bus` finds in the Harness.
+    pub(crate) fn clock_bus<B: Harness>(&mut self, period: u64, bus: impl Fn(&mut H) -> &mut PortBus<B> + Send + 'static) -> Event {
+        let mut last = self.clock;
+        self.schedule_every(period, move |machine| {
+            let now = machine.clock();
+            bus(machine).tick(now.saturating_sub(last) as usize);
+            last = now;
+            let Some(vector) = bus(machine).request() else { return };
+            if machine.deliver(vector) { bus(machine).acknowledge(); }
+        })
+    }
+
+    /// Passes an interrupt vector to the processor, returning whether it was accepted.
+    pub(crate) fn deliver(&mut self, vector: Vector) -> bool {
+        match vector {
+            Vector::Restart(index) => matches!(self.reset_to(index as usize), Ok(true)),
+            Vector::Call(address) => self.call_to(address),
+        }
+    }
+}
+
+impl<H: Harness> Deref for PortBus<H> {
+    type Target = H;
+    fn deref(&self) -> &Self::Target { &self.board }
+}
+
+impl<H: Harness> DerefMut for PortBus<H> {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.board }
+}
+
+impl<H: Harness> Harness for PortBus<H> {
+    fn read(&self, from: u16) -> u8 { self.board.read(from) }
+    fn read_word(&self, from: u16) -> u16 { self.board.read_word(from) }
+    fn write(&mut self, to: u16, value: u8) { self.board.write(to, value) }
+    fn write_word(&mut self, to: u16, value: u16) { self.board.write_word(to, value) }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        match self.decode(port) {
+            Some((device, register)) => Wrapping(device.read(register)),
+            None => self.board.input(port),
+        }
+    }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        match self.decode(port) {
+            Some((device, register)) => device.write(register, value.0),
+            None => self.board.output(port, value),
+        }
+    }
+    fn bus_cycle(&mut self, cycle: crate::BusCycle) -> raw::u8 { self.board.bus_cycle(cycle) }
+    #[cfg(feature="open")]
+    fn did_execute(&mut self, client: &crate::chip::State, did: crate::chip::opcode::Op) -> Result<Option<crate::chip::opcode::Op>, String> {
+        self.board.did_execute(client, did)
+    }
+}
+
+#[cfg(test)]
+mod tests;
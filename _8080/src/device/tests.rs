@@ -0,0 +1,416 @@
+use super::*;
+use crate::MemoryMap;
+
+/// A device with four registers that counts its ticks into the last one.
+#[derive(Default)]
+struct Registers([raw::u8; 4]);
+
+impl Device for Registers {
+    fn read(&mut self, register: raw::u8) -> raw::u8 { self.0[register as usize] }
+    fn write(&mut self, register: raw::u8, value: raw::u8) { self.0[register as usize] = value; }
+    fn reset(&mut self) { self.0 = [0; 4]; }
+    fn tick(&mut self, cycles: usize) { self.0[3] = self.0[3].wrapping_add(cycles as raw::u8); }
+}
+
+#[test]
+fn decoding() {
+    let shared = Rc::new(RefCell::new(Registers::default()));
+    let mut bus = PortBus::new(MemoryMap::new().ram(..).banked(0x8000..0x9000, 2, Some(0x07)))
+        .attach(0x10, 0xFC, Registers::default())
+        .attach(0x20, 0xFC, shared.clone())
+        .attach(0x13, 0xFF, Box::new(Registers::default()));
+    bus.output(0x11, Wrapping(5));
+    bus.output(0x23, Wrapping(6));
+    bus.output(0x13, Wrapping(7));
+    assert_eq!(bus.input(0x11).0, 5);
+    assert_eq!(bus.input(0x13).0, 7);
+    assert_eq!(bus.input(0x12).0, 0);
+    assert_eq!(RefCell::borrow(&shared).0, [0, 0, 0, 6]);
+    assert_eq!(bus.input(0x30).0, 0xFF);
+
+    bus.output(0x07, Wrapping(1));
+    assert_eq!(bus.bank(0x8000), Some(1));
+    bus.write(Wrapping(0x8000), Wrapping(9));
+    assert_eq!(bus.read(Wrapping(0x8000)).0, 9);
+
+    bus.tick(3);
+    assert_eq!(bus.input(0x23).0, 9);
+    bus.reset();
+    assert_eq!([bus.input(0x11).0, bus.input(0x23).0], [0, 0]);
+    assert_eq!(bus.into_inner().bank(0x8000), Some(1));
+}
+
+#[test]
+fn machine() {
+    let mut machine = Machine::new(PortBus::new(MemoryMap::new().ram(..)).attach(0x40, 0xFE, Registers::default()));
+    machine.program(0x0000, &[0x3E, 0x2A, 0xD3, 0x41, 0xDB, 0x41, 0x3C, 0xD3, 0x40, 0xDB, 0x40, 0x32, 0x00, 0x10, 0x76]);
+    for _ in 0..7 { let _ = machine.execute(); }
+    assert_eq!(machine.read(Wrapping(0x1000)).0, 0x2B);
+}
+
+#[cfg(feature="std")]
+#[test]
+fn usart() {
+    let mut usart = I8251::new(&b"xyz"[..], Vec::new(), 1);
+    usart.write(1, 0x4E);
+    usart.write(1, 0x37);
+    assert!(usart.data_terminal_ready() && usart.request_to_send());
+    assert_eq!(usart.read(1), 0x85);
+    usart.write(0, b'A');
+    assert_eq!(usart.read(1), 0x81);
+    usart.write(0, b'B');
+    assert_eq!(usart.read(1), 0x80);
+    usart.tick(159);
+    assert!(usart.get_mut().1.is_empty());
+    usart.tick(1);
+    assert_eq!(usart.get_mut().1, b"A");
+    assert_eq!(usart.read(1), 0x83);
+    assert_eq!(usart.read(0), b'x');
+    usart.tick(160);
+    assert_eq!(usart.read(1), 0x87);
+    usart.tick(160);
+    assert_eq!(usart.read(1), 0x97);
+    assert_eq!(usart.read(0), b'z');
+    usart.write(1, 0x15);
+    assert_eq!(usart.read(1), 0x85);
+
+    usart.write(1, 0x40);
+    usart.write(1, 0x49);
+    usart.write(1, 0x01);
+    usart.write(0, 0xFF);
+    usart.tick(8);
+    assert_eq!(usart.get_mut().1, b"AB");
+    usart.tick(1);
+    assert_eq!(usart.into_inner().1, b"AB\x7F");
+}
+
+#[cfg(feature="std")]
+#[test]
+fn usart_sync() {
+    let mut usart = I8251::new(&[0x55, 0x16, 0x55, 0x16, 0x16, b'Q'][..], Vec::new(), 1);
+    usart.write(1, 0x0C);
+    usart.write(1, 0x16);
+    usart.write(1, 0x16);
+    usart.write(1, 0x84);
+    usart.tick(8 * 5);
+    assert_eq!(usart.read(1), 0xC5);
+    assert_eq!(usart.read(1), 0x85);
+    usart.tick(8);
+    assert_eq!(usart.read(1), 0x87);
+    assert_eq!(usart.read(0), b'Q');
+    usart.reset();
+    usart.write(1, 0xCC);
+    usart.write(1, 0x16);
+    usart.write(1, 0x84);
+    usart.set_data_set_ready(false);
+    assert_eq!(usart.read(1), 0x05);
+}
+
+/// The levels of a counter's OUT line over a number of clocks.
+fn outs(timer: &mut I8253, counter: usize, clocks: usize) -> Vec<bool> {
+    (0..clocks).map(|_| { timer.tick(2); timer.out(counter) }).collect()
+}
+
+#[test]
+fn timer_modes() {
+    let mut timer = I8253::new(2);
+    timer.write(3, 0x30);
+    assert!(!timer.out(0));
+    timer.write(0, 5);
+    timer.write(0, 0);
+    assert_eq!(outs(&mut timer, 0, 6), [false, false, false, false, false, true]);
+    timer.tick(4);
+    timer.write(3, 0x00);
+    timer.tick(2);
+    assert_eq!([timer.read(0), timer.read(0), timer.read(0)], [0xFE, 0xFF, 0xFD]);
+
+    timer.write(3, 0x74);
+    timer.write(1, 3);
+    timer.write(1, 0);
+    assert_eq!(outs(&mut timer, 1, 7), [true, true, false, true, true, false, true]);
+
+    timer.write(3, 0xB6);
+    timer.write(2, 5);
+    timer.write(2, 0);
+    assert_eq!(outs(&mut timer, 2, 11), [true, true, true, false, false, true, true, true, false, false, true]);
+    timer.set_gate(2, false);
+    assert!(timer.out(2));
+    assert_eq!(outs(&mut timer, 2, 3), [true; 3]);
+    timer.set_gate(2, true);
+    assert_eq!(outs(&mut timer, 2, 5), [true, true, true, false, false]);
+
+    timer.write(3, 0x12);
+    timer.write(0, 3);
+    assert_eq!(outs(&mut timer, 0, 2), [true, true]);
+    timer.set_gate(0, false);
+    timer.set_gate(0, true);
+    assert_eq!(outs(&mut timer, 0, 5), [false, false, false, true, true]);
+
+    timer.write(3, 0x18);
+    timer.write(0, 2);
+    assert_eq!(outs(&mut timer, 0, 5), [true, true, false, true, true]);
+    timer.write(3, 0x1A);
+    timer.write(0, 2);
+    assert_eq!(outs(&mut timer, 0, 2), [true, true]);
+    timer.set_gate(0, false);
+    timer.set_gate(0, true);
+    assert_eq!(outs(&mut timer, 0, 5), [true, true, false, true, true]);
+
+    timer.write(3, 0x31);
+    timer.write(0, 0x10);
+    timer.write(0, 0x00);
+    timer.tick(4);
+    assert_eq!([timer.read(0), timer.read(0)], [0x09, 0x00]);
+    timer.reset();
+    assert!(!timer.out(0));
+}
+
+#[test]
+fn timer_snapshot() {
+    let mut timer = I8253::new(1);
+    timer.write(3, 0x14);
+    timer.write(0, 5);
+    timer.tick(3);
+    let mut saved = Vec::new();
+    timer.save(&mut saved);
+    assert!(I8253::new(1).restore(&mut saved.as_slice()).is_ok());
+    saved[6..10].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(I8253::new(1).restore(&mut saved.as_slice()), Err(crate::snapshot::Error::Mismatch));
+}
+
+#[test]
+fn timer_interrupts() {
+    let mut timer = I8253::new(1);
+    timer.connect(0, Some(7));
+    let mut machine = Machine::new(PortBus::new(MemoryMap::new().ram(..)).attach(0x40, 0xFC, timer));
+    let program = crate::assembler::assemble("
+        ORG     0
+        LXI     SP,1000H
+        MVI     A,34H
+        OUT     43H
+        MVI     A,100
+        OUT     40H
+        XRA     A
+        OUT     40H
+        EI
+LOOP:   HLT
+        JMP     LOOP
+        ORG     38H
+        LXI     H,COUNT
+        INR     M
+        EI
+        RET
+COUNT:  DB      0
+    ").unwrap();
+    machine.program(0, &program.image);
+    let clocking = machine.clock_devices(4);
+    machine.run_for(2150);
+    assert_eq!(machine.read(Wrapping(program.symbols["COUNT"])).0, 20);
+    assert_eq!(machine.request(), None);
+    assert!(machine.cancel(clocking));
+}
+
+#[test]
+fn controller() {
+    let mut pic = I8259::new();
+    pic.set_line(1, true);
+    assert_eq!(pic.request(), None);
+    pic.write(0, 0x16);
+    pic.write(1, 0x10);
+    pic.set_line(3, true);
+    pic.set_line(1, false);
+    pic.set_line(1, true);
+    assert_eq!(pic.request(), Some(Vector::Call(0x1004)));
+    pic.acknowledge();
+    assert_eq!(pic.request(), None);
+    pic.set_line(0, true);
+    assert_eq!(pic.request(), Some(Vector::Call(0x1000)));
+    pic.acknowledge();
+    pic.write(0, 0x0B);
+    assert_eq!(pic.read(0), 0x03);
+    pic.write(0, 0x0A);
+    assert_eq!(pic.read(0), 0x08);
+    pic.write(0, 0x20);
+    assert_eq!(pic.request(), None);
+    pic.write(0, 0x20);
+    assert_eq!(pic.request(), Some(Vector::Call(0x100C)));
+
+    pic.write(1, 0x08);
+    assert_eq!(pic.read(1), 0x08);
+    assert_eq!(pic.request(), None);
+    pic.write(1, 0x00);
+    pic.write(0, 0xC3);
+    pic.set_line(2, true);
+    assert_eq!(pic.request(), Some(Vector::Call(0x1008)));
+    pic.write(0, 0x0C);
+    assert_eq!(pic.read(0), 0x82);
+    pic.write(0, 0x0C);
+    assert_eq!(pic.read(0), 0x00);
+    pic.write(0, 0xA0);
+    assert_eq!(pic.request(), Some(Vector::Call(0x100C)));
+    pic.acknowledge();
+    pic.write(0, 0x68);
+    pic.set_line(4, true);
+    assert_eq!(pic.request(), Some(Vector::Call(0x1010)));
+    pic.write(0, 0x48);
+    assert_eq!(pic.request(), None);
+
+    for line in [0, 2, 3, 4] { pic.set_line(line, false); }
+    pic.write(0, 0x1B);
+    pic.write(1, 0x20);
+    pic.write(1, 0x02);
+    assert_eq!(pic.request(), Some(Vector::Call(0x2008)));
+    pic.acknowledge();
+    assert_eq!(pic.request(), Some(Vector::Call(0x2008)));
+    pic.set_line(1, false);
+    assert_eq!(pic.request(), None);
+    pic.set_line(4, true);
+    assert_eq!(pic.request(), Some(Vector::Call(0x2020)));
+}
+
+#[test]
+fn controller_interrupts() {
+    let pic = Rc::new(RefCell::new(I8259::new()));
+    let mut timer = I8253::new(1);
+    let mut machine = Machine::new(PortBus::new(MemoryMap::new().ram(..)).attach(0x20, 0xFE, pic.clone()));
+    let program = crate::assembler::assemble("
+        ORG     0
+        LXI     SP,1000H
+        MVI     A,16H
+        OUT     20H
+        MVI     A,20H
+        OUT     21H
+        EI
+LOOP:   JMP     LOOP
+        ORG     2000H
+        LXI     H,COUNT
+        INR     M
+        MVI     A,20H
+        OUT     20H
+        EI
+        RET
+COUNT:  DB      0
+    ").unwrap();
+    machine.program(0, &program.image[..program.symbols["LOOP"] as usize + 3]);
+    machine.program(0x2000, &program.image[0x2000..]);
+    timer.write(3, 0x36);
+    timer.write(0, 100);
+    timer.write(0, 0);
+    let mut elapsed = 0;
+    while elapsed < 2150 {
+        #[cfg(feature="open")]
+        let cycles = machine.execute().unwrap().unwrap().get() as usize;
+        #[cfg(not(feature="open"))]
+        let cycles = machine.execute().unwrap().get() as usize;
+        elapsed += cycles;
+        timer.tick(cycles);
+        RefCell::borrow_mut(&pic).set_line(0, timer.out(0));
+        machine.service();
+    }
+    assert_eq!(machine.read(Wrapping(program.symbols["COUNT"])).0, 21);
+}
+
+#[test]
+fn ppi() {
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let mut ppi = I8255::new();
+    let log = changes.clone();
+    ppi.on_change(move |port, value| RefCell::borrow_mut(&log).push((port, value)));
+    ppi.set_input(i8255::Port::A, 0x5A);
+    assert_eq!(ppi.read(0), 0x5A);
+    ppi.write(3, 0x81);
+    ppi.write(0, 0x12);
+    ppi.write(1, 0x34);
+    ppi.write(2, 0xFF);
+    ppi.set_input(i8255::Port::C, 0x06);
+    assert_eq!([ppi.read(0), ppi.read(1), ppi.read(2)], [0x12, 0x34, 0xF6]);
+    ppi.write(3, 0x0E);
+    ppi.write(3, 0x09);
+    ppi.write(3, 0x01);
+    assert_eq!(ppi.outputs(i8255::Port::C), 0x70);
+    assert_eq!(*RefCell::borrow(&changes), [
+        (i8255::Port::A, 0x12), (i8255::Port::B, 0x34), (i8255::Port::C, 0xF0), (i8255::Port::C, 0x70),
+    ]);
+    ppi.reset();
+    assert_eq!(ppi.outputs(i8255::Port::A), 0);
+    assert_eq!(ppi.read(1), 0xFF);
+}
+
+#[test]
+fn ppi_handshakes() {
+    let mut ppi = I8255::new();
+    ppi.connect(i8255::Port::A, Some(5));
+    ppi.write(3, 0xB4);
+    ppi.strobe(i8255::Port::A, 0x41);
+    assert_eq!(ppi.read(2) & 0x38, 0x20);
+    assert_eq!(ppi.request(), None);
+    ppi.write(3, 0x09);
+    ppi.strobe(i8255::Port::A, 0x42);
+    assert_eq!(ppi.read(2) & 0x38, 0x38);
+    assert_eq!(ppi.request(), Some(Vector::Restart(5)));
+    assert_eq!(ppi.read(0), 0x42);
+    assert_eq!(ppi.read(2) & 0x38, 0x10);
+    assert_eq!(ppi.request(), None);
+
+    ppi.write(3, 0x05);
+    assert_eq!(ppi.take(i8255::Port::B), None);
+    ppi.write(1, 0x99);
+    assert_eq!(ppi.read(2) & 0x07, 0x04);
+    assert_eq!(ppi.take(i8255::Port::B), Some(0x99));
+    assert_eq!(ppi.read(2) & 0x07, 0x07);
+
+    let mut ppi = I8255::new();
+    ppi.connect(i8255::Port::A, Some(3));
+    ppi.write(3, 0xC0);
+    ppi.write(3, 0x0D);
+    ppi.write(0, 0x77);
+    assert_eq!(ppi.read(2) & 0xF8, 0x40);
+    assert_eq!(ppi.outputs(i8255::Port::A), 0);
+    assert_eq!(ppi.take(i8255::Port::A), Some(0x77));
+    assert_eq!(ppi.request(), Some(Vector::Restart(3)));
+    ppi.strobe(i8255::Port::A, 0x88);
+    assert_eq!(ppi.read(2) & 0xF8, 0xE8);
+    assert_eq!(ppi.read(0), 0x88);
+    assert_eq!(ppi.read(2) & 0xF8, 0xC8);
+    ppi.write(0, 0x66);
+    assert_eq!(ppi.request(), None);
+}
+
+#[cfg(feature="std")]
+#[test]
+fn acia() {
+    let mut acia = Mc6850::new(&b"xyz"[..], Vec::new(), 1);
+    acia.connect(Some(7));
+    acia.write(1, b'-');
+    acia.tick(100);
+    assert_eq!(acia.read(0), 0x00);
+    assert!(acia.get_mut().1.is_empty());
+
+    acia.write(0, 0x94);
+    assert_eq!(acia.read(0), 0x02);
+    acia.write(1, b'H');
+    acia.write(1, b'i');
+    assert_eq!(acia.read(0), 0x00);
+    acia.tick(9);
+    assert_eq!(acia.read(0), 0x00);
+    acia.tick(1);
+    assert_eq!(acia.get_mut().1, b"H");
+    assert_eq!(acia.read(0), 0x83);
+    assert_eq!(acia.request(), Some(Vector::Restart(7)));
+    assert_eq!(acia.read(1), b'x');
+    assert_eq!(acia.request(), None);
+    acia.tick(20);
+    assert_eq!(acia.get_mut().1, b"Hi");
+    assert_eq!(acia.read(0), 0xA3);
+    assert_eq!(acia.read(1), b'y');
+    assert_eq!(acia.read(0), 0x02);
+
+    acia.write(0, 0x03);
+    acia.write(0, 0x21);
+    assert!(acia.request_to_send());
+    assert_eq!(acia.read(0), 0x82);
+    assert_eq!(acia.request(), Some(Vector::Restart(7)));
+    acia.write(1, 0xC1);
+    acia.tick(16 * 11);
+    assert_eq!(acia.into_inner().1, b"Hi\x41");
+}
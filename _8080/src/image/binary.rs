@@ -0,0 +1,20 @@
+//! Raw binary images: memory contents with no addresses of their own, such as ROM dumps and
+//! CP/M `.COM` files, which are placed at an origin chosen by the caller.
+
+use crate::prelude::{*, vec::Vec};
+use core::ops::RangeBounds;
+use super::{bounds, Error, Loaded};
+
+/// Stores a binary image into a harness, starting at `origin`. The image must fit below the
+/// end of the address space.
+pub fn load<H: Harness + ?Sized>(bytes: &[raw::u8], origin: raw::u16, harness: &mut H) -> Result<Loaded, Error> {
+    let mut loaded = Loaded { origin, ..Loaded::default() };
+    loaded.store(harness, origin as usize, bytes).map_err(|kind| Error { line: 0, kind })?;
+    Ok(loaded)
+}
+
+/// Reads a range of a harness's memory as a binary image.
+pub fn save<H: Harness + ?Sized>(harness: &H, range: impl RangeBounds<raw::u16>) -> Vec<raw::u8> {
+    let (start, end) = bounds(range);
+    (start..end).map(|address| harness.read(Wrapping(address as raw::u16)).0).collect()
+}
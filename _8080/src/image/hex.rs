@@ -0,0 +1,109 @@
+//! Intel HEX: a text format of `:`-prefixed records, each holding a byte count, a 16-bit
+//! address, a record type, its data and a checksum, all as pairs of hexadecimal digits.
+//!
+//! The loader accepts data (`00`) and end-of-file (`01`) records, and takes the start address
+//! from either kind of start-address record (`03` or `05`). Extended address records (`02` and
+//! `04`) are only accepted when they select the first 64K, since that's all an 8080 can reach.
+
+use crate::prelude::{*, vec::Vec, fmt::Write};
+use core::ops::RangeBounds;
+use super::{bounds, Error, ErrorKind, Loaded};
+
+/// One decoded record.
+struct Record {
+    address: raw::u16,
+    kind: raw::u8,
+    data: Vec<raw::u8>,
+}
+
+fn decode(line: &str) -> Result<Record, ErrorKind> {
+    let digits = line.strip_prefix(':').ok_or(ErrorKind::MissingStart)?;
+    if digits.len() % 2 != 0 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ErrorKind::BadDigit);
+    }
+    let bytes = (0..digits.len()).step_by(2)
+        .map(|at| raw::u8::from_str_radix(&digits[at..at + 2], 16).map_err(|_| ErrorKind::BadDigit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(&count) = bytes.first() else { return Err(ErrorKind::Length { expected: 5, found: 0 }) };
+    let expected = count as usize + 5;
+    if bytes.len() != expected {
+        return Err(ErrorKind::Length { expected, found: bytes.len() });
+    }
+    let (&found, body) = bytes.split_last().ok_or(ErrorKind::BadDigit)?;
+    let expected = checksum(body);
+    if found != expected {
+        return Err(ErrorKind::Checksum { expected, found });
+    }
+    Ok(Record { address: raw::u16::from_be_bytes([body[1], body[2]]), kind: body[3], data: body[4..].to_vec() })
+}
+
+fn checksum(bytes: &[raw::u8]) -> raw::u8 {
+    bytes.iter().fold(0, |sum: raw::u8, byte| sum.wrapping_add(*byte)).wrapping_neg()
+}
+
+/// Reads Intel HEX text and stores its data into a harness. Blank lines are skipped, and
+/// anything after the end-of-file record is ignored. Data is stored as each record is read, so
+/// memory may already be partly written when an error is reported.
+pub fn load<H: Harness + ?Sized>(text: &str, harness: &mut H) -> Result<Loaded, Error> {
+    load_at(text, 0, harness)
+}
+
+/// Loads Intel HEX text with every record moved up by `offset` bytes.
+pub(super) fn load_at<H: Harness + ?Sized>(text: &str, offset: raw::u16, harness: &mut H) -> Result<Loaded, Error> {
+    let mut loaded = Loaded::default();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue; }
+        let fail = |kind| Error { line: index + 1, kind };
+        let Record { address, kind, data } = decode(line).map_err(fail)?;
+        match (kind, &data[..]) {
+            (0x00, data) => loaded.store(harness, address as usize + offset as usize, data).map_err(fail)?,
+            (0x01, []) => return Ok(loaded),
+            (0x02 | 0x04, [0, 0]) => (),
+            (0x02 | 0x04, [_, _]) => return Err(fail(ErrorKind::OutOfRange)),
+            (0x03, &[segment_high, segment_low, high, low]) => {
+                let segment = raw::u16::from_be_bytes([segment_high, segment_low]) as u32;
+                let linear = (segment << 4) + raw::u16::from_be_bytes([high, low]) as u32 + offset as u32;
+                loaded.entry = Some(raw::u16::try_from(linear).map_err(|_| fail(ErrorKind::OutOfRange))?);
+            }
+            (0x05, &[a, b, c, d]) => {
+                let linear = u32::from_be_bytes([a, b, c, d]).saturating_add(offset as u32);
+                loaded.entry = Some(raw::u16::try_from(linear).map_err(|_| fail(ErrorKind::OutOfRange))?);
+            }
+            (0x01..=0x05, _) => return Err(fail(ErrorKind::BadRecord(kind))),
+            _ => return Err(fail(ErrorKind::UnknownType(kind))),
+        }
+    }
+    Err(Error { line: text.lines().count(), kind: ErrorKind::MissingEnd })
+}
+
+fn record(text: &mut String, address: raw::u16, kind: raw::u8, data: &[raw::u8]) {
+    let [high, low] = address.to_be_bytes();
+    let mut bytes = vec![data.len() as raw::u8, high, low, kind];
+    bytes.extend_from_slice(data);
+    bytes.push(checksum(&bytes));
+    text.push(':');
+    for byte in bytes {
+        let _ = write!(text, "{byte:02X}");
+    }
+    text.push('\n');
+}
+
+/// Writes a range of a harness's memory as Intel HEX text, 16 bytes to a data record, and
+/// ends it with a start-address record (if an entry point is given) and an end-of-file record.
+pub fn save<H: Harness + ?Sized>(harness: &H, range: impl RangeBounds<raw::u16>, entry: Option<raw::u16>) -> String {
+    let (start, end) = bounds(range);
+    let mut text = String::new();
+    for from in (start..end).step_by(16) {
+        let data = (from..end.min(from + 16))
+            .map(|address| harness.read(Wrapping(address as raw::u16)).0)
+            .collect::<Vec<_>>();
+        record(&mut text, from as raw::u16, 0x00, &data);
+    }
+    if let Some(entry) = entry {
+        let [high, low] = entry.to_be_bytes();
+        record(&mut text, 0, 0x03, &[0, 0, high, low]);
+    }
+    record(&mut text, 0, 0x01, &[]);
+    text
+}
@@ -0,0 +1,163 @@
+//! Loaders and writers for the file formats that 8080 programs and ROM dumps are distributed
+//! in. Loaders store what they read into any `Harness` through its `write` method, so they
+//! work the same way for a `SimpleBoard` as for a board of your own.
+//!
+//! Each format has its own module; `load` (or `Machine::load`) chooses between them with a
+//! `Format`, and can place the image at an origin and start the processor at its entry point.
+
+use crate::prelude::{*, fmt::{self, Display, Formatter}};
+use core::ops::{Bound, RangeBounds};
+
+pub mod binary;
+pub mod hex;
+pub mod srec;
+
+/// The ways an image can be malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A record didn't start with the format's start code (`:` for HEX, `S` for S-records).
+    MissingStart,
+    /// A record had a character that isn't a hexadecimal digit, or an odd number of digits.
+    BadDigit,
+    /// A record was shorter or longer than its byte count says.
+    Length { expected: usize, found: usize },
+    /// A record's checksum didn't match its contents.
+    Checksum { expected: raw::u8, found: raw::u8 },
+    /// The record type isn't one the loader understands.
+    UnknownType(raw::u8),
+    /// A record of a known type had the wrong amount of data for that type.
+    BadRecord(raw::u8),
+    /// An S-record count didn't match the number of data records before it.
+    Count { expected: usize, found: usize },
+    /// The image placed data beyond the 64K an 8080 can address.
+    OutOfRange,
+    /// The text ended without the record that should end it.
+    MissingEnd,
+    /// A text format was given bytes that aren't text.
+    NotText,
+}
+
+/// A loading failure, along with the (1-based) line of text it occurred on; the line is 0 for
+/// binary images and for failures that don't belong to one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingStart => write!(f, "record has no start code"),
+            Self::BadDigit => write!(f, "record isn't made of pairs of hexadecimal digits"),
+            Self::Length { expected, found } => write!(f, "record should have {expected} bytes but has {found}"),
+            Self::Checksum { expected, found } => write!(f, "checksum is {found:02X}H but should be {expected:02X}H"),
+            Self::UnknownType(kind) => write!(f, "unknown record type {kind}"),
+            Self::BadRecord(kind) => write!(f, "malformed record of type {kind}"),
+            Self::Count { expected, found } => write!(f, "record count is {expected} but there were {found} records"),
+            Self::OutOfRange => write!(f, "address beyond 64K"),
+            Self::MissingEnd => write!(f, "no closing record"),
+            Self::NotText => write!(f, "image isn't text"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.kind),
+            line => write!(f, "line {line}: {}", self.kind),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A summary of what a loader stored into memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Loaded {
+    /// The lowest address that received data (or 0 if the image had no data).
+    pub origin: raw::u16,
+    /// The number of bytes stored.
+    pub length: usize,
+    /// The start address recorded in the image, if it had one.
+    pub entry: Option<raw::u16>,
+}
+
+impl Loaded {
+    /// Writes one block of data, which must end within the address space.
+    fn store<H: Harness + ?Sized>(&mut self, harness: &mut H, address: usize, data: &[raw::u8]) -> Result<(), ErrorKind> {
+        if address + data.len() > 0x10000 {
+            return Err(ErrorKind::OutOfRange);
+        }
+        for (offset, byte) in data.iter().enumerate() {
+            harness.write(Wrapping((address + offset) as raw::u16), Wrapping(*byte));
+        }
+        if data.is_empty() { return Ok(()); }
+        if self.length == 0 || (address as raw::u16) < self.origin {
+            self.origin = address as raw::u16;
+        }
+        self.length += data.len();
+        Ok(())
+    }
+}
+
+/// Converts a range of addresses into the start and (exclusive) end of a `usize` range, so that
+/// a range can run through the top of memory.
+pub(crate) fn bounds(range: impl RangeBounds<raw::u16>) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start as usize,
+        Bound::Excluded(&start) => start as usize + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end as usize + 1,
+        Bound::Excluded(&end) => end as usize,
+        Bound::Unbounded => 0x10000,
+    };
+    (start, end)
+}
+
+/// The file formats that `load` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Intel HEX text.
+    Hex,
+    /// Motorola S-record text.
+    SRecord,
+    /// A raw memory image.
+    Binary,
+}
+
+/// Stores an image of any format into a harness. A binary image is placed at `origin`; the
+/// records of a text image are moved up by `origin` bytes, so it is usually 0 for them. The
+/// entry point is `entry` if given, or else the start address in the image (moved up the same
+/// way), or else `origin` for a binary image.
+pub fn load<H: Harness + ?Sized>(format: Format, image: &[raw::u8], origin: raw::u16, entry: Option<raw::u16>, harness: &mut H) -> Result<Loaded, Error> {
+    let text = || core::str::from_utf8(image).map_err(|_| Error { line: 0, kind: ErrorKind::NotText });
+    let mut loaded = match format {
+        Format::Hex => hex::load_at(text()?, origin, harness)?,
+        Format::SRecord => srec::load_at(text()?, origin, harness)?,
+        Format::Binary => {
+            let loaded = binary::load(image, origin, harness)?;
+            Loaded { entry: Some(origin), ..loaded }
+        }
+    };
+    loaded.entry = entry.or(loaded.entry);
+    Ok(loaded)
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Stores an image into this machine's harness as `image::load` does, then starts the
+    /// processor at the image's entry point (if there is one).
+    pub fn load(&mut self, format: Format, image: &[raw::u8], origin: raw::u16, entry: Option<raw::u16>) -> Result<Loaded, Error> {
+        let loaded = load(format, image, origin, entry, self.board.borrow_mut())?;
+        if let Some(entry) = loaded.entry {
+            self.start_at(entry);
+        }
+        Ok(loaded)
+    }
+}
+
+#[cfg(test)]
+mod tests;
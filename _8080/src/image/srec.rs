@@ -0,0 +1,126 @@
+//! Motorola S-records: a text format of records that start with `S` and a type digit, followed
+//! by pairs of hexadecimal digits for the byte count, the address, the data and a checksum.
+//!
+//! The loader skips the `S0` header, stores `S1`, `S2` and `S3` data, checks `S5` and `S6`
+//! record counts and takes the start address from the `S7`, `S8` or `S9` record that ends the
+//! image. Addresses wider than 16 bits are only accepted when they fall in the first 64K.
+
+use crate::prelude::{*, vec::Vec, fmt::Write};
+use core::ops::RangeBounds;
+use super::{bounds, Error, ErrorKind, Loaded};
+
+/// One decoded record.
+struct Record {
+    kind: raw::u8,
+    address: u32,
+    data: Vec<raw::u8>,
+}
+
+/// The width in bytes of the address field of each record type.
+fn width(kind: raw::u8) -> Option<usize> {
+    match kind {
+        0 | 1 | 5 | 9 => Some(2),
+        2 | 6 | 8 => Some(3),
+        3 | 7 => Some(4),
+        _ => None,
+    }
+}
+
+fn decode(line: &str) -> Result<Record, ErrorKind> {
+    let rest = line.strip_prefix(['S', 's']).ok_or(ErrorKind::MissingStart)?;
+    let mut chars = rest.chars();
+    let kind = chars.next().and_then(|c| c.to_digit(10)).ok_or(ErrorKind::BadDigit)? as raw::u8;
+    let digits = chars.as_str();
+    if digits.len() % 2 != 0 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ErrorKind::BadDigit);
+    }
+    let bytes = (0..digits.len()).step_by(2)
+        .map(|at| raw::u8::from_str_radix(&digits[at..at + 2], 16).map_err(|_| ErrorKind::BadDigit))
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(&count) = bytes.first() else { return Err(ErrorKind::Length { expected: 1, found: 0 }) };
+    let expected = count as usize + 1;
+    if bytes.len() != expected {
+        return Err(ErrorKind::Length { expected, found: bytes.len() });
+    }
+    let (&found, body) = bytes.split_last().ok_or(ErrorKind::BadDigit)?;
+    let expected = checksum(body);
+    if found != expected {
+        return Err(ErrorKind::Checksum { expected, found });
+    }
+    let width = width(kind).ok_or(ErrorKind::UnknownType(kind))?;
+    if body.len() < 1 + width {
+        return Err(ErrorKind::BadRecord(kind));
+    }
+    let address = body[1..=width].iter().fold(0, |address, byte| address << 8 | *byte as u32);
+    Ok(Record { kind, address, data: body[1 + width..].to_vec() })
+}
+
+fn checksum(bytes: &[raw::u8]) -> raw::u8 {
+    !bytes.iter().fold(0, |sum: raw::u8, byte| sum.wrapping_add(*byte))
+}
+
+/// Reads S-record text and stores its data into a harness. Blank lines are skipped, and
+/// anything after the terminating record is ignored. Data is stored as each record is read, so
+/// memory may already be partly written when an error is reported.
+pub fn load<H: Harness + ?Sized>(text: &str, harness: &mut H) -> Result<Loaded, Error> {
+    load_at(text, 0, harness)
+}
+
+/// Loads S-record text with every record moved up by `offset` bytes.
+pub(super) fn load_at<H: Harness + ?Sized>(text: &str, offset: raw::u16, harness: &mut H) -> Result<Loaded, Error> {
+    let mut loaded = Loaded::default();
+    let mut records = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue; }
+        let fail = |kind| Error { line: index + 1, kind };
+        let Record { kind, address, data } = decode(line).map_err(fail)?;
+        match kind {
+            0 => (),
+            1..=3 => {
+                loaded.store(harness, address as usize + offset as usize, &data).map_err(fail)?;
+                records += 1;
+            }
+            5 | 6 if !data.is_empty() => return Err(fail(ErrorKind::BadRecord(kind))),
+            5 | 6 if address != records => return Err(fail(ErrorKind::Count { expected: address as usize, found: records as usize })),
+            5 | 6 => (),
+            _ if !data.is_empty() => return Err(fail(ErrorKind::BadRecord(kind))),
+            _ => {
+                let entry = address.saturating_add(offset as u32);
+                loaded.entry = Some(raw::u16::try_from(entry).map_err(|_| fail(ErrorKind::OutOfRange))?);
+                return Ok(loaded);
+            }
+        }
+    }
+    Err(Error { line: text.lines().count(), kind: ErrorKind::MissingEnd })
+}
+
+fn record(text: &mut String, kind: raw::u8, address: raw::u16, data: &[raw::u8]) {
+    let [high, low] = address.to_be_bytes();
+    let mut bytes = vec![data.len() as raw::u8 + 3, high, low];
+    bytes.extend_from_slice(data);
+    bytes.push(checksum(&bytes));
+    let _ = write!(text, "S{kind}");
+    for byte in bytes {
+        let _ = write!(text, "{byte:02X}");
+    }
+    text.push('\n');
+}
+
+/// Writes a range of a harness's memory as S19 text: `S1` records of 16 bytes, an `S5` record
+/// count and an `S9` record holding the entry point (or 0 if there isn't one).
+pub fn save<H: Harness + ?Sized>(harness: &H, range: impl RangeBounds<raw::u16>, entry: Option<raw::u16>) -> String {
+    let (start, end) = bounds(range);
+    let mut text = String::new();
+    let mut records = 0;
+    for from in (start..end).step_by(16) {
+        let data = (from..end.min(from + 16))
+            .map(|address| harness.read(Wrapping(address as raw::u16)).0)
+            .collect::<Vec<_>>();
+        record(&mut text, 1, from as raw::u16, &data);
+        records += 1;
+    }
+    record(&mut text, 5, records, &[]);
+    record(&mut text, 9, entry.unwrap_or(0), &[]);
+    text
+}
@@ -0,0 +1,122 @@
+use super::*;
+use crate::{Machine, SimpleBoard, string::ToString};
+
+#[test]
+fn hex_load() {
+    let mut board = SimpleBoard::default();
+    let loaded = hex::load("
+:03010000C3000138
+:0201FE003E0DB4
+:0400000300000100F8
+:00000001FF
+:01020000FFFE
+    ", &mut board).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0x0100, length: 5, entry: Some(0x0100) });
+    assert_eq!(board[0x0100..0x0103], [Wrapping(0xC3), Wrapping(0x00), Wrapping(0x01)]);
+    assert_eq!(board[0x01FE..0x0200], [Wrapping(0x3E), Wrapping(0x0D)]);
+    assert_eq!(board[0x0200], Wrapping(0x00));
+}
+
+#[test]
+fn hex_round_trip() {
+    let mut board = SimpleBoard::default();
+    for (index, byte) in board[0x1000..0x1025].iter_mut().enumerate() {
+        *byte = Wrapping(index as raw::u8 * 7);
+    }
+    let text = hex::save(&board, 0x1000..0x1025, Some(0x1000));
+    assert_eq!(text.lines().count(), 5);
+    assert_eq!(text.lines().nth(2), Some(":05102000E0E7EEF5FC25"));
+    assert_eq!(text.lines().last(), Some(":00000001FF"));
+    let mut copy = SimpleBoard::default();
+    let loaded = hex::load(&text, &mut copy).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0x1000, length: 0x25, entry: Some(0x1000) });
+    assert_eq!(copy[..], board[..]);
+    assert_eq!(hex::save(&board, 0xFFFF.., None), ":01FFFF000001\n:00000001FF\n");
+}
+
+#[test]
+fn hex_errors() {
+    let error = |text| hex::load(text, &mut SimpleBoard::default()).unwrap_err();
+    assert_eq!(error("03010000C3000138"), Error { line: 1, kind: ErrorKind::MissingStart });
+    assert_eq!(error("\n:03010000C30001B"), Error { line: 2, kind: ErrorKind::BadDigit });
+    assert_eq!(error(":0301G000C3000138"), Error { line: 1, kind: ErrorKind::BadDigit });
+    assert_eq!(error(":03010000C300B8"), Error { line: 1, kind: ErrorKind::Length { expected: 8, found: 7 } });
+    assert_eq!(error(":03010000C3000139"), Error { line: 1, kind: ErrorKind::Checksum { expected: 0x38, found: 0x39 } });
+    assert_eq!(error(":00000006FA"), Error { line: 1, kind: ErrorKind::UnknownType(6) });
+    assert_eq!(error(":020000040001F9"), Error { line: 1, kind: ErrorKind::OutOfRange });
+    assert_eq!(error(":02FFFF00000000"), Error { line: 1, kind: ErrorKind::OutOfRange });
+    assert_eq!(error(":03010000C3000138\n"), Error { line: 1, kind: ErrorKind::MissingEnd });
+    assert_eq!(error(":03010000C3000139").to_string(), "line 1: checksum is 39H but should be 38H");
+}
+
+#[test]
+fn srec_load() {
+    let mut board = SimpleBoard::default();
+    let loaded = srec::load("
+S00600004844521B
+S1060100C3000134
+S2070001FE3E0D00AE
+S5030002FA
+S9030100FB
+S1040200FFFA
+    ", &mut board).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0x0100, length: 6, entry: Some(0x0100) });
+    assert_eq!(board[0x0100..0x0103], [Wrapping(0xC3), Wrapping(0x00), Wrapping(0x01)]);
+    assert_eq!(board[0x01FE..0x0201], [Wrapping(0x3E), Wrapping(0x0D), Wrapping(0x00)]);
+}
+
+#[test]
+fn srec_round_trip() {
+    let mut board = SimpleBoard::default();
+    for (index, byte) in board[0x2000..0x2012].iter_mut().enumerate() {
+        *byte = Wrapping(index as raw::u8 + 1);
+    }
+    let text = srec::save(&board, 0x2000..0x2012, Some(0x2000));
+    assert_eq!(text.lines().nth(1), Some("S10520101112A7"));
+    assert_eq!(text.lines().nth(2), Some("S5030002FA"));
+    assert_eq!(text.lines().nth(3), Some("S9032000DC"));
+    let mut copy = SimpleBoard::default();
+    assert_eq!(srec::load(&text, &mut copy).unwrap(), Loaded { origin: 0x2000, length: 0x12, entry: Some(0x2000) });
+    assert_eq!(copy[..], board[..]);
+}
+
+#[test]
+fn srec_errors() {
+    let error = |text| srec::load(text, &mut SimpleBoard::default()).unwrap_err();
+    assert_eq!(error(":1060100C3000135"), Error { line: 1, kind: ErrorKind::MissingStart });
+    assert_eq!(error("S1060100C3000135"), Error { line: 1, kind: ErrorKind::Checksum { expected: 0x34, found: 0x35 } });
+    assert_eq!(error("S4030000FC"), Error { line: 1, kind: ErrorKind::UnknownType(4) });
+    assert_eq!(error("S1060100C3000134\nS5030002FA"), Error { line: 2, kind: ErrorKind::Count { expected: 2, found: 1 } });
+    assert_eq!(error("S2050100000FB"), Error { line: 1, kind: ErrorKind::BadDigit });
+    assert_eq!(error("S20501000000F9"), Error { line: 1, kind: ErrorKind::OutOfRange });
+    assert_eq!(error("S1060100C3000134"), Error { line: 1, kind: ErrorKind::MissingEnd });
+}
+
+#[test]
+fn formats() {
+    let mut board = SimpleBoard::default();
+    let loaded = load(Format::Binary, &[1, 2, 3], 0xFFFD, None, &mut board).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0xFFFD, length: 3, entry: Some(0xFFFD) });
+    assert_eq!(board[0xFFFD..], [Wrapping(1), Wrapping(2), Wrapping(3)]);
+    assert_eq!(load(Format::Binary, &[1, 2, 3, 4], 0xFFFD, None, &mut board), Err(Error { line: 0, kind: ErrorKind::OutOfRange }));
+    let loaded = load(Format::Hex, b":010000009966\n:0400000300000010E9\n:00000001FF", 0x4000, None, &mut board).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0x4000, length: 1, entry: Some(0x4010) });
+    assert_eq!(board[0x4000], Wrapping(0x99));
+    let loaded = load(Format::SRecord, b"S1040010AA41\nS9030000FC", 0, Some(0x1234), &mut board).unwrap();
+    assert_eq!(loaded, Loaded { origin: 0x0010, length: 1, entry: Some(0x1234) });
+    assert_eq!(load(Format::Hex, &[0xFF], 0, None, &mut board), Err(Error { line: 0, kind: ErrorKind::NotText }));
+}
+
+#[test]
+fn machine_load() {
+    let mut machine = Machine::new(SimpleBoard::default());
+    let program = [0x3E, 0x2A, 0x32, 0x00, 0x02, 0x76];
+    let loaded = machine.load(Format::Binary, &program, 0x0100, None).unwrap();
+    assert_eq!(loaded.entry, Some(0x0100));
+    for _ in 0..3 { let _ = machine.execute(); }
+    assert_eq!(machine[0x0200], Wrapping(0x2A));
+    machine[0x0200] = Wrapping(0);
+    machine.load(Format::Binary, &[0x00], 0x0000, Some(0x0100)).unwrap();
+    for _ in 0..3 { let _ = machine.execute(); }
+    assert_eq!(machine[0x0200], Wrapping(0x2A));
+}
@@ -0,0 +1,298 @@
+//! The Taito Space Invaders arcade board (Midway 8080 hardware), ready to run the game's ROMs.
+//!
+//! The board has 8K of ROM at 0x0000, 8K of RAM at 0x2000 (mirrored through the rest of the
+//! address space) whose top 7K is a 1-bit framebuffer, an MB14241 barrel shifter to help draw
+//! sprites at any bit offset, the player controls and DIP switches on input ports 0 to 2, and
+//! the triggers for its discrete sound circuits on output ports 3 and 5. The monitor is
+//! mounted on its side, so the 256x224 raster comes out 224 pixels wide and 256 high.
+//!
+//! The video hardware interrupts the processor twice a frame: with `RST 1` when the beam
+//! reaches the middle of the screen, and with `RST 2` when it reaches the vertical blank. Once
+//! `start_video` is called, a scheduled event moves the beam along the machine's clock a line at
+//! a time and delivers those interrupts through `reset_to`, in whichever run loop the machine is
+//! driven by; `run_frame` runs it for a frame at a time.
+
+use crate::prelude::{*, vec::Vec};
+use crate::{MemoryMap, run::{Event, Stop, Summary}};
+use crate::snapshot::{self, Snapshot};
+
+/// The processor clock in Hz.
+pub const CLOCK: usize = 1_996_800;
+/// The processor cycles taken by each scan line.
+pub const LINE: usize = 128;
+/// The processor cycles taken by each frame of 262 lines, about 60 a second.
+pub const FRAME: usize = 262 * LINE;
+/// The width of the rotated picture.
+pub const WIDTH: usize = 224;
+/// The height of the rotated picture.
+pub const HEIGHT: usize = 256;
+/// Where the framebuffer starts in RAM.
+pub const VIDEO: raw::u16 = 0x2400;
+/// The line on which `RST 1` is requested.
+const MIDDLE: usize = 96;
+/// The line on which `RST 2` is requested, as the vertical blank starts.
+const BLANK: usize = 224;
+
+/// A control on the cabinet, each of which sets one bit of the input ports while it is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Coin,
+    Start1,
+    Start2,
+    Fire1,
+    Left1,
+    Right1,
+    Fire2,
+    Left2,
+    Right2,
+    Tilt,
+}
+
+impl Button {
+    /// The input port and bit the button sets.
+    fn bit(self) -> (usize, raw::u8) {
+        match self {
+            Button::Coin => (1, 0x01),
+            Button::Start2 => (1, 0x02),
+            Button::Start1 => (1, 0x04),
+            Button::Fire1 => (1, 0x10),
+            Button::Left1 => (1, 0x20),
+            Button::Right1 => (1, 0x40),
+            Button::Tilt => (2, 0x04),
+            Button::Fire2 => (2, 0x10),
+            Button::Left2 => (2, 0x20),
+            Button::Right2 => (2, 0x40),
+        }
+    }
+}
+
+/// The settings of the DIP switches the game reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Switches {
+    /// The number of bases each player starts with, from 3 to 6.
+    pub ships: raw::u8,
+    /// Whether the extra base comes at 1000 points instead of 1500.
+    pub early_bonus: bool,
+    /// Whether the coin information is hidden on the attract screen.
+    pub hide_coin_info: bool,
+}
+
+impl Default for Switches {
+    fn default() -> Self { Self { ships: 3, early_bonus: false, hide_coin_info: false } }
+}
+
+/// One of the board's sound circuits, which the game turns on and off through ports 3 and 5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    /// The flying saucer, which repeats for as long as it stays on.
+    Ufo,
+    Shot,
+    PlayerDies,
+    InvaderDies,
+    ExtraShip,
+    /// The enable of the sound amplifier as a whole.
+    Amplifier,
+    /// One of the four notes of the marching fleet, from 1 to 4.
+    Fleet(raw::u8),
+    UfoHit,
+}
+
+impl Sound {
+    const ALL: [Sound; 11] = [
+        Sound::Ufo, Sound::Shot, Sound::PlayerDies, Sound::InvaderDies, Sound::ExtraShip, Sound::Amplifier,
+        Sound::Fleet(1), Sound::Fleet(2), Sound::Fleet(3), Sound::Fleet(4), Sound::UfoHit,
+    ];
+
+    /// The sound port (0 for port 3 and 1 for port 5) and bit that control the sound.
+    fn bit(self) -> (usize, raw::u8) {
+        match self {
+            Sound::Ufo => (0, 0x01),
+            Sound::Shot => (0, 0x02),
+            Sound::PlayerDies => (0, 0x04),
+            Sound::InvaderDies => (0, 0x08),
+            Sound::ExtraShip => (0, 0x10),
+            Sound::Amplifier => (0, 0x20),
+            Sound::Fleet(note) => (1, 1 << (note.clamp(1, 4) - 1)),
+            Sound::UfoHit => (1, 0x10),
+        }
+    }
+}
+
+/// The Space Invaders board, as a Harness.
+#[derive(Debug, Clone)]
+pub struct Invaders {
+    memory: MemoryMap,
+    /// The MB14241's 16-bit register and the offset of the byte it returns.
+    shift: raw::u16,
+    offset: raw::u8,
+    inputs: [raw::u8; 3],
+    switches: Switches,
+    /// The last values written to ports 3 and 5.
+    sound: [raw::u8; 2],
+    events: Vec<(Sound, bool)>,
+    /// The processor cycles since the top of the frame.
+    beam: usize,
+    pending: Option<usize>,
+}
+
+impl Invaders {
+    /// Creates a board with the game's ROMs, which are usually the 2K images `invaders.h`,
+    /// `.g`, `.f` and `.e` in that order, starting at 0x0000.
+    pub fn new(rom: &[raw::u8]) -> Self {
+        assert!(rom.len() <= 0x2000, "the ROMs occupy at most 8K");
+        Self {
+            memory: MemoryMap::new().open_bus(..0x2000, 0).rom(0, rom).ram(0x2000..0x4000).mirror(0x4000.., 0x2000..0x4000),
+            shift: 0, offset: 0, inputs: [0x0E, 0x08, 0x00], switches: Switches::default(), sound: [0; 2],
+            events: Vec::new(), beam: 0, pending: None,
+        }
+    }
+
+    /// Presses or releases a control.
+    pub fn press(&mut self, button: Button, held: bool) {
+        let (port, bit) = button.bit();
+        self.inputs[port] = if held { self.inputs[port] | bit } else { self.inputs[port] & !bit };
+    }
+
+    /// The settings of the DIP switches.
+    pub fn switches(&self) -> Switches { self.switches }
+
+    /// Changes the settings of the DIP switches. The game reads them as it starts.
+    pub fn set_switches(&mut self, switches: Switches) { self.switches = switches; }
+
+    /// Takes the sounds turned on (`true`) and off (`false`) since the last call, in order.
+    pub fn sounds(&mut self) -> Vec<(Sound, bool)> { core::mem::take(&mut self.events) }
+
+    /// Whether a sound is on now.
+    pub fn playing(&self, sound: Sound) -> bool {
+        let (index, bit) = sound.bit();
+        self.sound[index] & bit != 0
+    }
+
+    /// The scan line the beam is on, from 0 at the top of the raster.
+    pub fn line(&self) -> usize { self.beam / LINE }
+
+    /// The picture as it appears on the cabinet's monitor, as `WIDTH` times `HEIGHT` RGBA pixels
+    /// from the top left, white on black.
+    pub fn frame(&self) -> Vec<raw::u8> {
+        let mut rgba = vec![0; WIDTH * HEIGHT * 4];
+        for (index, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (index % WIDTH, HEIGHT - 1 - index / WIDTH);
+            let byte = self.memory.read(Wrapping(VIDEO + (x * HEIGHT / 8 + y / 8) as raw::u16)).0;
+            let level = if byte & 1 << (y % 8) != 0 { 0xFF } else { 0 };
+            pixel.copy_from_slice(&[level, level, level, 0xFF]);
+        }
+        rgba
+    }
+
+    /// Records the sounds that a write to port 3 or 5 turns on or off.
+    fn play(&mut self, index: usize, value: raw::u8) {
+        let changed = core::mem::replace(&mut self.sound[index], value) ^ value;
+        for sound in Sound::ALL {
+            match sound.bit() {
+                (port, bit) if port == index && changed & bit != 0 => self.events.push((sound, value & bit != 0)),
+                _ => (),
+            }
+        }
+    }
+
+    /// Moves the beam along by a number of processor cycles, noting the interrupt for the
+    /// last line it passes on the way.
+    fn advance(&mut self, cycles: usize) {
+        let before = self.beam;
+        self.beam += cycles;
+        for (line, vector) in [(MIDDLE, 1), (BLANK, 2)] {
+            if (before..self.beam).contains(&(line * LINE)) { self.pending = Some(vector); }
+        }
+        self.beam %= FRAME;
+    }
+}
+
+impl Harness for Invaders {
+    fn read(&self, from: u16) -> u8 { self.memory.read(from) }
+    fn write(&mut self, to: u16, value: u8) { self.memory.write(to, value) }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        Wrapping(match port {
+            0 | 1 => self.inputs[port as usize],
+            2 => {
+                let switches = self.switches;
+                let mut value = self.inputs[2] | (switches.ships.clamp(3, 6) - 3);
+                if switches.early_bonus { value |= 0x08; }
+                if switches.hide_coin_info { value |= 0x80; }
+                value
+            }
+            3 => (self.shift >> (8 - self.offset)) as raw::u8,
+            _ => 0,
+        })
+    }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        match port {
+            2 => self.offset = value.0 & 0x07,
+            3 => self.play(0, value.0),
+            4 => self.shift = self.shift >> 8 | (value.0 as raw::u16) << 8,
+            5 => self.play(1, value.0),
+            _ => (),
+        }
+    }
+}
+
+/// Saves the memory, the shifter, the controls and switches, the sounds playing and the
+/// position of the beam. Sounds that `sounds` hasn't taken yet aren't saved.
+impl Snapshot for Invaders {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.memory.save(out);
+        self.shift.save(out);
+        self.offset.save(out);
+        self.inputs.save(out);
+        [self.switches.ships].save(out);
+        [self.switches.early_bonus, self.switches.hide_coin_info].save(out);
+        self.sound.save(out);
+        self.beam.save(out);
+        self.pending.save(out);
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        self.memory.restore(data)?;
+        self.shift.restore(data)?;
+        self.offset.restore(data)?;
+        self.inputs.restore(data)?;
+        self.switches.ships.restore(data)?;
+        self.switches.early_bonus.restore(data)?;
+        self.switches.hide_coin_info.restore(data)?;
+        self.sound.restore(data)?;
+        self.beam.restore(data)?;
+        self.pending.restore(data)
+    }
+}
+
+impl<C: BorrowMut<Invaders>> Machine<Invaders, C> {
+    /// Starts the video timing, which the machine needs once before it runs: at the end of each
+    /// scan line, a scheduled event moves the beam along by the cycles that have passed and
+    /// passes the video interrupt to the processor if the beam has reached it. An interrupt that
+    /// arrives while interrupts are disabled is tried again at the end of each line until they
+    /// are enabled, unless the next one replaces it first. Cancelling the returned event stops
+    /// the beam.
+    pub fn start_video(&mut self) -> Event {
+        let mut last = self.clock;
+        self.schedule_every(LINE as u64, move |machine| {
+            let now = machine.clock();
+            machine.advance(now.saturating_sub(last) as usize);
+            last = now;
+            if let Some(vector) = machine.pending {
+                if matches!(machine.reset_to(vector), Ok(true)) { machine.pending = None; }
+            }
+        })
+    }
+
+    /// Runs until the beam returns to the top of the raster, so that a whole frame has been
+    /// drawn when it started from the top, or the processor can't go on.
+    pub fn run_frame(&mut self) -> Summary {
+        let mut before = self.beam;
+        self.drive(None, |machine, _| {
+            let wrapped = machine.beam < before;
+            before = machine.beam;
+            wrapped.then_some(Stop::Budget)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
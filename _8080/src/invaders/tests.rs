@@ -0,0 +1,89 @@
+use super::*;
+use crate::assembler::assemble;
+
+#[test]
+fn board() {
+    let program = assemble("
+MIDS    EQU     2000H
+BLANKS  EQU     2001H
+SHIFTED EQU     2002H
+INPUT1  EQU     2003H
+INPUT2  EQU     2004H
+
+        ORG     0
+        JMP     START
+        ORG     8
+        JMP     MID
+        ORG     10H
+        JMP     BLANK
+MID:    PUSH    PSW
+        LDA     MIDS
+        INR     A
+        STA     MIDS
+        POP     PSW
+        EI
+        RET
+BLANK:  PUSH    PSW
+        LDA     BLANKS
+        INR     A
+        STA     BLANKS
+        POP     PSW
+        EI
+        RET
+START:  LXI     SP,2400H
+        MVI     A,0ABH
+        OUT     4
+        MVI     A,0CDH
+        OUT     4
+        MVI     A,5
+        OUT     2
+        IN      3
+        STA     SHIFTED
+        IN      1
+        STA     INPUT1
+        IN      2
+        STA     INPUT2
+        MVI     A,3
+        OUT     3
+        MVI     A,1
+        OUT     3
+        OUT     5
+        STA     2400H
+        MVI     A,80H
+        STA     3FFFH
+        MVI     A,42H
+        STA     6010H
+        EI
+LOOP:   JMP     LOOP
+    ").unwrap();
+    let mut machine = Machine::new(Invaders::new(&program.image));
+    machine.press(Button::Coin, true);
+    machine.press(Button::Fire2, true);
+    machine.press(Button::Fire2, false);
+    machine.set_switches(Switches { ships: 5, early_bonus: true, ..Switches::default() });
+    machine.start_video();
+    for _ in 0..3 { assert_eq!(machine.run_frame().stop, Stop::Budget); }
+    let read = |machine: &Machine<Invaders, Invaders>, address| machine.read(Wrapping(address)).0;
+    assert_eq!([read(&machine, 0x2000), read(&machine, 0x2001)], [3, 3]);
+    assert!(machine.run_for(FRAME / 2).cycles >= FRAME / 2);
+    assert_eq!([read(&machine, 0x2000), read(&machine, 0x2001)], [4, 3]);
+    machine.run_frame();
+    assert_eq!([read(&machine, 0x2002), read(&machine, 0x2003), read(&machine, 0x2004)], [0xB5, 0x09, 0x0A]);
+    assert_eq!(read(&machine, 0x2010), 0x42);
+    machine.write(Wrapping(0), Wrapping(0));
+    assert_eq!(read(&machine, 0), 0xC3);
+    assert_eq!(machine.line(), 0);
+
+    assert_eq!(machine.sounds(), [(Sound::Ufo, true), (Sound::Shot, true), (Sound::Shot, false), (Sound::Fleet(1), true)]);
+    assert!(machine.playing(Sound::Ufo) && !machine.playing(Sound::Shot));
+    assert_eq!(machine.sounds(), []);
+
+    let frame = machine.frame();
+    assert_eq!(frame.len(), WIDTH * HEIGHT * 4);
+    let lit: Vec<_> = frame.chunks_exact(4).enumerate()
+        .filter(|(_, pixel)| pixel[0] != 0)
+        .map(|(index, _)| (index % WIDTH, index / WIDTH))
+        .collect();
+    assert_eq!(lit, [(WIDTH - 1, 0), (0, HEIGHT - 1)]);
+    assert_eq!(frame[3], 0xFF);
+}
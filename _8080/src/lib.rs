@@ -23,6 +23,12 @@
 //!
 //! The package assumes that you will just use the core opaquely, but the `"open"` feature exposes
 //! several debug features so that you can examine what is happening with the execution directly.
+//!
+//! Machines whose Harness implements `Snapshot` can be saved and restored with `save_state` and
+//! `load_state`; the `"serde"` feature lets the resulting `SaveState` go through serde as well.
+//! They can also record a `Movie` of their input and interrupts, which plays the run back exactly.
+//! Any machine can keep a `Trace` of the instructions it executes, to compare with another
+//! emulator's log, and a `Debugger` runs one with breakpoints, watchpoints and stepping.
 
 #![no_std]
 #![feature(generic_arg_infer)]
@@ -34,12 +40,12 @@ extern crate disclose;
 #[cfg(feature="std")]
 mod foundation {
     extern crate std;
-    pub use std::{any, array, borrow, boxed, convert, fmt, num, ops, rc, result, slice, string, sync, vec};
+    pub use std::{any, array, borrow, boxed, collections, convert, fmt, num, ops, rc, result, slice, string, sync, vec};
 }
 #[cfg(not(feature="std"))]
 mod foundation {
     extern crate alloc;
-    pub use alloc::{boxed, rc, string, vec};
+    pub use alloc::{boxed, collections, rc, string, vec};
     pub use core::{array, borrow, convert, fmt, num, result, ops, slice, any};
 }
 
@@ -52,6 +58,20 @@ mod prelude {
 use self::{prelude::*, rc::Rc};
 
 mod chip;
+mod run;
+
+#[cfg(feature="std")]
+pub mod altair;
+pub mod assembler;
+pub mod cpm;
+pub mod debug;
+pub mod device;
+pub mod image;
+pub mod invaders;
+pub mod memory;
+pub mod movie;
+pub mod snapshot;
+pub mod trace;
 
 /// The cpp mod contains FFI exports to create and access Machine objects in C++.
 #[cfg(feature="_cpp")]
@@ -72,6 +92,10 @@ mod bits {
 
 #[cfg(any(feature="open", doc))]
 pub use crate::chip::{State, access::*, opcode::{self as op, Op::{self, *}, Flag::*, Test::*}};
+#[cfg(any(feature="open", doc))]
+pub use crate::chip::opcode::{disassemble, Formatted, Line, Syntax};
+pub use crate::chip::timing::{BusCycle, CycleKind};
+pub use crate::{debug::Debugger, device::{Device, PortBus, Vector}, memory::MemoryMap, movie::Movie, run::{Event, Stop, Summary}, snapshot::{SaveState, Snapshot}, trace::Trace};
 
 /// The Harness trait is the core of using this package; the `Machine` struct will use a
 /// type of your choosing that the chip can use to read 8-bit or 16-bit values from 16-bit
@@ -131,6 +155,17 @@ pub trait Harness {
     #[cfg(any(feature="open", doc))]
     fn did_execute(&mut self, client: &chip::State, did: chip::opcode::Op) -> Result<Option<chip::opcode::Op>, String> { let _ = (client, did); Ok( None ) }
 
+    /// This method reports each machine cycle of an instruction to the Harness as it happens,
+    /// while the Machine is in its T-state-accurate mode (see `Machine::set_cycle_accurate`):
+    /// what the cycle does, the address and byte on the bus, and how many T-states into the
+    /// instruction it starts. A Harness that emulates contention for the bus, such as by video
+    /// circuits or a DMA controller, can return a number of wait states to hold the processor
+    /// for before the next cycle; they are added to the cycles the instruction takes.
+    ///
+    /// You don't have to supply this method; it defaults to ignoring the cycle and adding no
+    /// wait states.
+    fn bus_cycle(&mut self, cycle: chip::timing::BusCycle) -> raw::u8 { let _ = cycle; 0 }
+
     /// You don't usually need to implement this method; it enables downcasting in cases where a
     /// Machine stores a `dyn Harness` trait object.
     fn as_any(&self) -> Option<&dyn any::Any> { None }
@@ -149,6 +184,7 @@ impl<H: Harness + ?Sized, C: BorrowMut<H>> Harness for Shared<H, C> {
 	fn write_word(&mut self, address: u16, value: u16) { (**self).borrow_mut().0.borrow_mut().write_word(address, value) }
 	fn input(&mut self, port: raw::u8) -> u8 { (**self).borrow_mut().0.borrow_mut().input(port) }
 	fn output(&mut self, port: raw::u8, value: u8) { (**self).borrow_mut().0.borrow_mut().output(port, value) }
+	fn bus_cycle(&mut self, cycle: chip::timing::BusCycle) -> raw::u8 { (**self).borrow_mut().0.borrow_mut().bus_cycle(cycle) }
 	#[cfg(feature="cfg")]
 	fn did_execute(&mut self, client: &chip::State, did: chip::opcode::Op) -> Result<Option<chip::opcode::Op>, string::String> {
 		(**self).borrow_mut().0.borrow_mut().did_execute(client, did)
@@ -166,6 +202,7 @@ impl<H: Harness + ?Sized, C: BorrowMut<H>> Harness for Synced<H, C> {
 	fn write_word(&mut self, address: u16, value: u16) { (**self).lock().unwrap().0.borrow_mut().write_word(address, value) }
 	fn input(&mut self, port: raw::u8) -> u8 { (**self).lock().unwrap().0.borrow_mut().input(port) }
 	fn output(&mut self, port: raw::u8, value: u8) { (**self).lock().unwrap().0.borrow_mut().output(port, value) }
+	fn bus_cycle(&mut self, cycle: chip::timing::BusCycle) -> raw::u8 { (**self).lock().unwrap().0.borrow_mut().bus_cycle(cycle) }
 	#[cfg(feature="cfg")]
 	fn did_execute(&mut self, client: &chip::State, did: chip::opcode::Op) -> Result<Option<chip::opcode::Op>, string::String> {
 		(**self).lock().unwrap().0.borrow_mut().did_execute(client, did)
@@ -192,11 +229,17 @@ pub struct Machine<H: Harness + ?Sized, C: BorrowMut<H>> {
     chip: chip::State,
     board: C,
     _grammar: PhantomData<H>,
+    clock: u64,
+    events: run::Schedule<H, C>,
+    timed: bool,
+    tape: Option<Box<movie::Reel>>,
+    trace: Option<Box<trace::Trace>>,
+    watches: Option<Box<debug::Watches>>,
 }
 
 impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
 	pub fn new(board: C) -> Self {
-		Self { board, chip: chip::State::new(), _grammar: PhantomData::default() }
+		Self { board, chip: chip::State::new(), _grammar: PhantomData::default(), clock: 0, events: run::Schedule::new(), timed: false, tape: None, trace: None, watches: None }
 	}
 
 	fn split_mut(&mut self) -> (&mut chip::State, &mut H) { (&mut self.chip, self.board.borrow_mut() )}
@@ -0,0 +1,249 @@
+//! A Harness assembled from regions of memory, for declaring the memory of a board rather than
+//! hand-coding its `read` and `write` methods.
+//!
+//! A `MemoryMap` starts out as open bus, with nothing at any address, and each region declared
+//! on it covers part of the address space: RAM, ROM that ignores writes, a mirror that repeats
+//! another range, open bus that reads as a fixed value, or a window onto one of several banks
+//! of RAM. A region declared later covers any earlier ones where they overlap, so a map can
+//! start with a broad region and then carve smaller ones out of it.
+//!
+//! A banked window shows one bank at a time. The bank can be chosen with `select`, or by the
+//! program through an `OUT` instruction to the window's port, if it has one; the number written
+//! chooses the bank (modulo the number of banks). The selection is part of the map's state, so
+//! it is kept along with the contents of memory when a map is cloned.
+
+use crate::prelude::{*, vec::Vec};
+use crate::image::bounds;
+use crate::snapshot::{self, Snapshot};
+use core::ops::RangeBounds;
+
+/// The value read from addresses where nothing is mapped, unless the map says otherwise.
+const FLOATING: raw::u8 = 0xFF;
+
+/// What a region of memory does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Region {
+    Ram(Box<[raw::u8]>),
+    Rom(Box<[raw::u8]>),
+    /// Repeats the memory from `target` through `target + size - 1` across the region.
+    Mirror { target: raw::u16, size: usize },
+    Open(raw::u8),
+    /// Shows one of several banks of RAM, chosen by `current`; `OUT` instructions to `port`
+    /// change the choice.
+    Banked { banks: Vec<Box<[raw::u8]>>, current: usize, port: Option<raw::u8> },
+}
+
+/// A region along with the addresses it covers, from `start` through `end - 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Window {
+    start: usize,
+    end: usize,
+    region: Region,
+}
+
+/// A Harness whose memory is made of declared regions. Its ports are unconnected, apart from
+/// those that select banks: input reads as open bus and other output is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    windows: Vec<Window>,
+    open: raw::u8,
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self { Self::new() }
+}
+
+impl MemoryMap {
+    /// Creates a map with nothing mapped, where every address reads as 0xFF.
+    pub fn new() -> Self { Self { windows: Vec::new(), open: FLOATING } }
+
+    fn with(mut self, range: impl RangeBounds<raw::u16>, region: impl FnOnce(usize) -> Region) -> Self {
+        let (start, end) = bounds(range);
+        if start < end {
+            let region = region(end - start);
+            self.windows.push(Window { start, end, region });
+        }
+        self
+    }
+
+    /// Maps zero-filled RAM to a range of addresses.
+    pub fn ram(self, range: impl RangeBounds<raw::u16>) -> Self {
+        self.with(range, |size| Region::Ram(vec![0; size].into_boxed_slice()))
+    }
+
+    /// Maps ROM holding `contents` to the addresses from `start`; the contents must fit below
+    /// the end of the address space. Writes to it are ignored.
+    pub fn rom(self, start: raw::u16, contents: &[raw::u8]) -> Self {
+        assert!(start as usize + contents.len() <= 0x10000, "ROM extends beyond 64K");
+        let rom = Region::Rom(contents.into());
+        match contents.len() {
+            0 => self,
+            size => self.with(start..=start + (size - 1) as raw::u16, |_| rom),
+        }
+    }
+
+    /// Makes a range of addresses repeat the memory at `target`: the first address of `range`
+    /// reaches the first address of `target`, and so on, starting over from the beginning of
+    /// `target` every time it runs out. Mirrors reach the regions under `target`, not other mirrors.
+    pub fn mirror(self, range: impl RangeBounds<raw::u16>, target: impl RangeBounds<raw::u16>) -> Self {
+        let (from, to) = bounds(target);
+        match to.checked_sub(from) {
+            Some(size @ 1..) => self.with(range, |_| Region::Mirror { target: from as raw::u16, size }),
+            _ => self,
+        }
+    }
+
+    /// Makes a range of addresses read as `value` and ignore writes, as an unconnected bus does.
+    pub fn open_bus(self, range: impl RangeBounds<raw::u16>, value: raw::u8) -> Self {
+        self.with(range, |_| Region::Open(value))
+    }
+
+    /// Maps a window onto `count` banks of zero-filled RAM to a range of addresses, starting with
+    /// bank 0. If `port` is given, output to that port selects the bank.
+    pub fn banked(self, range: impl RangeBounds<raw::u16>, count: usize, port: Option<raw::u8>) -> Self {
+        match count {
+            0 => self,
+            _ => self.with(range, |size| Region::Banked { banks: vec![vec![0; size].into_boxed_slice(); count], current: 0, port }),
+        }
+    }
+
+    /// Sets the value read from addresses that no region covers, and from every port.
+    pub fn unmapped(mut self, value: raw::u8) -> Self {
+        self.open = value;
+        self
+    }
+
+    /// The window covering an address, by its position in the map, and the address's offset
+    /// into it, after following a mirror to the region under it.
+    fn locate(&self, address: raw::u16) -> Option<(usize, usize)> {
+        let mut address = address as usize;
+        for follow in [true, false] {
+            let index = self.windows.iter().rposition(|window| (window.start..window.end).contains(&address))?;
+            let window = &self.windows[index];
+            let offset = address - window.start;
+            match window.region {
+                Region::Mirror { target, size } if follow => address = (target as usize + offset % size) & 0xFFFF,
+                Region::Mirror { .. } => return None,
+                _ => return Some((index, offset)),
+            }
+        }
+        None
+    }
+
+    /// Finds the byte of RAM (or of ROM, if `rom` is set) at an address.
+    fn cell(&mut self, address: raw::u16, rom: bool) -> Option<&mut raw::u8> {
+        let (index, offset) = self.locate(address)?;
+        match &mut self.windows[index].region {
+            Region::Ram(memory) => memory.get_mut(offset),
+            Region::Rom(memory) if rom => memory.get_mut(offset),
+            Region::Banked { banks, current, .. } => banks[*current].get_mut(offset),
+            _ => None,
+        }
+    }
+
+    /// The banked window covering an address, without following mirrors.
+    fn banks(&mut self, address: raw::u16) -> Option<(&mut usize, usize)> {
+        let window = self.windows.iter_mut().rev().find(|window| (window.start..window.end).contains(&(address as usize)))?;
+        match &mut window.region {
+            Region::Banked { banks, current, .. } => Some((current, banks.len())),
+            _ => None,
+        }
+    }
+
+    /// Switches the banked window covering `address` to `bank`. Returns `false`, without
+    /// switching anything, if there is no banked window there or it has no such bank.
+    pub fn select(&mut self, address: raw::u16, bank: usize) -> bool {
+        match self.banks(address) {
+            Some((current, count)) if bank < count => { *current = bank; true }
+            _ => false,
+        }
+    }
+
+    /// The bank shown by the banked window covering `address`, if there is one.
+    pub fn bank(&self, address: raw::u16) -> Option<usize> {
+        let window = self.windows.iter().rev().find(|window| (window.start..window.end).contains(&(address as usize)))?;
+        match window.region {
+            Region::Banked { current, .. } => Some(current),
+            _ => None,
+        }
+    }
+
+    /// Stores data from `to` onward into RAM or ROM alike, as a ROM programmer would; bytes that
+    /// land on open bus or beyond the end of memory are dropped.
+    pub fn program(&mut self, to: raw::u16, data: &[raw::u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            let Some(address) = (to as usize).checked_add(offset).filter(|address| *address < 0x10000) else { break };
+            if let Some(cell) = self.cell(address as raw::u16, true) {
+                *cell = *byte;
+            }
+        }
+    }
+}
+
+/// Saves the contents of RAM and of every bank, and the bank each window shows. ROM and the
+/// layout of the map aren't saved, so a snapshot is restored into a map declared the same way.
+impl Snapshot for MemoryMap {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        for window in &self.windows {
+            match &window.region {
+                Region::Ram(memory) => memory.save(out),
+                Region::Banked { banks, current, .. } => {
+                    current.save(out);
+                    banks.iter().for_each(|bank| bank.save(out));
+                }
+                _ => (),
+            }
+        }
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), snapshot::Error> {
+        for window in &mut self.windows {
+            match &mut window.region {
+                Region::Ram(memory) => memory.restore(data)?,
+                Region::Banked { banks, current, .. } => {
+                    current.restore(data)?;
+                    if *current >= banks.len() { return Err(snapshot::Error::Mismatch); }
+                    banks.iter_mut().try_for_each(|bank| bank.restore(data))?;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Harness for MemoryMap {
+    fn read(&self, from: u16) -> u8 {
+        let Some((index, offset)) = self.locate(from.0) else { return Wrapping(self.open) };
+        Wrapping(match &self.windows[index].region {
+            Region::Ram(memory) | Region::Rom(memory) => memory[offset],
+            Region::Open(value) => *value,
+            Region::Banked { banks, current, .. } => banks[*current][offset],
+            Region::Mirror { .. } => self.open,
+        })
+    }
+    fn read_word(&self, from: u16) -> u16 {
+        Wrapping(raw::u16::from_le_bytes([self.read(from).0, self.read(from + Wrapping(1)).0]))
+    }
+    fn write(&mut self, to: u16, value: u8) {
+        if let Some(cell) = self.cell(to.0, false) {
+            *cell = value.0;
+        }
+    }
+    fn write_word(&mut self, to: u16, value: u16) {
+        let [low, high] = value.0.to_le_bytes();
+        self.write(to, Wrapping(low));
+        self.write(to + Wrapping(1), Wrapping(high));
+    }
+    fn input(&mut self, _port: raw::u8) -> u8 { Wrapping(self.open) }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        for window in &mut self.windows {
+            if let Region::Banked { banks, current, port: Some(select) } = &mut window.region {
+                if *select == port { *current = value.0 as usize % banks.len(); }
+            }
+        }
+    }
+    fn as_any(&self) -> Option<&dyn any::Any> { Some(self) }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,94 @@
+use super::*;
+
+fn peek(map: &MemoryMap, address: raw::u16) -> raw::u8 { map.read(Wrapping(address)).0 }
+
+#[test]
+fn regions() {
+    let mut map = MemoryMap::new()
+        .ram(..)
+        .rom(0x0000, &[1, 2, 3, 4])
+        .open_bus(0x8000..0x9000, 0x00)
+        .ram(0x8800..=0x88FF);
+    assert_eq!([peek(&map, 0), peek(&map, 3), peek(&map, 4)], [1, 4, 0]);
+    map.write(Wrapping(0x0001), Wrapping(9));
+    map.write(Wrapping(0x0004), Wrapping(9));
+    assert_eq!([peek(&map, 1), peek(&map, 4)], [2, 9]);
+    map.write(Wrapping(0x8000), Wrapping(9));
+    map.write(Wrapping(0x8800), Wrapping(9));
+    assert_eq!([peek(&map, 0x8000), peek(&map, 0x8800), peek(&map, 0x8FFF)], [0, 9, 0]);
+    map.write_word(Wrapping(0xFFFF), Wrapping(0x1234));
+    assert_eq!(peek(&map, 0xFFFF), 0x34);
+    assert_eq!(map.read_word(Wrapping(0xFFFF)).0, 0x0134);
+    assert_eq!(map.read_word(Wrapping(0x0002)).0, 0x0403);
+
+    map.program(0x0002, &[7, 7, 7]);
+    assert_eq!(map.read_word(Wrapping(0x0002)).0, 0x0707);
+    assert_eq!(peek(&map, 0x0004), 7);
+}
+
+#[test]
+fn mirrors() {
+    let mut map = MemoryMap::new()
+        .rom(0x0000, &[0x10, 0x20])
+        .ram(0x2000..0x2400)
+        .mirror(0x2400..0x4000, 0x2000..0x2400)
+        .mirror(0x6000..0x6010, 0x0000..0x0002)
+        .mirror(0x7000..0x7001, 0x6000..0x6001);
+    map.write(Wrapping(0x2401), Wrapping(5));
+    map.write(Wrapping(0x3C02), Wrapping(6));
+    assert_eq!([peek(&map, 0x2001), peek(&map, 0x2002), peek(&map, 0x2801)], [5, 6, 5]);
+    assert_eq!([peek(&map, 0x600E), peek(&map, 0x600F)], [0x10, 0x20]);
+    map.write(Wrapping(0x6000), Wrapping(0));
+    assert_eq!(peek(&map, 0x0000), 0x10);
+    map.program(0x6001, &[0x21]);
+    assert_eq!(peek(&map, 0x0001), 0x21);
+    assert_eq!(peek(&map, 0x7000), 0xFF);
+}
+
+#[test]
+fn open_bus() {
+    let mut map = MemoryMap::new().unmapped(0x3F).ram(0x0000..0x0100);
+    assert_eq!([peek(&map, 0x00FF), peek(&map, 0x0100)], [0, 0x3F]);
+    map.write(Wrapping(0x0100), Wrapping(1));
+    assert_eq!(peek(&map, 0x0100), 0x3F);
+    assert_eq!(map.input(0x10).0, 0x3F);
+    let empty = MemoryMap::new().ram(0x10..0x10).mirror(0x0000..0x0100, 0x0200..0x0200);
+    assert_eq!(empty, MemoryMap::new());
+}
+
+#[test]
+fn banks() {
+    let mut map = MemoryMap::new()
+        .ram(..)
+        .banked(0xC000..0xE000, 3, Some(0x40))
+        .mirror(0xE000..0xE100, 0xC000..0xC100);
+    assert_eq!(map.bank(0xC000), Some(0));
+    assert_eq!(map.bank(0xBFFF), None);
+    map.write(Wrapping(0xC000), Wrapping(1));
+    assert!(map.select(0xDFFF, 2));
+    assert_eq!(peek(&map, 0xC000), 0);
+    map.write(Wrapping(0xE000), Wrapping(3));
+    assert_eq!(peek(&map, 0xC000), 3);
+    assert!(!map.select(0xC000, 3));
+    assert!(!map.select(0xE000, 0));
+    assert_eq!(map.bank(0xC000), Some(2));
+
+    map.output(0x41, Wrapping(0));
+    assert_eq!(map.bank(0xC000), Some(2));
+    map.output(0x40, Wrapping(0));
+    assert_eq!(peek(&map, 0xC000), 1);
+    map.output(0x40, Wrapping(5));
+    assert_eq!(map.bank(0xC000), Some(2));
+    let saved = map.clone();
+    map.output(0x40, Wrapping(1));
+    assert_eq!(saved.bank(0xC000), Some(2));
+    assert_eq!(peek(&saved, 0xC000), 3);
+
+    let mut machine = Machine::new(MemoryMap::new().ram(..).banked(0x8000..0x8001, 2, Some(0x10)));
+    machine.program(0x0000, &[0x3E, 0x01, 0xD3, 0x10, 0x3E, 0x55, 0x32, 0x00, 0x80, 0x76]);
+    for _ in 0..5 { let _ = machine.execute(); }
+    assert_eq!(machine.bank(0x8000), Some(1));
+    assert_eq!(machine.read(Wrapping(0x8000)).0, 0x55);
+    machine.select(0x8000, 0);
+    assert_eq!(machine.read(Wrapping(0x8000)).0, 0);
+}
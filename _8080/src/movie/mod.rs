@@ -0,0 +1,297 @@
+//! Movies, which record everything a machine takes from outside (its input and its interrupts)
+//! so that a run can be played back exactly, for regression tests and bug reports.
+//!
+//! While a machine records, every value its Harness returns from `input` and every interrupt
+//! the processor accepts is kept, tagged with the cycle clock: input with the clock when the
+//! instruction that read it started, and an interrupt with the clock when it came. A movie
+//! starts from a save-state of the machine, and every so often it keeps a hash of what the
+//! program can see: the processor, the clock and the 64K of memory. The rest of the Harness's
+//! state isn't hashed, since that is where the input comes from, and playing back doesn't
+//! bring it along.
+//!
+//! Playing a movie back restores its save-state and feeds the recorded input to the program in
+//! place of what the Harness returns; the Harness is still asked, so that reading a port has
+//! the same effect on it. Interrupts come at the cycles they were recorded at: one the front
+//! end raises at that cycle goes through, any other is refused, and a recorded one that nothing
+//! raises is delivered before the next instruction. At each recorded hash the state is hashed
+//! again. The first difference, or input from a port the movie didn't record, is kept as a
+//! `Desync`, since from there on the run has gone its own way. Past the end of the movie, the
+//! program gets its input from the Harness again.
+
+use crate::prelude::{*, vec::Vec};
+use crate::chip::opcode::Op;
+use crate::snapshot::{Error, SaveState, Snapshot};
+
+/// The signature at the start of a movie written by `Movie::to_bytes`.
+const SIGNATURE: [raw::u8; 4] = *b"L80M";
+/// The version of the movie format this version of the crate writes.
+pub const VERSION: raw::u16 = 1;
+
+/// Something a machine took from outside while recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Record {
+    /// The Harness returned `value` for input from `port`.
+    Input { port: raw::u8, value: raw::u8 },
+    /// The processor accepted an interrupt carrying this operation.
+    Interrupt(Op),
+}
+
+/// Where a played-back run first went differently from the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Desync {
+    /// The machine's state hashed differently at this cycle, or the machine didn't stop
+    /// between instructions there.
+    State { cycle: u64 },
+    /// The program read from `port` at `cycle`, where the movie has no input from that port.
+    Input { cycle: u64, port: raw::u8 },
+    /// The processor refused the interrupt recorded at `cycle`, since its interrupts were off.
+    Interrupt { cycle: u64 },
+}
+
+/// A recorded run of a machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    /// The machine when recording started.
+    pub start: SaveState,
+    /// The input and interrupts, in order, each with the cycle it came at.
+    pub events: Vec<(u64, Record)>,
+    /// The hashes of the machine's state, each with the cycle it was taken at.
+    pub hashes: Vec<(u64, u64)>,
+    /// The cycle recording stopped at.
+    pub end: u64,
+}
+
+impl Movie {
+    /// Writes the movie in the crate's binary format, with its starting save-state as
+    /// `SaveState::to_bytes` writes it.
+    pub fn to_bytes(&self) -> Vec<raw::u8> {
+        let mut out = Vec::from(SIGNATURE);
+        VERSION.save(&mut out);
+        self.start.to_bytes().save(&mut out);
+        self.end.save(&mut out);
+        self.events.len().save(&mut out);
+        for (cycle, record) in &self.events {
+            cycle.save(&mut out);
+            match record {
+                Record::Input { port, value } => out.extend([0, *port, *value]),
+                Record::Interrupt(op) => {
+                    out.push(1);
+                    let code: [raw::u8; 4] = (*op).into();
+                    out.extend(code);
+                }
+            }
+        }
+        self.hashes.len().save(&mut out);
+        for (cycle, hash) in &self.hashes {
+            cycle.save(&mut out);
+            hash.save(&mut out);
+        }
+        out
+    }
+
+    /// Reads a movie written by `to_bytes`, in this version of the format or an earlier one.
+    pub fn from_bytes(mut data: &[raw::u8]) -> Result<Self, Error> {
+        if !data.starts_with(&SIGNATURE) { return Err(Error::BadSignature); }
+        data = &data[SIGNATURE.len()..];
+        let mut version: raw::u16 = 0;
+        version.restore(&mut data)?;
+        if !(1..=VERSION).contains(&version) { return Err(Error::Version(version)); }
+        let mut start: Vec<raw::u8> = Vec::new();
+        start.restore(&mut data)?;
+        let mut movie = Movie { start: SaveState::from_bytes(&start)?, events: Vec::new(), hashes: Vec::new(), end: 0 };
+        movie.end.restore(&mut data)?;
+        let mut count = 0usize;
+        count.restore(&mut data)?;
+        for _ in 0..count {
+            let (mut cycle, mut kind) = (0u64, 0u8);
+            cycle.restore(&mut data)?;
+            kind.restore(&mut data)?;
+            let record = match kind {
+                0 => {
+                    let mut bytes = [0u8; 2];
+                    bytes.restore(&mut data)?;
+                    Record::Input { port: bytes[0], value: bytes[1] }
+                }
+                1 => {
+                    let mut bytes = [0u8; 4];
+                    bytes.restore(&mut data)?;
+                    let [len, code @ ..] = bytes;
+                    let code = code.into_iter().take(len as usize).map(Wrapping);
+                    match Op::extract(code) {
+                        Ok((op, size)) if size == len as usize => Record::Interrupt(op),
+                        _ => return Err(Error::Mismatch),
+                    }
+                }
+                _ => return Err(Error::Mismatch),
+            };
+            movie.events.push((cycle, record));
+        }
+        count.restore(&mut data)?;
+        for _ in 0..count {
+            let (mut cycle, mut hash) = (0u64, 0u64);
+            cycle.restore(&mut data)?;
+            hash.restore(&mut data)?;
+            movie.hashes.push((cycle, hash));
+        }
+        match data.is_empty() {
+            true => Ok(movie),
+            false => Err(Error::Mismatch),
+        }
+    }
+}
+
+/// Hashes the processor, the clock and memory with 64-bit FNV-1a, which gives the same hash
+/// on any host.
+fn hash<H: Harness + ?Sized, C: BorrowMut<H>>(machine: &Machine<H, C>) -> u64 {
+    let mut state = Vec::new();
+    machine.chip.save(&mut state);
+    machine.clock.save(&mut state);
+    let memory = (0..=0xFFFF).map(|address| machine.read(Wrapping(address)).0);
+    state.into_iter().chain(memory)
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+/// The movie a machine is recording or playing back, and how far it has got.
+#[derive(Debug)]
+pub(crate) struct Reel {
+    movie: Movie,
+    playing: bool,
+    /// The cycles between hashes, while recording, and the cycle the next is due at.
+    every: u64,
+    due: u64,
+    /// The positions of the next event and the next hash, while playing back.
+    next: usize,
+    checked: usize,
+    desync: Option<Desync>,
+}
+
+impl Reel {
+    fn lose(&mut self, desync: Desync) {
+        self.desync.get_or_insert(desync);
+    }
+
+    /// The next recorded interrupt, while playing back.
+    fn cue(&self) -> Option<(u64, Op)> {
+        match self.movie.events.get(self.next) {
+            Some(&(cycle, Record::Interrupt(op))) if self.playing => Some((cycle, op)),
+            _ => None,
+        }
+    }
+
+    /// Records input the Harness returned at `clock`, or gives the recorded input in its place.
+    pub(crate) fn input(&mut self, clock: u64, port: raw::u8, value: raw::u8) -> raw::u8 {
+        if !self.playing {
+            self.movie.events.push((clock, Record::Input { port, value }));
+            return value;
+        }
+        match self.movie.events.get(self.next) {
+            Some(&(cycle, Record::Input { port: recorded, value })) if cycle == clock && recorded == port => {
+                self.next += 1;
+                value
+            }
+            _ if clock >= self.movie.end => value,
+            _ => {
+                self.lose(Desync::Input { cycle: clock, port });
+                value
+            }
+        }
+    }
+}
+
+impl<H: Harness + Snapshot + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Starts recording a movie from the machine as it is now, keeping a hash of its state
+    /// every `every` cycles (at least 1). Recording again, or playing a movie, drops the movie
+    /// being recorded.
+    pub fn record_movie(&mut self, every: u64) {
+        let movie = Movie { start: self.save_state(), events: Vec::new(), hashes: Vec::new(), end: self.clock };
+        let reel = Reel { movie, playing: false, every: every.max(1), due: self.clock, next: 0, checked: 0, desync: None };
+        self.tape = Some(Box::new(reel));
+    }
+
+    /// Restores the starting state of a movie and starts playing it back.
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), Error> {
+        self.load_state(&movie.start)?;
+        let reel = Reel { movie, playing: true, every: 0, due: 0, next: 0, checked: 0, desync: None };
+        self.tape = Some(Box::new(reel));
+        Ok(())
+    }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Stops recording or playing back, and returns the movie; a recorded one ends at the
+    /// current cycle.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        let Reel { mut movie, playing, .. } = *self.tape.take()?;
+        if !playing { movie.end = self.clock; }
+        Some(movie)
+    }
+
+    /// The movie being recorded so far, or being played back.
+    pub fn movie(&self) -> Option<&Movie> { self.tape.as_ref().map(|tape| &tape.movie) }
+
+    /// Whether the machine is playing a movie back, as opposed to recording one or neither.
+    pub fn is_playing(&self) -> bool { self.tape.as_ref().is_some_and(|tape| tape.playing) }
+
+    /// Where the movie being played back first went differently, if it has.
+    pub fn desync(&self) -> Option<Desync> { self.tape.as_ref().and_then(|tape| tape.desync) }
+
+    /// The cycle of the next recorded interrupt, while playing back, which wakes a halted
+    /// processor as an event would.
+    pub(crate) fn cue(&self) -> Option<u64> { self.tape.as_ref()?.cue().map(|(cycle, _)| cycle) }
+
+    /// Whether an interrupt raised now may go ahead: while playing back, only the one
+    /// recorded next, at this cycle, may.
+    pub(crate) fn admit(&mut self, op: Op) -> bool {
+        let clock = self.clock;
+        match self.tape.as_deref_mut() {
+            Some(reel) if reel.playing => match reel.cue() {
+                Some((cycle, recorded)) if cycle == clock && recorded == op => {
+                    reel.next += 1;
+                    true
+                }
+                _ => false,
+            },
+            _ => true,
+        }
+    }
+
+    /// Records an interrupt raised at `cycle`, if the processor accepted it, or notes that a
+    /// recorded one was refused.
+    pub(crate) fn log_interrupt(&mut self, op: Op, cycle: u64, accepted: bool) {
+        let Some(reel) = self.tape.as_deref_mut() else { return };
+        match (reel.playing, accepted) {
+            (false, true) => reel.movie.events.push((cycle, Record::Interrupt(op))),
+            (true, false) => reel.lose(Desync::Interrupt { cycle }),
+            _ => (),
+        }
+    }
+
+    /// Brings the tape up to the clock before an instruction: while playing back, delivers the
+    /// recorded interrupts that are due, and hashes the state if a hash is due.
+    pub(crate) fn follow_tape(&mut self) {
+        while let Some((cycle, op)) = self.tape.as_ref().and_then(|tape| tape.cue()).filter(|(cycle, _)| *cycle <= self.clock) {
+            if let Some(reel) = self.tape.as_deref_mut() { reel.next += 1; }
+            let accepted = self.accept(op);
+            self.log_interrupt(op, cycle, accepted);
+        }
+        let Some(reel) = self.tape.as_deref() else { return };
+        let due = match reel.playing {
+            true => reel.movie.hashes.get(reel.checked).map(|(cycle, _)| *cycle),
+            false => Some(reel.due),
+        };
+        if due.is_none_or(|due| self.clock < due) { return; }
+        let (hash, clock) = (hash(self), self.clock);
+        let Some(reel) = self.tape.as_deref_mut() else { return };
+        if reel.playing {
+            let (cycle, expected) = reel.movie.hashes[reel.checked];
+            reel.checked += 1;
+            if (cycle, expected) != (clock, hash) { reel.lose(Desync::State { cycle }); }
+        } else {
+            reel.movie.hashes.push((clock, hash));
+            reel.due += (clock - reel.due) / reel.every * reel.every + reel.every;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,84 @@
+use super::*;
+use crate::{SimpleBoard, assembler::assemble, chip::access::Register};
+
+/// Adds up input from port 1 into B, counting interrupts in C, and halts every 8 reads to
+/// wait for one.
+fn machine() -> Machine<SimpleBoard, SimpleBoard> {
+    let mut machine = Machine::new(SimpleBoard::default());
+    let image = assemble("
+        JMP     START
+        ORG     8
+        INR     C
+        EI
+        RET
+START:  LXI     SP,100H
+        EI
+AGAIN:  MVI     D,8
+LOOP:   IN      1
+        ADD     B
+        MOV     B,A
+        DCR     D
+        JNZ     LOOP
+        HLT
+        JMP     AGAIN
+    ").unwrap().image;
+    for (address, byte) in image.iter().enumerate() {
+        machine.write(Wrapping(address as raw::u16), Wrapping(*byte));
+    }
+    machine
+}
+
+/// Runs the machine for a while with changing input, raising an interrupt before each stretch.
+fn record() -> (Movie, SaveState) {
+    let mut machine = machine();
+    machine.run_for(50);
+    machine.record_movie(100);
+    for round in 0..20u8 {
+        machine.port_in[1] = Wrapping(round.wrapping_mul(37));
+        let _ = machine.reset_to(1);
+        machine.run_for(90);
+    }
+    let end = machine.save_state();
+    (machine.stop_movie().unwrap(), end)
+}
+
+#[test]
+fn replay() {
+    let (movie, end) = record();
+    assert!(movie.events.iter().any(|(_, record)| matches!(record, Record::Interrupt(Op::Reset{vector: 1}))));
+    assert!(movie.events.iter().any(|(_, record)| matches!(record, Record::Input{port: 1, value: 37})));
+    assert!(movie.hashes.len() > 10);
+    assert_eq!(movie.end, end.clock);
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie.clone()));
+    assert_eq!(Movie::from_bytes(b"L80S"), Err(Error::BadSignature));
+
+    let mut machine = machine();
+    machine.play_movie(movie.clone()).unwrap();
+    assert!(machine.is_playing());
+    machine.run_until(|machine| machine.clock() >= end.clock);
+    assert_eq!(machine.desync(), None);
+    assert_eq!(machine.save_state().chip, end.chip);
+    assert_eq!(machine.chip[Register::C].0, 20);
+    assert_eq!(machine.stop_movie(), Some(movie));
+}
+
+#[test]
+fn desync() {
+    let (movie, _) = record();
+    let mut machine = machine();
+    machine.play_movie(movie.clone()).unwrap();
+    assert!(matches!(machine.reset_to(2), Ok(false)));
+    assert!(matches!(machine.reset_to(1), Ok(true)));
+    machine.run_for(300);
+    assert_eq!(machine.desync(), None);
+    machine.write(Wrapping(0x80), Wrapping(1));
+    machine.run_for(200);
+    assert!(matches!(machine.desync(), Some(Desync::State{cycle}) if cycle > 300));
+
+    let mut tampered = movie.clone();
+    let first = tampered.events.iter().position(|(_, record)| matches!(record, Record::Input{..})).unwrap();
+    tampered.events[first].1 = Record::Input { port: 2, value: 0 };
+    machine.play_movie(tampered.clone()).unwrap();
+    machine.run_for(100);
+    assert_eq!(machine.desync(), Some(Desync::Input { cycle: tampered.events[first].0, port: 1 }));
+}
@@ -0,0 +1,119 @@
+//! Run loops that execute instructions until a budget of processor cycles is used up or a
+//! condition is met, so that a front end can run a machine a frame at a time without summing
+//! the cycles of each `execute` itself, and the events they run between instructions.
+//!
+//! A `Machine` keeps a clock of the processor cycles that have passed, and a schedule of
+//! actions to run at chosen times on that clock, once or periodically. An action gets the
+//! whole machine, so it can reach the Harness and its devices, raise an interrupt through
+//! `interrupt` or `reset_to`, or schedule more events. While the processor is halted, the run
+//! loops let the clock jump ahead to the next event, since only an event (or an interrupt
+//! recorded in a movie being played back) can wake it.
+
+use crate::prelude::*;
+
+mod observed;
+mod schedule;
+pub use schedule::Event;
+pub(crate) use schedule::Schedule;
+
+/// Why a run loop stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// `run_for` used up its budget of cycles.
+    Budget,
+    /// The processor halted, or was halted by the Harness.
+    Halted,
+    /// The processor couldn't go on; with the `"open"` feature, this carries the message the
+    /// Harness gave.
+    Error(String),
+    /// The condition given to `run_until` was met.
+    Breakpoint,
+}
+
+/// What happened during a run loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// The processor cycles that passed.
+    pub cycles: usize,
+    /// The instructions executed.
+    pub instructions: usize,
+    /// How many cycles past its budget `run_for` went, since it only stops between instructions.
+    pub overshoot: usize,
+    /// Why the loop stopped.
+    pub stop: Stop,
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Executes one instruction, returning the cycles it took, or why the processor can't go on.
+    fn proceed(&mut self) -> Result<usize, Stop> {
+        self.follow_tape();
+        if self.chip.is_stopped() { return Err(Stop::Halted); }
+        #[cfg(feature="open")]
+        return match self.execute_next() {
+            Ok(Some(cycles)) => Ok(cycles.get() as usize),
+            Ok(None) => Err(Stop::Halted),
+            Err(message) => Err(Stop::Error(message)),
+        };
+        #[cfg(not(feature="open"))]
+        return self.execute_next().map(|cycles| cycles.get() as usize).ok_or_else(|| Stop::Error(String::from("the processor failed")));
+    }
+
+    /// The cycle of the next event, or of the next interrupt of a movie being played back,
+    /// either of which can wake a halted processor.
+    fn wake(&self) -> Option<u64> { self.events.next().into_iter().chain(self.cue()).min() }
+
+    /// Executes instructions, with the events that come due between them, until `done` gives a
+    /// reason to stop after one of them, or the processor can't go on. A halted processor waits
+    /// for the next event, but no more than `limit` cycles into the loop.
+    pub(crate) fn drive(&mut self, limit: Option<usize>, mut done: impl FnMut(&Self, &Summary) -> Option<Stop>) -> Summary {
+        let mut summary = Summary { cycles: 0, instructions: 0, overshoot: 0, stop: Stop::Budget };
+        let start = self.clock;
+        loop {
+            summary.cycles = (self.clock - start) as usize;
+            if let Some(stop) = done(self, &summary) {
+                summary.stop = stop;
+                return summary;
+            }
+            self.service_events();
+            match self.proceed() {
+                Ok(_) => summary.instructions += 1,
+                // With interrupts disabled, nothing is bound to wake the processor, so only a
+                // budget is worth waiting out.
+                Err(Stop::Halted) if self.wake().is_some() && (limit.is_some() || self.chip.is_interrupt_ready()) => {
+                    let due = self.wake().unwrap_or(self.clock);
+                    let wait = due.saturating_sub(self.clock) as usize;
+                    let wait = limit.map_or(wait, |limit| wait.min(limit.saturating_sub((self.clock - start) as usize)));
+                    if wait == 0 {
+                        summary.stop = Stop::Halted;
+                        return summary;
+                    }
+                    self.clock += wait as u64;
+                }
+                Err(stop) => {
+                    summary.stop = stop;
+                    return summary;
+                }
+            }
+        }
+    }
+
+    /// Executes instructions until at least `budget` processor cycles have passed, or the
+    /// processor halts or fails. A halted processor waits for the next event within the
+    /// budget; with no events waiting, it stays halted, so the loop returns at once until an
+    /// interrupt wakes it.
+    pub fn run_for(&mut self, budget: usize) -> Summary {
+        let mut summary = self.drive(Some(budget), |_, summary| (summary.cycles >= budget).then_some(Stop::Budget));
+        if summary.stop == Stop::Budget { summary.overshoot = summary.cycles - budget; }
+        summary
+    }
+
+    /// Executes instructions until `condition` is true of the machine after one of them, or
+    /// the processor halts or fails. A halted processor waits for the next event, if any, as
+    /// long as it has interrupts enabled.
+    pub fn run_until(&mut self, mut condition: impl FnMut(&Self) -> bool) -> Summary {
+        self.drive(None, |machine, summary| (summary.instructions > 0 && condition(machine)).then_some(Stop::Breakpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests;
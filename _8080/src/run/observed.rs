@@ -0,0 +1,82 @@
+//! How an instruction reaches the Harness while a movie is recording or playing back, or the
+//! machine is under a debugger.
+
+use crate::prelude::*;
+use crate::chip::{dispatch, opcode::Op, OpOutcome};
+use crate::{debug::{Access, Hit, Id, Watches}, movie::Reel};
+use core::cell::Cell;
+
+/// A Harness that puts the input of the Harness it wraps on a reel, or takes it from there,
+/// and notes the first access a watchpoint watches for.
+struct Observed<'a, H: Harness + ?Sized> {
+    board: &'a mut H,
+    reel: Option<&'a mut Reel>,
+    /// The clock when the instruction started.
+    clock: u64,
+    watches: Option<&'a Watches>,
+    hit: Cell<Option<(Id, Hit)>>,
+}
+
+impl<H: Harness + ?Sized> Observed<'_, H> {
+    fn note(&self, access: Access, address: raw::u16, value: raw::u8) {
+        let Some(watches) = self.watches else { return };
+        if self.hit.get().is_none() {
+            self.hit.set(watches.check(access, address, value));
+        }
+    }
+}
+
+impl<H: Harness + ?Sized> Harness for Observed<'_, H> {
+    fn read(&self, from: u16) -> u8 {
+        let value = self.board.read(from);
+        self.note(Access::Read, from.0, value.0);
+        value
+    }
+    fn read_word(&self, from: u16) -> u16 {
+        let value = self.board.read_word(from);
+        let [low, high] = value.0.to_le_bytes();
+        self.note(Access::Read, from.0, low);
+        self.note(Access::Read, from.0.wrapping_add(1), high);
+        value
+    }
+    fn write(&mut self, to: u16, value: u8) {
+        self.board.write(to, value);
+        self.note(Access::Write, to.0, value.0);
+    }
+    fn write_word(&mut self, to: u16, value: u16) {
+        self.board.write_word(to, value);
+        let [low, high] = value.0.to_le_bytes();
+        self.note(Access::Write, to.0, low);
+        self.note(Access::Write, to.0.wrapping_add(1), high);
+    }
+    fn input(&mut self, port: raw::u8) -> u8 {
+        let mut value = self.board.input(port);
+        if let Some(reel) = self.reel.as_deref_mut() { value = Wrapping(reel.input(self.clock, port, value.0)); }
+        self.note(Access::Input, port as raw::u16, value.0);
+        value
+    }
+    fn output(&mut self, port: raw::u8, value: u8) {
+        self.board.output(port, value);
+        self.note(Access::Output, port as raw::u16, value.0);
+    }
+    fn bus_cycle(&mut self, cycle: crate::BusCycle) -> raw::u8 { self.board.bus_cycle(cycle) }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Executes an operation fetched from `at`, reporting its machine cycles if the machine is
+    /// cycle-accurate. While a movie is recording or playing, or watchpoints are set, the
+    /// Harness is reached through an `Observed` one, which keeps the movie's input and notes
+    /// the first access a watchpoint sees.
+    pub(crate) fn perform(&mut self, op: Op, at: u16) -> OpOutcome {
+        let (chip, timed) = (&mut self.chip, self.timed);
+        let board = self.board.borrow_mut();
+        if self.tape.is_none() && self.watches.is_none() { return dispatch(op, at, chip, board, timed); }
+        let mut observed = Observed {
+            board, reel: self.tape.as_deref_mut(), clock: self.clock, watches: self.watches.as_deref(), hit: Cell::new(None),
+        };
+        let outcome = dispatch(op, at, chip, &mut observed, timed);
+        let hit = observed.hit.get();
+        if let (Some(watches), Some(hit)) = (self.watches.as_deref_mut(), hit) { watches.hit.get_or_insert(hit); }
+        outcome
+    }
+}
@@ -0,0 +1,107 @@
+//! Events that run at chosen times on the machine's cycle clock.
+
+use crate::prelude::{*, vec::Vec};
+
+/// A handle to a scheduled event, for cancelling it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Event(u64);
+
+/// An event's action. It must be `Send`, so that a machine stays `Send` with events scheduled.
+type Action<H, C> = Box<dyn FnMut(&mut Machine<H, C>) + Send>;
+
+struct Entry<H: Harness + ?Sized, C: BorrowMut<H>> {
+    due: u64,
+    period: Option<u64>,
+    event: Event,
+    action: Action<H, C>,
+}
+
+/// The events waiting to run on a machine, in the order they will run.
+pub(crate) struct Schedule<H: Harness + ?Sized, C: BorrowMut<H>> {
+    entries: Vec<Entry<H, C>>,
+    issued: u64,
+    /// The event whose action is running, and whether it has been cancelled from inside it.
+    running: Option<(Event, bool)>,
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Schedule<H, C> {
+    pub(crate) fn new() -> Self { Self { entries: Vec::new(), issued: 0, running: None } }
+
+    /// Adds an entry after any that are due at the same time.
+    fn insert(&mut self, entry: Entry<H, C>) {
+        let at = self.entries.partition_point(|other| (other.due, other.event) < (entry.due, entry.event));
+        self.entries.insert(at, entry);
+    }
+
+    fn add(&mut self, due: u64, period: Option<u64>, action: Action<H, C>) -> Event {
+        self.issued += 1;
+        let event = Event(self.issued);
+        self.insert(Entry { due, period, event, action });
+        event
+    }
+
+    /// The time the next event is due, if any are waiting.
+    pub(crate) fn next(&self) -> Option<u64> { self.entries.first().map(|entry| entry.due) }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// The processor cycles that have passed since the machine was created, counting every
+    /// instruction executed, every interrupt accepted and every cycle spent halted.
+    pub fn clock(&self) -> u64 { self.clock }
+
+    /// Schedules `action` to run once, `delay` cycles from now.
+    pub fn schedule(&mut self, delay: u64, action: impl FnMut(&mut Self) + Send + 'static) -> Event {
+        let due = self.clock + delay;
+        self.events.add(due, None, Box::new(action))
+    }
+
+    /// Schedules `action` to run every `period` cycles (at least 1), starting `period` cycles
+    /// from now. A late run doesn't move the ones after it.
+    pub fn schedule_every(&mut self, period: u64, action: impl FnMut(&mut Self) + Send + 'static) -> Event {
+        let period = period.max(1);
+        let due = self.clock + period;
+        self.events.add(due, Some(period), Box::new(action))
+    }
+
+    /// Sets the clock, moving the events still waiting along with it so that each stays as far
+    /// off as it was; any that were already due stay due.
+    pub(crate) fn set_clock(&mut self, clock: u64) {
+        let now = self.clock;
+        for entry in &mut self.events.entries { entry.due = entry.due.saturating_sub(now) + clock; }
+        self.events.entries.sort_by_key(|entry| (entry.due, entry.event));
+        self.clock = clock;
+    }
+
+    /// Cancels an event, returning whether it was still waiting to run. An event can cancel
+    /// itself from inside its action.
+    pub fn cancel(&mut self, event: Event) -> bool {
+        if let Some((running, cancelled)) = &mut self.events.running {
+            if *running == event && !*cancelled {
+                *cancelled = true;
+                return true;
+            }
+        }
+        let before = self.events.entries.len();
+        self.events.entries.retain(|entry| entry.event != event);
+        self.events.entries.len() != before
+    }
+
+    /// Runs every event that is due, in the order they came due; events due at the same time run
+    /// in the order they were scheduled. The run loops call this between instructions, so a
+    /// front end that calls `execute` itself should do the same. Returns the number of actions run.
+    pub fn service_events(&mut self) -> usize {
+        let mut count = 0;
+        while self.events.next().is_some_and(|due| due <= self.clock) {
+            let mut entry = self.events.entries.remove(0);
+            self.events.running = Some((entry.event, false));
+            (entry.action)(self);
+            count += 1;
+            let cancelled = matches!(self.events.running.take(), Some((_, true)));
+            if let (Some(period), false) = (entry.period, cancelled) {
+                entry.due += period;
+                self.events.insert(entry);
+            }
+        }
+        count
+    }
+}
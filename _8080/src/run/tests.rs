@@ -0,0 +1,127 @@
+use super::*;
+use crate::{MemoryMap, SimpleBoard, assembler::assemble, chip::access::Register, vec::Vec};
+extern crate std;
+use std::sync::{Arc, Mutex};
+
+fn machine(source: &str) -> Machine<MemoryMap, MemoryMap> {
+    let mut memory = MemoryMap::new().ram(..);
+    memory.program(0, &assemble(source).unwrap().image);
+    Machine::new(memory)
+}
+
+#[test]
+fn budget() {
+    let mut machine = machine("
+LOOP:   INR     A
+        JMP     LOOP
+    ");
+    assert_eq!(machine.run_for(100), Summary { cycles: 105, instructions: 14, overshoot: 5, stop: Stop::Budget });
+    assert_eq!(machine.run_for(0), Summary { cycles: 0, instructions: 0, overshoot: 0, stop: Stop::Budget });
+    assert_eq!(machine.run_for(15).instructions, 2);
+}
+
+#[test]
+fn halt() {
+    let mut machine = machine("
+        MVI     A,1
+        HLT
+    ");
+    assert_eq!(machine.run_for(1000), Summary { cycles: 14, instructions: 2, overshoot: 0, stop: Stop::Halted });
+    assert_eq!(machine.run_for(1000), Summary { cycles: 0, instructions: 0, overshoot: 0, stop: Stop::Halted });
+    machine.start_at(0);
+    assert_eq!(machine.run_until(|_| false).stop, Stop::Halted);
+}
+
+#[test]
+fn condition() {
+    let mut machine = machine("
+        LXI     H,100H
+LOOP:   INR     M
+        JMP     LOOP
+    ");
+    let summary = machine.run_until(|machine| machine.read(Wrapping(0x100)).0 == 3);
+    assert_eq!(summary, Summary { cycles: 10 + 10 * 5, instructions: 6, overshoot: 0, stop: Stop::Breakpoint });
+    let summary = machine.run_until(|_| true);
+    assert_eq!((summary.instructions, summary.stop), (1, Stop::Breakpoint));
+}
+
+#[test]
+fn events() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut machine = machine("
+LOOP:   INR     A
+        JMP     LOOP
+    ");
+    let record = |name: &'static str| {
+        let log = log.clone();
+        move |machine: &mut Machine<MemoryMap, MemoryMap>| log.lock().unwrap().push((name, machine.clock()))
+    };
+    machine.schedule(20, record("once"));
+    let tick = machine.schedule_every(30, record("tick"));
+    let late = machine.schedule(40, record("late"));
+    machine.schedule(60, record("tie"));
+    assert!(machine.cancel(late));
+    assert!(!machine.cancel(late));
+    machine.run_for(100);
+    assert_eq!(machine.clock(), 105);
+    assert_eq!(*log.lock().unwrap(), [("once", 20), ("tick", 30), ("tick", 60), ("tie", 60), ("tick", 90)]);
+    assert!(machine.cancel(tick));
+    machine.run_for(100);
+    assert_eq!(log.lock().unwrap().len(), 5);
+
+    let handle = Arc::new(Mutex::new(None));
+    let runs = Arc::new(Mutex::new(0));
+    let (inner, counter) = (handle.clone(), runs.clone());
+    let event = machine.schedule_every(5, move |machine| {
+        let mut count = counter.lock().unwrap();
+        *count += 1;
+        if *count == 3 { assert!(machine.cancel(inner.lock().unwrap().unwrap())); }
+    });
+    *handle.lock().unwrap() = Some(event);
+    machine.run_for(100);
+    assert_eq!(*runs.lock().unwrap(), 3);
+    assert!(!machine.cancel(event));
+}
+
+#[test]
+fn waiting() {
+    let mut machine = machine("
+        JMP     START
+        ORG     8
+        INR     B
+        RET
+START:  LXI     SP,100H
+LOOP:   EI
+        HLT
+        JMP     LOOP
+    ");
+    let wake = machine.schedule_every(100, |machine| { let _ = machine.reset_to(1); });
+    let summary = machine.run_for(1000);
+    assert_eq!((summary.cycles, summary.stop), (1000, Stop::Budget));
+    assert_eq!(machine.clock(), 1000);
+    assert_eq!(machine.chip[Register::B].0, 9);
+    let summary = machine.run_until(|machine| machine.chip[Register::B].0 == 12);
+    assert_eq!((machine.clock(), summary.stop), (1216, Stop::Breakpoint));
+    machine.cancel(wake);
+    let summary = machine.run_until(|_| false);
+    assert_eq!(summary.stop, Stop::Halted);
+    assert_eq!(machine.clock(), 1216 + 10 + 10 + 4 + 7);
+}
+
+#[test]
+fn send() {
+    fn send<T: Send>() {}
+    send::<Machine<SimpleBoard, SimpleBoard>>();
+    send::<Machine<MemoryMap, MemoryMap>>();
+}
+
+#[test]
+fn stuck() {
+    let mut machine = machine("
+        DI
+        HLT
+    ");
+    machine.schedule_every(50, |_| ());
+    assert_eq!(machine.run_until(|_| false), Summary { cycles: 11, instructions: 2, overshoot: 0, stop: Stop::Halted });
+    assert_eq!((machine.run_for(200).cycles, machine.clock()), (200, 211));
+}
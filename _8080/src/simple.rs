@@ -38,6 +38,19 @@ impl crate::Harness for SimpleBoard {
     }
 }
 
+impl crate::Snapshot for SimpleBoard {
+    fn save(&self, out: &mut crate::vec::Vec<raw::u8>) {
+        self.ram.save(out);
+        self.port_out.save(out);
+        self.port_in.save(out);
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), crate::snapshot::Error> {
+        self.ram.restore(data)?;
+        self.port_out.restore(data)?;
+        self.port_in.restore(data)
+    }
+}
+
 impl Index<raw::u16> for SimpleBoard {
     type Output = u8;
     fn index(&self, index: raw::u16) -> &Self::Output { &self.ram[index as usize] }
@@ -0,0 +1,261 @@
+//! Save-states, which capture a machine so that it can be put back exactly as it was.
+//!
+//! A `SaveState` holds the processor's registers and flags, the machine's cycle clock and the
+//! state of its Harness. The processor's part is always available, but the Harness's is opt-in:
+//! a Harness that implements `Snapshot` says what of its state (memory, latches, devices) to
+//! keep and how to put it back, and only a `Machine` whose Harness does can be saved with
+//! `save_state` and restored with `load_state`. Snapshots hold state, not configuration: a
+//! Harness is restored into one built the same way, with the same memory layout and devices,
+//! and anything it can't save (streams, callbacks, the events scheduled on the machine) stays
+//! as it was.
+//!
+//! `SaveState::to_bytes` writes a save-state in a compact binary format, which starts with a
+//! signature and a version number so that later versions of the crate can still read it. With
+//! the `"serde"` feature, a `SaveState` can also go through any serde format.
+//!
+//! A `Rewind` buffer keeps a machine's recent save-states, compressed against each other, so
+//! that it can be stepped backwards.
+
+use crate::prelude::{*, vec::Vec};
+
+pub mod rewind;
+pub use rewind::Rewind;
+
+/// The signature at the start of a save-state written by `SaveState::to_bytes`.
+const SIGNATURE: [raw::u8; 4] = *b"L80S";
+/// The version of the save-state format this version of the crate writes.
+pub const VERSION: raw::u16 = 1;
+
+/// The ways restoring a snapshot, or reading a recording such as a movie or a trace, can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The data doesn't start with the signature of a save-state.
+    NotSnapshot,
+    /// The data doesn't start with the signature of the kind of recording being read.
+    BadSignature,
+    /// The data was written in a version of its format that this one can't read.
+    Version(raw::u16),
+    /// The data ended before the snapshot did.
+    Truncated,
+    /// The data doesn't fit what it is being restored into, such as memory of a different size,
+    /// or holds a value that isn't allowed there.
+    Mismatch,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::NotSnapshot => write!(f, "not a save-state"),
+            Error::BadSignature => write!(f, "unknown signature"),
+            Error::Version(version) => write!(f, "format version {version} is not supported"),
+            Error::Truncated => write!(f, "data ends early"),
+            Error::Mismatch => write!(f, "data doesn't fit what it is restored into"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A value whose state can be saved as bytes and restored into a value built the same way.
+///
+/// This is implemented for the integers, `bool`, `Wrapping`, `Option`, pairs, arrays, boxed
+/// slices and `Vec`s of them, so a Harness can usually save and restore its fields one after another.
+pub trait Snapshot {
+    /// Appends the state to `out`.
+    fn save(&self, out: &mut Vec<raw::u8>);
+
+    /// Restores the state from the start of `data`, and moves `data` past it.
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error>;
+}
+
+/// Takes `count` bytes from the start of `data`.
+fn take<'a>(data: &mut &'a [raw::u8], count: usize) -> Result<&'a [raw::u8], Error> {
+    if data.len() < count { return Err(Error::Truncated); }
+    let (taken, rest) = data.split_at(count);
+    *data = rest;
+    Ok(taken)
+}
+
+macro_rules! integer {
+    ($($type:ty),*) => {$(
+        impl Snapshot for $type {
+            fn save(&self, out: &mut Vec<raw::u8>) { out.extend_from_slice(&self.to_le_bytes()); }
+            fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+                let bytes = take(data, core::mem::size_of::<$type>())?;
+                *self = <$type>::from_le_bytes(bytes.try_into().map_err(|_| Error::Truncated)?);
+                Ok(())
+            }
+        }
+    )*};
+}
+
+integer!(raw::u8, raw::u16, u32, u64);
+
+/// Saved as 64 bits, whatever the size on the host.
+impl Snapshot for usize {
+    fn save(&self, out: &mut Vec<raw::u8>) { (*self as u64).save(out) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        let mut value = 0u64;
+        value.restore(data)?;
+        *self = value.try_into().map_err(|_| Error::Mismatch)?;
+        Ok(())
+    }
+}
+
+impl Snapshot for bool {
+    fn save(&self, out: &mut Vec<raw::u8>) { out.push(*self as raw::u8) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        *self = match take(data, 1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::Mismatch),
+        };
+        Ok(())
+    }
+}
+
+impl<T: Snapshot> Snapshot for Wrapping<T> {
+    fn save(&self, out: &mut Vec<raw::u8>) { self.0.save(out) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> { self.0.restore(data) }
+}
+
+impl<A: Snapshot, B: Snapshot> Snapshot for (A, B) {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.0.save(out);
+        self.1.save(out);
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        self.0.restore(data)?;
+        self.1.restore(data)
+    }
+}
+
+impl<T: Snapshot + Default> Snapshot for Option<T> {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.is_some().save(out);
+        if let Some(value) = self { value.save(out); }
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        let mut present = false;
+        present.restore(data)?;
+        *self = match present {
+            true => {
+                let mut value = T::default();
+                value.restore(data)?;
+                Some(value)
+            }
+            false => None,
+        };
+        Ok(())
+    }
+}
+
+/// Saves the elements one after another; the number of them isn't saved, since it is fixed.
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn save(&self, out: &mut Vec<raw::u8>) { self.as_slice().iter().for_each(|item| item.save(out)) }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        self.as_mut_slice().iter_mut().try_for_each(|item| item.restore(data))
+    }
+}
+
+/// Saves the length and then the elements. The length is fixed, so restoring data of another
+/// length fails.
+impl<T: Snapshot> Snapshot for Box<[T]> {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.len().save(out);
+        self.iter().for_each(|item| item.save(out));
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        let mut len = 0usize;
+        len.restore(data)?;
+        if len != self.len() { return Err(Error::Mismatch); }
+        self.iter_mut().try_for_each(|item| item.restore(data))
+    }
+}
+
+/// Saves the length and then the elements, and restores to the saved length.
+impl<T: Snapshot + Default> Snapshot for Vec<T> {
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.len().save(out);
+        self.iter().for_each(|item| item.save(out));
+    }
+    fn restore(&mut self, data: &mut &[raw::u8]) -> Result<(), Error> {
+        let mut len = 0usize;
+        len.restore(data)?;
+        if len > data.len() { return Err(Error::Truncated); }
+        self.clear();
+        self.resize_with(len, T::default);
+        self.iter_mut().try_for_each(|item| item.restore(data))
+    }
+}
+
+/// Kept apart from the prelude, whose `u8` would be taken for the primitive in the code that
+/// serde derives.
+mod saved {
+    use crate::{chip::State, vec::Vec};
+
+    /// A saved machine.
+    #[derive(Debug, Clone, PartialEq)]
+    #[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct SaveState {
+        /// The version of the format the Harness's state was saved in.
+        pub version: u16,
+        /// The processor's registers and flags, and whether it is halted and accepting interrupts.
+        pub chip: State,
+        /// The machine's cycle clock.
+        pub clock: u64,
+        /// The state of the Harness, as its `Snapshot` implementation saved it.
+        pub board: Vec<u8>,
+    }
+}
+pub use saved::SaveState;
+
+impl SaveState {
+    /// Writes the save-state in the crate's binary format.
+    pub fn to_bytes(&self) -> Vec<raw::u8> {
+        let mut out = Vec::from(SIGNATURE);
+        self.version.save(&mut out);
+        self.chip.save(&mut out);
+        self.clock.save(&mut out);
+        out.extend_from_slice(&self.board);
+        out
+    }
+
+    /// Reads a save-state written by `to_bytes`, in this version of the format or an earlier one.
+    pub fn from_bytes(mut data: &[raw::u8]) -> Result<Self, Error> {
+        if !data.starts_with(&SIGNATURE) { return Err(Error::NotSnapshot); }
+        data = &data[SIGNATURE.len()..];
+        let mut state = Self { version: 0, chip: State::new(), clock: 0, board: Vec::new() };
+        state.version.restore(&mut data)?;
+        if !(1..=VERSION).contains(&state.version) { return Err(Error::Version(state.version)); }
+        state.chip.restore(&mut data)?;
+        state.clock.restore(&mut data)?;
+        state.board = data.to_vec();
+        Ok(state)
+    }
+}
+
+impl<H: Harness + Snapshot + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Saves the processor, the cycle clock and the Harness.
+    pub fn save_state(&self) -> SaveState {
+        let mut board = Vec::new();
+        (**self).save(&mut board);
+        SaveState { version: VERSION, chip: self.chip.clone(), clock: self.clock, board }
+    }
+
+    /// Restores a save-state into the machine. Scheduled events aren't part of a save-state, so
+    /// those still waiting keep as many cycles to go as they had. If the Harness can't take its
+    /// part, the processor and clock are left alone, but the Harness may have been partly restored.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), Error> {
+        if !(1..=VERSION).contains(&state.version) { return Err(Error::Version(state.version)); }
+        let mut data = state.board.as_slice();
+        (**self).restore(&mut data)?;
+        if !data.is_empty() { return Err(Error::Mismatch); }
+        self.chip = state.chip.clone();
+        self.set_clock(state.clock);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
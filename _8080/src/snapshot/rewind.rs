@@ -0,0 +1,232 @@
+//! A rewind buffer, which keeps the recent history of a machine as save-states so that it can
+//! be stepped backwards.
+//!
+//! Most of a save-state is the Harness's memory, and little of it changes from one to the next,
+//! so the buffer doesn't keep whole save-states. Every so often it keeps a keyframe, and in
+//! between it keeps each save-state as the bytes that differ from the last keyframe: the two
+//! are XORed together, which leaves zeros wherever they match, and the result is run-length
+//! encoded. Keyframes are run-length encoded the same way, which pays off for memory that is
+//! mostly empty. When the buffer is full, recording a save-state drops the oldest; if the one
+//! after it isn't a keyframe, it becomes one, and the others that were encoded against the
+//! dropped keyframe are encoded against it instead.
+
+use crate::prelude::{*, vec::Vec, collections::VecDeque};
+use super::{Error, SaveState, Snapshot};
+
+/// How often `Rewind::record` keeps a save-state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    /// Once the machine's clock has moved on this many cycles since the last one.
+    Cycles(u64),
+    /// Once in this many calls, for a front end that calls it once a frame.
+    Frames(usize),
+}
+
+/// A save-state as the buffer keeps it.
+#[derive(Debug, Clone)]
+struct Entry {
+    version: raw::u16,
+    chip: State,
+    clock: u64,
+    /// Whether `runs` encodes the Harness's state by itself rather than against a keyframe.
+    key: bool,
+    /// The length of the Harness's state.
+    len: usize,
+    runs: Vec<raw::u8>,
+}
+
+fn put_number(out: &mut Vec<raw::u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as raw::u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as raw::u8);
+}
+
+fn take_number(data: &mut &[raw::u8]) -> usize {
+    let (mut value, mut shift) = (0, 0);
+    while let Some((&byte, rest)) = data.split_first() {
+        *data = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 { break; }
+    }
+    value
+}
+
+/// Run-length encodes `state` XORed with `base`, as alternating counts of matching bytes and
+/// of differing bytes, each of the latter followed by the XORed bytes themselves.
+fn encode(state: &[raw::u8], base: &[raw::u8]) -> Vec<raw::u8> {
+    let diff = |index: usize| state[index] ^ base.get(index).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    let mut index = 0;
+    while index < state.len() {
+        let start = index;
+        while index < state.len() && diff(index) == 0 { index += 1; }
+        put_number(&mut out, index - start);
+        let start = index;
+        while index < state.len() && (diff(index) != 0 || index + 1 < state.len() && diff(index + 1) != 0) { index += 1; }
+        put_number(&mut out, index - start);
+        out.extend((start..index).map(diff));
+    }
+    out
+}
+
+/// Undoes `encode`, giving back a state of `len` bytes.
+fn decode(mut runs: &[raw::u8], len: usize, base: &[raw::u8]) -> Vec<raw::u8> {
+    let mut state: Vec<raw::u8> = (0..len).map(|index| base.get(index).copied().unwrap_or(0)).collect();
+    let mut index = 0;
+    while !runs.is_empty() && index < len {
+        index += take_number(&mut runs);
+        let count = take_number(&mut runs).min(runs.len());
+        let (bytes, rest) = runs.split_at(count);
+        for (cell, byte) in state.iter_mut().skip(index).zip(bytes) { *cell ^= byte; }
+        index += count;
+        runs = rest;
+    }
+    state
+}
+
+/// A ring buffer of the recent save-states of a machine, for stepping it backwards.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    interval: Interval,
+    keyframes: usize,
+    /// The Harness's state in the newest keyframe, decoded.
+    key: Vec<raw::u8>,
+    /// The save-states kept since the newest keyframe, and the calls to `record` and the clock
+    /// when the last one was kept.
+    since_key: usize,
+    calls: usize,
+    last: Option<u64>,
+}
+
+impl Rewind {
+    /// Creates a buffer that keeps up to `capacity` save-states (at least 1), recorded as often
+    /// as `interval` says, with a keyframe every 16 of them.
+    pub fn new(capacity: usize, interval: Interval) -> Self {
+        Self {
+            entries: VecDeque::new(), capacity: capacity.max(1), interval, keyframes: 16, key: Vec::new(),
+            since_key: 0, calls: 0, last: None,
+        }
+    }
+
+    /// Keeps a keyframe every `every` save-states (at least 1) instead. More keyframes take
+    /// more memory, but fewer make the deltas between them grow.
+    pub fn keyframes(mut self, every: usize) -> Self {
+        self.keyframes = every.max(1);
+        self
+    }
+
+    /// The number of save-states kept.
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    /// Whether no save-states are kept.
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    /// The bytes of Harness state the buffer holds, after compression.
+    pub fn stored(&self) -> usize { self.entries.iter().map(|entry| entry.runs.len()).sum() }
+
+    /// Forgets every save-state kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.key.clear();
+        (self.since_key, self.calls, self.last) = (0, 0, None);
+    }
+
+    /// Keeps a save-state, whether or not one is due, dropping the oldest if the buffer is full.
+    pub fn push(&mut self, state: &SaveState) {
+        let key = self.entries.is_empty() || self.since_key + 1 >= self.keyframes;
+        let runs = encode(&state.board, if key { &[] } else { &self.key });
+        if key {
+            self.key = state.board.clone();
+            self.since_key = 0;
+        } else {
+            self.since_key += 1;
+        }
+        self.entries.push_back(Entry {
+            version: state.version, chip: state.chip.clone(), clock: state.clock, key, len: state.board.len(), runs,
+        });
+        self.last = Some(state.clock);
+        while self.entries.len() > self.capacity {
+            let Some(dropped) = self.entries.pop_front() else { break };
+            self.promote(&decode(&dropped.runs, dropped.len, &[]));
+        }
+    }
+
+    /// Makes the oldest save-state a keyframe, after the keyframe before it, whose Harness state
+    /// was `base`, has been dropped, and encodes the ones that depended on that against it.
+    fn promote(&mut self, base: &[raw::u8]) {
+        let group = self.entries.iter().take_while(|entry| !entry.key).count();
+        if group == 0 { return; }
+        let boards: Vec<_> = self.entries.range(..group).map(|entry| decode(&entry.runs, entry.len, base)).collect();
+        for (index, board) in boards.iter().enumerate() {
+            let entry = &mut self.entries[index];
+            entry.key = index == 0;
+            entry.runs = encode(board, if index == 0 { &[] } else { &boards[0] });
+        }
+        if group == self.entries.len() {
+            self.since_key = group - 1;
+            self.key = boards.into_iter().next().unwrap_or_default();
+        }
+    }
+
+    /// The save-state `back` steps before the newest (which is 0), if the buffer holds one
+    /// that far back.
+    pub fn get(&self, back: usize) -> Option<SaveState> {
+        let index = self.entries.len().checked_sub(back + 1)?;
+        let entry = &self.entries[index];
+        let board = match entry.key {
+            true => decode(&entry.runs, entry.len, &[]),
+            false => {
+                let key = self.entries.range(..index).rfind(|entry| entry.key)?;
+                decode(&entry.runs, entry.len, &decode(&key.runs, key.len, &[]))
+            }
+        };
+        Some(SaveState { version: entry.version, chip: entry.chip.clone(), clock: entry.clock, board })
+    }
+
+    /// Removes the newest save-state and returns it.
+    pub fn pop(&mut self) -> Option<SaveState> {
+        let state = self.get(0)?;
+        self.entries.pop_back();
+        match self.entries.iter().rposition(|entry| entry.key) {
+            Some(index) => {
+                self.key = decode(&self.entries[index].runs, self.entries[index].len, &[]);
+                self.since_key = self.entries.len() - 1 - index;
+            }
+            None => self.clear(),
+        }
+        self.last = Some(state.clock);
+        self.calls = 0;
+        Some(state)
+    }
+
+    /// Keeps a save-state of the machine if one is due, returning whether it did. This is meant
+    /// to be called regularly, such as once a frame; the first call always keeps one.
+    pub fn record<H: Harness + Snapshot + ?Sized, C: BorrowMut<H>>(&mut self, machine: &Machine<H, C>) -> bool {
+        self.calls += 1;
+        let due = match (self.interval, self.last) {
+            (_, None) => true,
+            (Interval::Cycles(cycles), Some(last)) => machine.clock().saturating_sub(last) >= cycles || machine.clock() < last,
+            (Interval::Frames(frames), Some(_)) => self.calls >= frames,
+        };
+        if due {
+            self.push(&machine.save_state());
+            self.calls = 0;
+        }
+        due
+    }
+
+    /// Steps the machine back to the newest save-state and forgets it, so that calling this
+    /// repeatedly goes further back each time. Returns `Ok(false)` if the buffer is empty; if
+    /// the machine can't take the save-state, it is kept.
+    pub fn step_back<H: Harness + Snapshot + ?Sized, C: BorrowMut<H>>(&mut self, machine: &mut Machine<H, C>) -> Result<bool, Error> {
+        let Some(state) = self.get(0) else { return Ok(false) };
+        machine.load_state(&state)?;
+        self.pop();
+        Ok(true)
+    }
+}
@@ -0,0 +1,164 @@
+use super::*;
+use crate::{MemoryMap, PortBus, assembler::assemble, device::{I8253, I8255, I8259}};
+extern crate std;
+use std::sync::{Arc, Mutex};
+
+fn machine() -> Machine<MemoryMap, MemoryMap> {
+    let mut memory = MemoryMap::new().ram(..0x8000).banked(0x8000..0x9000, 2, Some(0x07));
+    memory.program(0, &assemble("
+        LXI     SP,100H
+        MVI     A,1
+        OUT     7
+        LXI     H,8000H
+LOOP:   INR     M
+        INX     B
+        JMP     LOOP
+    ").unwrap().image);
+    Machine::new(memory)
+}
+
+#[test]
+fn round_trip() {
+    let mut machine = machine();
+    machine.run_for(200);
+    let state = machine.save_state();
+    assert_eq!(SaveState::from_bytes(&state.to_bytes()), Ok(state.clone()));
+
+    machine.run_for(300);
+    let later = (machine.chip.clone(), machine.clock(), machine.read(Wrapping(0x8000)).0);
+    machine.select(0x8000, 0);
+    machine.write(Wrapping(0x8000), Wrapping(0));
+    machine.load_state(&state).unwrap();
+    assert_eq!((&machine.chip, machine.clock()), (&state.chip, state.clock));
+    assert_eq!(machine.bank(0x8000), Some(1));
+    machine.run_for(300);
+    assert_eq!((machine.chip.clone(), machine.clock(), machine.read(Wrapping(0x8000)).0), later);
+}
+
+#[test]
+fn events() {
+    let mut machine = machine();
+    machine.run_for(200);
+    let state = machine.save_state();
+    machine.run_for(1000);
+    let fired = Arc::new(Mutex::new(Vec::new()));
+    let log = fired.clone();
+    machine.schedule(100, move |machine| log.lock().unwrap().push(machine.clock()));
+    machine.load_state(&state).unwrap();
+    machine.run_for(150);
+    let fired = fired.lock().unwrap();
+    assert_eq!(fired.len(), 1);
+    assert!((state.clock + 100..state.clock + 118).contains(&fired[0]));
+}
+
+#[test]
+fn format() {
+    let mut machine = machine();
+    machine.run_for(100);
+    let bytes = machine.save_state().to_bytes();
+    assert_eq!(SaveState::from_bytes(b"L80"), Err(Error::NotSnapshot));
+    let mut future = bytes.clone();
+    future[4..6].copy_from_slice(&99u16.to_le_bytes());
+    assert_eq!(SaveState::from_bytes(&future), Err(Error::Version(99)));
+    assert_eq!(SaveState::from_bytes(&bytes[..10]), Err(Error::Truncated));
+
+    let state = SaveState::from_bytes(&bytes).unwrap();
+    let mut smaller = Machine::new(MemoryMap::new().ram(..0x4000));
+    assert_eq!(smaller.load_state(&state), Err(Error::Mismatch));
+    let mut unbanked = Machine::new(MemoryMap::new().ram(..0x8000));
+    assert_eq!(unbanked.load_state(&state), Err(Error::Mismatch));
+    assert_eq!(unbanked.clock(), 0);
+}
+
+#[test]
+fn devices() {
+    let mut bus = PortBus::new(MemoryMap::new().ram(..))
+        .attach(0x40, 0xFC, I8253::new(1))
+        .attach(0x20, 0xFE, I8259::new())
+        .attach(0x30, 0xFC, I8255::new());
+    bus.output(0x43, Wrapping(0x34));
+    bus.output(0x40, Wrapping(0x00));
+    bus.output(0x40, Wrapping(0x10));
+    bus.output(0x20, Wrapping(0x16));
+    bus.output(0x21, Wrapping(0x40));
+    bus.output(0x21, Wrapping(0xF0));
+    bus.output(0x33, Wrapping(0x80));
+    bus.output(0x30, Wrapping(0x5A));
+    bus.tick(5);
+    let mut saved = Vec::new();
+    bus.save(&mut saved);
+
+    let read = |bus: &mut PortBus<MemoryMap>| {
+        bus.output(0x43, Wrapping(0x00));
+        let count = [bus.input(0x40).0, bus.input(0x40).0];
+        (count, bus.input(0x21).0, bus.input(0x30).0)
+    };
+    bus.tick(100);
+    let ticked = read(&mut bus);
+    bus.output(0x21, Wrapping(0x0F));
+    bus.output(0x30, Wrapping(0x00));
+    bus.restore(&mut saved.as_slice()).unwrap();
+    bus.tick(100);
+    assert_eq!(read(&mut bus), ticked);
+    assert_eq!(ticked.1, 0xF0);
+    assert_eq!(ticked.2, 0x5A);
+}
+
+#[cfg(feature="std")]
+#[test]
+fn serial() {
+    use crate::device::{I8251, Mc6850};
+    let mut bus = PortBus::new(MemoryMap::new().ram(..))
+        .attach(0x10, 0xFE, I8251::new(&b"ab"[..], Vec::new(), 1))
+        .attach(0x50, 0xFE, Mc6850::new(&b"xyz"[..], Vec::new(), 1));
+    bus.output(0x11, Wrapping(0x4D));
+    bus.output(0x11, Wrapping(0x05));
+    bus.output(0x50, Wrapping(0x94));
+    bus.output(0x10, Wrapping(b'H'));
+    bus.output(0x51, Wrapping(b'H'));
+    bus.tick(5);
+    let mut saved = Vec::new();
+    bus.save(&mut saved);
+
+    let read = |bus: &mut PortBus<MemoryMap>| {
+        [bus.input(0x11).0, bus.input(0x10).0, bus.input(0x50).0, bus.input(0x51).0]
+    };
+    bus.tick(5);
+    let ticked = read(&mut bus);
+    bus.output(0x11, Wrapping(0x40));
+    bus.output(0x50, Wrapping(0x03));
+    bus.restore(&mut saved.as_slice()).unwrap();
+    bus.tick(5);
+    assert_eq!(read(&mut bus), ticked);
+    assert_eq!(ticked, [0x87, b'a', 0x83, b'x']);
+}
+
+#[test]
+fn rewind() {
+    let mut machine = machine();
+    let mut buffer = Rewind::new(8, rewind::Interval::Cycles(100)).keyframes(3);
+    let mut recorded = Vec::new();
+    for _ in 0..60 {
+        if buffer.record(&machine) { recorded.push(machine.save_state()); }
+        machine.run_for(40);
+    }
+    assert_eq!(buffer.len(), 8);
+    assert!(recorded.len() > 8);
+    assert!(buffer.stored() < 8 * 0x1000);
+    assert_eq!(buffer.get(3).as_ref(), recorded.iter().rev().nth(3));
+    for expected in recorded.iter().rev().take(8) {
+        assert_eq!(buffer.step_back(&mut machine), Ok(true));
+        assert_eq!(&machine.save_state(), expected);
+    }
+    assert_eq!(buffer.step_back(&mut machine), Ok(false));
+    assert!(buffer.is_empty());
+
+    buffer.push(&machine.save_state());
+    let mut smaller = Machine::new(MemoryMap::new().ram(..0x4000));
+    assert_eq!(buffer.step_back(&mut smaller), Err(Error::Mismatch));
+    assert_eq!(buffer.len(), 1);
+
+    let mut frames = Rewind::new(4, rewind::Interval::Frames(3));
+    let kept: Vec<_> = (0..7).map(|_| frames.record(&machine)).collect();
+    assert_eq!(kept, [true, false, false, true, false, false, true]);
+}
@@ -0,0 +1,319 @@
+//! Execution traces, which log every instruction a machine executes along with the state of
+//! the processor before it, for checking the emulation against another emulator's log.
+//!
+//! A machine keeps a trace while tracing is on, whichever way it is driven; it doesn't need the
+//! `"open"` feature. Each `Step` holds the address and bytes of the instruction, the registers
+//! and flags as they were when it was fetched, and the cycle clock at that point. Interrupts
+//! aren't steps of their own; the instruction an interrupt carries shows as a jump in the
+//! program counter between two steps.
+//!
+//! A trace can be written in a compact binary format, or as text with one step a line, such as
+//!
+//! ```text
+//! 0100  06 03     MVI B,03H                A=00 B=00 C=00 D=00 E=00 H=00 L=00 F=02 SP=0000 CYC=12
+//! ```
+//!
+//! Reading text is lenient, so that a log from another emulator can be brought into the same
+//! shape with little work: the first word is the address, up to three pairs of hexadecimal
+//! digits after it are the bytes of the instruction, and `KEY=value` words give the registers
+//! (in hexadecimal), the flags as `F` in the `PSW` layout and the clock as `CYC` (in decimal),
+//! in any order and any case. Anything else, such as the disassembly, is skipped, and the clock
+//! can be left out, in which case it isn't compared. `Trace::diverge` finds the first step where
+//! two traces differ.
+
+use crate::prelude::{*, vec::Vec, fmt::{Display, Formatter, Write}};
+use crate::chip::{access::{Register, Internal}, opcode::{Line, Op}};
+use crate::snapshot::{Error, Snapshot};
+
+/// The signature at the start of a trace written by `Trace::to_bytes`.
+const SIGNATURE: [raw::u8; 4] = *b"L80T";
+/// The version of the trace format this version of the crate writes.
+pub const VERSION: raw::u16 = 1;
+
+/// The registers in the order a step keeps them.
+const REGISTERS: [(Register, &str); 7] = [
+    (Register::A, "A"), (Register::B, "B"), (Register::C, "C"), (Register::D, "D"),
+    (Register::E, "E"), (Register::H, "H"), (Register::L, "L"),
+];
+
+/// One executed instruction, with the processor as it was when the instruction was fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Step {
+    /// The cycle clock, if the trace has it.
+    pub cycle: Option<u64>,
+    pub pc: raw::u16,
+    /// The bytes of the instruction, of which the first `len` count.
+    pub bytes: [raw::u8; 3],
+    pub len: raw::u8,
+    /// The A, B, C, D, E, H and L registers.
+    pub registers: [raw::u8; 7],
+    /// The flags in the layout of the `PSW` pseudo-register.
+    pub flags: raw::u8,
+    pub sp: raw::u16,
+}
+
+impl Step {
+    /// Captures the instruction `op`, fetched from `pc`, and the processor before it.
+    fn new(chip: &State, pc: raw::u16, op: Op, cycle: u64) -> Self {
+        let [len, bytes @ ..]: [raw::u8; 4] = op.into();
+        Step {
+            cycle: Some(cycle), pc, bytes, len,
+            registers: REGISTERS.map(|(register, _)| chip[register].0),
+            flags: chip.flags(), sp: chip[Internal::StackPointer].0,
+        }
+    }
+
+    /// The bytes of the instruction.
+    pub fn code(&self) -> &[raw::u8] { &self.bytes[..(self.len as usize).min(3)] }
+
+    /// The instruction, if its bytes make one.
+    pub fn op(&self) -> Option<Op> {
+        Op::extract(self.code().iter().copied().map(Wrapping)).ok().map(|(op, _)| op)
+    }
+
+    /// The names of the fields in which the steps differ, as the text format writes them
+    /// (`"PC"` and `"bytes"` for the instruction). The clocks are only compared if both steps
+    /// have one.
+    pub fn differences(&self, other: &Step) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.pc != other.pc { fields.push("PC"); }
+        if self.code() != other.code() { fields.push("bytes"); }
+        for (index, (_, name)) in REGISTERS.iter().enumerate() {
+            if self.registers[index] != other.registers[index] { fields.push(*name); }
+        }
+        if self.flags != other.flags { fields.push("F"); }
+        if self.sp != other.sp { fields.push("SP"); }
+        if let (Some(cycle), Some(other)) = (self.cycle, other.cycle) {
+            if cycle != other { fields.push("CYC"); }
+        }
+        fields
+    }
+
+    /// Reads a step from a line of text, or gives the name of the field that is missing or
+    /// can't be read.
+    pub fn parse(line: &str) -> Result<Self, &'static str> {
+        let mut words = line.split_whitespace().peekable();
+        let mut step = Step::default();
+        let first = words.next().ok_or("PC")?;
+        let first = match first.split_once('=') {
+            Some((key, value)) if key.eq_ignore_ascii_case("PC") => value,
+            _ => first,
+        };
+        step.pc = raw::u16::from_str_radix(first, 16).map_err(|_| "PC")?;
+        let mut bytes = Vec::new();
+        while let Some(byte) = words.peek().filter(|word| word.len() == 2).and_then(|word| raw::u8::from_str_radix(word, 16).ok()) {
+            if bytes.len() == 3 { break; }
+            bytes.push(byte);
+            words.next();
+        }
+        step.len = match Op::extract(bytes.iter().copied().map(Wrapping)) {
+            Ok((_, len)) => len as raw::u8,
+            Err(_) if bytes.is_empty() => return Err("bytes"),
+            Err(_) => 1,
+        };
+        step.bytes[..step.len as usize].copy_from_slice(&bytes[..step.len as usize]);
+        let (mut registers, mut flags, mut sp) = ([None; 7], None, None);
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else { continue };
+            let key = key.to_ascii_uppercase();
+            let byte = || raw::u8::from_str_radix(value, 16).ok();
+            match key.as_str() {
+                "F" => flags = Some(byte().ok_or("F")?),
+                "SP" => sp = Some(raw::u16::from_str_radix(value, 16).map_err(|_| "SP")?),
+                "CYC" => step.cycle = Some(value.parse().map_err(|_| "CYC")?),
+                _ => if let Some(index) = REGISTERS.iter().position(|(_, name)| *name == key) {
+                    registers[index] = Some(byte().ok_or(REGISTERS[index].1)?);
+                },
+            }
+        }
+        for (index, register) in registers.into_iter().enumerate() {
+            step.registers[index] = register.ok_or(REGISTERS[index].1)?;
+        }
+        step.flags = flags.ok_or("F")?;
+        step.sp = sp.ok_or("SP")?;
+        Ok(step)
+    }
+
+    fn save(&self, out: &mut Vec<raw::u8>) {
+        self.cycle.save(out);
+        self.pc.save(out);
+        self.len.save(out);
+        self.bytes.save(out);
+        self.registers.save(out);
+        self.flags.save(out);
+        self.sp.save(out);
+    }
+
+    fn restore(data: &mut &[raw::u8]) -> Result<Self, Error> {
+        let mut step = Step::default();
+        step.cycle.restore(data)?;
+        step.pc.restore(data)?;
+        step.len.restore(data)?;
+        step.bytes.restore(data)?;
+        step.registers.restore(data)?;
+        step.flags.restore(data)?;
+        step.sp.restore(data)?;
+        match step.len {
+            1..=3 => Ok(step),
+            _ => Err(Error::Mismatch),
+        }
+    }
+}
+
+/// Writes the step as a line of the text format, without the line break.
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut listing = String::new();
+        write!(listing, "{}", Line { address: self.pc, bytes: self.code(), op: self.op() })?;
+        write!(f, "{listing:<40}")?;
+        for (index, (_, name)) in REGISTERS.iter().enumerate() {
+            write!(f, " {name}={:02X}", self.registers[index])?;
+        }
+        write!(f, " F={:02X} SP={:04X}", self.flags, self.sp)?;
+        match self.cycle {
+            Some(cycle) => write!(f, " CYC={cycle}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A line of a text trace that couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line, counting from 1.
+    pub line: usize,
+    /// The field that was missing or couldn't be read.
+    pub field: &'static str,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} is missing or can't be read", self.line, self.field)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// The first step where two traces differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The position of the step in the traces.
+    pub index: usize,
+    /// The step in the trace that is taken as right, or `None` if it ended first.
+    pub expected: Option<Step>,
+    /// The step in the trace being checked, or `None` if it ended first.
+    pub found: Option<Step>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => {
+                writeln!(f, "step {} differs in {}", self.index, expected.differences(found).join(", "))?;
+                writeln!(f, "expected: {expected}")?;
+                write!(f, "found:    {found}")
+            }
+            (Some(expected), None) => write!(f, "step {}: trace ends, but expected {expected}", self.index),
+            (None, Some(found)) => write!(f, "step {}: expected the end of the trace, but found {found}", self.index),
+            (None, None) => write!(f, "step {}: traces match", self.index),
+        }
+    }
+}
+
+/// The steps a machine has executed, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Trace {
+    steps: Vec<Step>,
+}
+
+impl Trace {
+    /// Creates an empty trace.
+    pub fn new() -> Self { Self::default() }
+
+    /// The steps, oldest first.
+    pub fn steps(&self) -> &[Step] { &self.steps }
+
+    /// The number of steps.
+    pub fn len(&self) -> usize { self.steps.len() }
+
+    /// Whether the trace has no steps.
+    pub fn is_empty(&self) -> bool { self.steps.is_empty() }
+
+    /// Adds a step to the end of the trace.
+    pub fn push(&mut self, step: Step) { self.steps.push(step) }
+
+    /// Finds the first step where `other` differs from this trace, taking this one as right;
+    /// where one trace is a prefix of the other, it is the step past the end of the shorter.
+    pub fn diverge(&self, other: &Trace) -> Option<Divergence> {
+        let length = self.len().max(other.len());
+        (0..length).find_map(|index| {
+            let (expected, found) = (self.steps.get(index).copied(), other.steps.get(index).copied());
+            let same = match (&expected, &found) {
+                (Some(expected), Some(found)) => expected.differences(found).is_empty(),
+                _ => false,
+            };
+            (!same).then_some(Divergence { index, expected, found })
+        })
+    }
+
+    /// Writes the trace in the crate's binary format.
+    pub fn to_bytes(&self) -> Vec<raw::u8> {
+        let mut out = Vec::from(SIGNATURE);
+        VERSION.save(&mut out);
+        self.len().save(&mut out);
+        self.steps.iter().for_each(|step| step.save(&mut out));
+        out
+    }
+
+    /// Reads a trace written by `to_bytes`, in this version of the format or an earlier one.
+    pub fn from_bytes(mut data: &[raw::u8]) -> Result<Self, Error> {
+        if !data.starts_with(&SIGNATURE) { return Err(Error::BadSignature); }
+        data = &data[SIGNATURE.len()..];
+        let (mut version, mut count) = (0u16, 0usize);
+        version.restore(&mut data)?;
+        if !(1..=VERSION).contains(&version) { return Err(Error::Version(version)); }
+        count.restore(&mut data)?;
+        let steps = (0..count).map(|_| Step::restore(&mut data)).collect::<Result<Vec<_>, _>>()?;
+        match data.is_empty() {
+            true => Ok(Self { steps }),
+            false => Err(Error::Mismatch),
+        }
+    }
+
+    /// Reads a trace from text, one step a line; blank lines are skipped.
+    pub fn from_text(text: &str) -> Result<Self, ParseError> {
+        let steps = text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| Step::parse(line).map_err(|field| ParseError { line: index + 1, field }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { steps })
+    }
+}
+
+/// Writes the trace as text, one step a line.
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.steps.iter().try_for_each(|step| writeln!(f, "{step}"))
+    }
+}
+
+impl<H: Harness + ?Sized, C: BorrowMut<H>> Machine<H, C> {
+    /// Starts logging every instruction executed to a new trace, dropping any trace kept so far.
+    pub fn start_trace(&mut self) { self.trace = Some(Box::default()); }
+
+    /// Stops tracing and returns the trace.
+    pub fn stop_trace(&mut self) -> Option<Trace> { self.trace.take().map(|trace| *trace) }
+
+    /// The trace kept so far, if tracing is on.
+    pub fn trace(&self) -> Option<&Trace> { self.trace.as_deref() }
+
+    /// Logs the instruction `op`, fetched from `pc`, before it is executed.
+    pub(crate) fn log_step(&mut self, pc: raw::u16, op: Op) {
+        if let Some(trace) = self.trace.as_deref_mut() {
+            trace.push(Step::new(&self.chip, pc, op, self.clock));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
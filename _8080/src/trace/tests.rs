@@ -0,0 +1,74 @@
+use super::*;
+use crate::{MemoryMap, assembler::assemble, string::ToString};
+
+fn traced() -> Trace {
+    let mut memory = MemoryMap::new().ram(..);
+    memory.program(0, &assemble("
+        LXI     SP,100H
+        MVI     B,3
+LOOP:   INR     A
+        PUSH    PSW
+        POP     H
+        DCR     B
+        JNZ     LOOP
+        HLT
+    ").unwrap().image);
+    let mut machine = Machine::new(memory);
+    machine.start_trace();
+    let summary = machine.run_for(1000);
+    let trace = machine.stop_trace().unwrap();
+    assert_eq!(trace.len(), summary.instructions);
+    assert!(machine.trace().is_none());
+    trace
+}
+
+#[test]
+fn record() {
+    let trace = traced();
+    let steps = trace.steps();
+    assert_eq!(steps.len(), 2 + 3 * 5 + 1);
+    assert_eq!(steps[0], Step { cycle: Some(0), pc: 0, bytes: [0x31, 0x00, 0x01], len: 3, registers: [0; 7], flags: 0x02, sp: 0 });
+    assert_eq!((steps[1].pc, steps[1].sp, steps[1].cycle), (3, 0x100, Some(10)));
+    assert_eq!(steps[3].code(), [0xF5]);
+    assert_eq!(steps.last().unwrap().op(), Some(Op::Halt));
+    assert_eq!(steps[4].registers[0], 1);
+
+    let text = trace.to_string();
+    assert!(text.lines().next().unwrap().starts_with("0000  31 00 01  LXI SP,0100H"));
+    assert!(text.lines().next().unwrap().ends_with("A=00 B=00 C=00 D=00 E=00 H=00 L=00 F=02 SP=0000 CYC=0"));
+    assert_eq!(Trace::from_text(&text), Ok(trace.clone()));
+    assert_eq!(Trace::from_bytes(&trace.to_bytes()), Ok(trace.clone()));
+    assert_eq!(Trace::from_bytes(b"L80S"), Err(Error::BadSignature));
+}
+
+#[test]
+fn diverge() {
+    let golden = traced();
+    assert_eq!(golden.diverge(&golden), None);
+
+    let mut steps = golden.steps().to_vec();
+    steps[6].registers[1] ^= 1;
+    steps[6].flags ^= 1;
+    let changed = Trace { steps: steps.clone() };
+    let divergence = golden.diverge(&changed).unwrap();
+    assert_eq!(divergence.index, 6);
+    assert_eq!(divergence.expected.unwrap().differences(&divergence.found.unwrap()), ["B", "F"]);
+    assert!(divergence.to_string().starts_with("step 6 differs in B, F\nexpected: "));
+
+    steps.truncate(4);
+    let short = Trace { steps };
+    assert_eq!(golden.diverge(&short).map(|divergence| (divergence.index, divergence.found)), Some((4, None)));
+
+    let foreign = "
+        pc=0000 31 00 01 lxi sp,$100  a=00 b=00 c=00 d=00 e=00 h=00 l=00 f=02 sp=0000
+
+        0003 06 03  MVI B,3  SP=0100 F=02 A=00 B=00 C=00 D=00 E=00 H=00 L=00
+    ";
+    let foreign = Trace::from_text(foreign).unwrap();
+    assert_eq!(foreign.steps()[1].cycle, None);
+    assert_eq!(golden.diverge(&foreign).map(|divergence| divergence.index), Some(2));
+    assert_eq!(Trace::from_text("0000 00 A=00\n0001 00 A=00 B=00 C=00 D=00 E=00 H=00 L=00 F=02"),
+        Err(ParseError { line: 1, field: "B" }));
+    assert_eq!(Trace::from_text("0000 00 A=00 B=00 C=00 D=00 E=00 H=00 L=00 F=02 SP=0000\n0001 ZZ"),
+        Err(ParseError { line: 2, field: "bytes" }));
+}
@@ -1,6 +1,6 @@
 #![feature(generic_arg_infer)]
 
-use lemurs_8080::Machine;
+use lemurs_8080::{Machine, Stop};
 
 mod src {
     pub mod cp_m;
@@ -14,17 +14,11 @@ fn exercise() {
     println!("currently at {}", std::env::current_dir().unwrap().display());
     let body = std::fs::read("tests/cpudiag.bin").expect("Couldn't load test file.");
     let mut machine = CP_M::with_program(&body);
-    let sample: Machine<CP_M, _> = Machine::new(&mut machine);
-    let mut cycles = 0usize;
-    for outcome in sample {
-        match outcome {
-            Ok(duration) => cycles += usize::from(duration),
-            Err(txt) => {
-                panic!("Stopped without completing after {cycles} cycles.\n{txt}\n");
-            }
-        }
-    };
-//    sample.map(usize::from).sum();
+    let mut sample: Machine<CP_M, _> = Machine::new(&mut machine);
+    let summary = sample.run_until(|_| false);
+    if let Stop::Error(txt) = summary.stop {
+        panic!("Stopped without completing after {} cycles.\n{txt}\n", summary.cycles);
+    }
     println!("Completed successfully.");
-    println!("Total of {cycles} cycles executed.")
-}
\ No newline at end of file
+    println!("Total of {} cycles executed.", summary.cycles)
+}
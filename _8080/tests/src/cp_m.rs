@@ -1,4 +1,4 @@
-use lemurs_8080::{prelude::*, Op};
+use lemurs_8080::{prelude::*, image, Op};
 use std::collections::HashSet;
 
 #[allow(non_camel_case_types)]
@@ -11,7 +11,7 @@ pub struct CP_M {
 }
 
 impl CP_M {
-    pub fn with_program(mut code: &[u8]) -> Self {
+    pub fn with_program(code: &[u8]) -> Self {
         let mut new = Self {
             dead: 0,
             ram: [0;_],
@@ -20,10 +20,7 @@ impl CP_M {
             order: vec!()
         };
         [new.ram[0], new.ram[1], new.ram[2]] = [0xC3, 0x00, 0x01];
-        let mut ram = &mut new.ram[0x100..];
-        if ram.len() > code.len() { ram = &mut ram[..code.len()]; }
-        if code.len() > ram.len() { code = &code[..ram.len()]; }
-        ram.copy_from_slice(code);
+        image::binary::load(code, 0x0100, &mut new).expect("Program doesn't fit in memory.");
         new
     }
 }